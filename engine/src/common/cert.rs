@@ -1,5 +1,12 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use x509_parser::extensions::{GeneralName as X509GeneralName, ParsedExtension};
+use x509_parser::objects::{oid_registry, oid2sn};
+use x509_parser::oid_registry::OID_PKIX_ACCESS_DESCRIPTOR_OCSP;
+use x509_parser::prelude::{FromDer, X509Name};
+
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -38,64 +45,6 @@ impl Default for X509Certificate {
 
 impl X509Certificate {
   pub fn new() -> X509Certificate {
-    // X509Certificate {
-    //   public_key: value
-    //     .public_key()
-    //     .ok()
-    //     .map(|x| x.public_key_to_pem().unwrap_or_default()),
-    //   text: String::from_utf8_lossy(&value.to_text().unwrap_or_default()).to_string(),
-    //   pem: value.to_pem().unwrap_or_default(),
-    //   not_after: value.not_after().to_string(),
-    //   not_before: value.not_before().to_string(),
-    //   version: value.version(),
-    //   subject_name_hash: value.subject_name_hash(),
-    //   serial_number: value.serial_number().to_bn().ok().map(|x| x.to_string()),
-    //   ocsp_responders: value
-    //     .ocsp_responders()
-    //     .map_or(Vec::new(), |x| x.iter().map(|o| o.to_string()).collect()),
-    //   signature_algorithm: value.signature_algorithm().object().to_string(),
-    //   signature: value.signature().as_slice().to_vec(),
-    //   subject_alt_names: value.subject_alt_names().map(|x| {
-    //     x.into_iter()
-    //       .map(|g| GeneralName {
-    //         dns_name: g.dnsname().map(|d| d.to_string()),
-    //         email: g.email().map(|e| e.to_string()),
-    //         uri: g.uri().map(|u| u.to_string()),
-    //         ipaddress: g.ipaddress().map(|i| i.to_vec()),
-    //       })
-    //       .collect()
-    //   }),
-    //   issuer_alt_names: value.issuer_alt_names().map(|x| {
-    //     x.into_iter()
-    //       .map(|g| GeneralName {
-    //         dns_name: g.dnsname().map(|d| d.to_string()),
-    //         email: g.email().map(|e| e.to_string()),
-    //         uri: g.uri().map(|u| u.to_string()),
-    //         ipaddress: g.ipaddress().map(|i| i.to_vec()),
-    //       })
-    //       .collect()
-    //   }),
-    //   subject_name: value
-    //     .subject_name()
-    //     .entries()
-    //     .map(|e| {
-    //       (
-    //         kebab_case(&e.object().to_string()),
-    //         String::from_utf8_lossy(e.data().as_slice()).to_string(),
-    //       )
-    //     })
-    //     .collect(),
-    //   issuer_name: value
-    //     .issuer_name()
-    //     .entries()
-    //     .map(|e| {
-    //       (
-    //         kebab_case(&e.object().to_string()),
-    //         String::from_utf8_lossy(e.data().as_slice()).to_string(),
-    //       )
-    //     })
-    //     .collect(),
-    // }
     X509Certificate {
       text: "".to_string(),
       pem: vec![],
@@ -114,19 +63,196 @@ impl X509Certificate {
       version: 0,
     }
   }
+  /// 解析DER编码的证书，解析失败返回None
+  pub fn from_der(der: &[u8]) -> Option<X509Certificate> {
+    let (_, cert) = x509_parser::certificate::X509Certificate::from_der(der).ok()?;
+    let mut subject_alt_names = None;
+    let mut issuer_alt_names = None;
+    let mut ocsp_responders = Vec::new();
+    for extension in cert.extensions() {
+      match extension.parsed_extension() {
+        ParsedExtension::SubjectAlternativeName(san) => {
+          subject_alt_names = Some(san.general_names.iter().map(general_name).collect());
+        }
+        ParsedExtension::IssuerAlternativeName(ian) => {
+          issuer_alt_names = Some(ian.general_names.iter().map(general_name).collect());
+        }
+        ParsedExtension::AuthorityInfoAccess(aia) => {
+          for access in aia.iter() {
+            if access.access_method == OID_PKIX_ACCESS_DESCRIPTOR_OCSP
+              && let X509GeneralName::URI(uri) = access.access_location
+            {
+              ocsp_responders.push(uri.to_string());
+            }
+          }
+        }
+        _ => {}
+      }
+    }
+    let validity = cert.validity();
+    let not_before = validity
+      .not_before
+      .to_rfc2822()
+      .unwrap_or_else(|_| validity.not_before.to_string());
+    let not_after = validity
+      .not_after
+      .to_rfc2822()
+      .unwrap_or_else(|_| validity.not_after.to_string());
+    let signature_algorithm = oid2sn(&cert.signature_algorithm.algorithm, oid_registry())
+      .map(|name| name.to_string())
+      .unwrap_or_else(|_| cert.signature_algorithm.algorithm.to_id_string());
+    let text = format!(
+      "Subject: {}\nIssuer: {}\nNot Before: {not_before}\nNot After: {not_after}\nSignature Algorithm: {signature_algorithm}",
+      cert.subject(),
+      cert.issuer()
+    );
+    Some(X509Certificate {
+      text,
+      pem: to_pem(der).into_bytes(),
+      public_key: Some(cert.public_key().raw.to_vec()),
+      subject_name: name_entries(cert.subject()),
+      issuer_name: name_entries(cert.issuer()),
+      subject_alt_names,
+      issuer_alt_names,
+      subject_name_hash: subject_name_hash(cert.subject().as_raw()),
+      signature: cert.signature_value.data.to_vec(),
+      signature_algorithm,
+      ocsp_responders,
+      serial_number: Some(cert.raw_serial_as_string()),
+      not_after,
+      not_before,
+      version: cert.version().0 as i32,
+    })
+  }
+  pub fn subject_name(&self) -> &BTreeMap<String, String> {
+    &self.subject_name
+  }
+  pub fn issuer_name(&self) -> &BTreeMap<String, String> {
+    &self.issuer_name
+  }
+  /// 使用者备用名称中的域名
+  pub fn dns_names(&self) -> Vec<String> {
+    self
+      .subject_alt_names
+      .iter()
+      .flatten()
+      .filter_map(|name| name.dns_name.clone())
+      .collect()
+  }
+  pub fn serial_number(&self) -> Option<&String> {
+    self.serial_number.as_ref()
+  }
+  pub fn signature_algorithm(&self) -> &str {
+    &self.signature_algorithm
+  }
+  pub fn not_before(&self) -> &str {
+    &self.not_before
+  }
+  pub fn not_after(&self) -> &str {
+    &self.not_after
+  }
+  pub fn pem(&self) -> &[u8] {
+    &self.pem
+  }
 }
 
-// fn kebab_case(name: &str) -> String {
-//   let mut new_name = String::new();
-//   let chars = name.chars().collect::<Vec<_>>();
-//   let l = chars.len();
-//   for (index, c) in chars.into_iter().enumerate() {
-//     if c.is_uppercase() && (index != 0 || index != l - 1) {
-//       new_name.push('_');
-//       c.to_lowercase().for_each(|nc| new_name.push(nc));
-//     } else {
-//       new_name.push(c);
-//     }
-//   }
-//   new_name
-// }
+fn general_name(name: &X509GeneralName) -> GeneralName {
+  let mut general = GeneralName::default();
+  match name {
+    X509GeneralName::RFC822Name(email) => general.email = Some(email.to_string()),
+    X509GeneralName::DNSName(dns) => general.dns_name = Some(dns.to_string()),
+    X509GeneralName::URI(uri) => general.uri = Some(uri.to_string()),
+    X509GeneralName::IPAddress(ip) => general.ipaddress = Some(ip.to_vec()),
+    _ => {}
+  }
+  general
+}
+
+// 名称中的属性，键为属性名称的下划线形式，例如：common_name
+fn name_entries(name: &X509Name) -> BTreeMap<String, String> {
+  let mut entries = BTreeMap::new();
+  for attribute in name.iter_attributes() {
+    let key = oid2sn(attribute.attr_type(), oid_registry())
+      .map(snake_case)
+      .unwrap_or_else(|_| attribute.attr_type().to_id_string());
+    let value = attribute
+      .as_str()
+      .map(|s| s.to_string())
+      .unwrap_or_else(|_| String::from_utf8_lossy(attribute.attr_value().data).to_string());
+    entries
+      .entry(key)
+      .and_modify(|v: &mut String| {
+        v.push_str(", ");
+        v.push_str(&value);
+      })
+      .or_insert(value);
+  }
+  entries
+}
+
+// 和openssl一样取使用者名称SHA1的前四个字节
+fn subject_name_hash(raw: &[u8]) -> u32 {
+  use sha1::Digest;
+  let hash = sha1::Sha1::digest(raw);
+  u32::from_le_bytes([hash[0], hash[1], hash[2], hash[3]])
+}
+
+fn to_pem(der: &[u8]) -> String {
+  let encoded = STANDARD.encode(der);
+  let mut pem = String::from("-----BEGIN CERTIFICATE-----\n");
+  for line in encoded.as_bytes().chunks(64) {
+    pem.push_str(&String::from_utf8_lossy(line));
+    pem.push('\n');
+  }
+  pem.push_str("-----END CERTIFICATE-----\n");
+  pem
+}
+
+fn snake_case(name: &str) -> String {
+  let mut new_name = String::new();
+  for (index, c) in name.chars().enumerate() {
+    if c.is_uppercase() && index != 0 {
+      new_name.push('_');
+    }
+    c.to_lowercase().for_each(|nc| new_name.push(nc));
+  }
+  new_name
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_der_certificate() {
+    let mut params = rcgen::CertificateParams::new(vec![
+      "example.com".to_string(),
+      "www.example.com".to_string(),
+    ])
+    .unwrap();
+    params
+      .distinguished_name
+      .push(rcgen::DnType::CommonName, "example.com");
+    params
+      .distinguished_name
+      .push(rcgen::DnType::OrganizationName, "Example Inc");
+    let key = rcgen::KeyPair::generate().unwrap();
+    let cert = params.self_signed(&key).unwrap();
+    let x509 = X509Certificate::from_der(cert.der()).unwrap();
+    assert_eq!(
+      x509.subject_name().get("common_name").map(String::as_str),
+      Some("example.com")
+    );
+    assert_eq!(
+      x509
+        .issuer_name()
+        .get("organization_name")
+        .map(String::as_str),
+      Some("Example Inc")
+    );
+    assert_eq!(x509.dns_names(), vec!["example.com", "www.example.com"]);
+    assert_eq!(x509.signature_algorithm(), "ecdsa-with-SHA256");
+    assert!(x509.pem().starts_with(b"-----BEGIN CERTIFICATE-----"));
+    assert!(X509Certificate::from_der(b"not a certificate").is_none());
+  }
+}
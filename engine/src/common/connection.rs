@@ -0,0 +1,15 @@
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// 建立连接和读取响应时的元数据，由发送请求的一方放到响应的扩展中
+///
+/// DSL中的 `ip`、`duration` 和 `tls_version` 变量从这里获取
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionInfo {
+  // 连接的远程地址
+  pub ip: Option<IpAddr>,
+  // 从发送请求到读取完响应的时间
+  pub duration: Duration,
+  // 协商的TLS版本，例如：tls12
+  pub tls_version: Option<String>,
+}
@@ -24,6 +24,35 @@ pub enum AttackType {
   ClusterBomb,
 }
 
+/// 单次请求使用的一组payload取值
+///
+/// 取值会加入 [`crate::common::Marker`] 的上下文替换请求中的 `{{name}}`，
+/// 发送后会作为响应的扩展传给匹配器
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Payload(BTreeMap<String, String>);
+
+impl Payload {
+  /// 按照攻击类型展开全部的payload组合，没有payload时只有一个空组合
+  pub fn generate(payload_attack: Option<&PayloadAttack>) -> Vec<Payload> {
+    match payload_attack {
+      Some(pa) => PayloadIterator::from(pa).into_iter().map(Payload).collect(),
+      None => vec![Payload::default()],
+    }
+  }
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+  pub fn values(&self) -> &BTreeMap<String, String> {
+    &self.0
+  }
+}
+
+impl From<BTreeMap<String, String>> for Payload {
+  fn from(value: BTreeMap<String, String>) -> Self {
+    Payload(value)
+  }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct PayloadIterator {
   payload_iterator: VecDeque<BTreeMap<String, String>>,
@@ -155,4 +184,42 @@ mod tests {
       println!("{p:?}");
     }
   }
+
+  #[test]
+  fn payload_generate() {
+    let mut payloads = BTreeMap::new();
+    payloads.insert(
+      "username".to_string(),
+      Value::List(vec![
+        Value::String("admin".to_string()),
+        Value::String("root".to_string()),
+      ]),
+    );
+    payloads.insert(
+      "password".to_string(),
+      Value::List(vec![
+        Value::String("123456".to_string()),
+        Value::String("toor".to_string()),
+      ]),
+    );
+    let pitchfork = PayloadAttack {
+      attack: AttackType::PitchFork,
+      payloads: payloads.clone(),
+    };
+    let generated = Payload::generate(Some(&pitchfork));
+    assert_eq!(generated.len(), 2);
+    assert_eq!(
+      generated[1].values(),
+      &BTreeMap::from_iter([
+        ("password".to_string(), "toor".to_string()),
+        ("username".to_string(), "root".to_string()),
+      ])
+    );
+    let clusterbomb = PayloadAttack {
+      attack: AttackType::ClusterBomb,
+      payloads,
+    };
+    assert_eq!(Payload::generate(Some(&clusterbomb)).len(), 4);
+    assert_eq!(Payload::generate(None), vec![Payload::default()]);
+  }
 }
@@ -0,0 +1,616 @@
+//! JARM和JA3S服务端TLS指纹
+//!
+//! JARM发送十个特殊构造的ClientHello，根据每个ServerHello选择的密码套件、版本和扩展计算指纹，
+//! 算法和 <https://github.com/salesforce/jarm> 保持一致。JA3S使用第一个ServerHello计算。
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// 每个探针读取响应的最大长度，和JARM的实现一样
+pub const JARM_READ_SIZE: usize = 1484;
+
+const GREASE: [[u8; 2]; 16] = [
+  [0x0a, 0x0a],
+  [0x1a, 0x1a],
+  [0x2a, 0x2a],
+  [0x3a, 0x3a],
+  [0x4a, 0x4a],
+  [0x5a, 0x5a],
+  [0x6a, 0x6a],
+  [0x7a, 0x7a],
+  [0x8a, 0x8a],
+  [0x9a, 0x9a],
+  [0xaa, 0xaa],
+  [0xba, 0xba],
+  [0xca, 0xca],
+  [0xda, 0xda],
+  [0xea, 0xea],
+  [0xfa, 0xfa],
+];
+
+const ALL_CIPHERS: [u16; 69] = [
+  0x0016, 0x0033, 0x0067, 0xc09e, 0xc0a2, 0x009e, 0x0039, 0x006b, 0xc09f, 0xc0a3, 0x009f, 0x0045,
+  0x00be, 0x0088, 0x00c4, 0x009a, 0xc008, 0xc009, 0xc023, 0xc0ac, 0xc0ae, 0xc02b, 0xc00a, 0xc024,
+  0xc0ad, 0xc0af, 0xc02c, 0xc072, 0xc073, 0xcca9, 0x1302, 0x1301, 0xcc14, 0xc007, 0xc012, 0xc013,
+  0xc027, 0xc02f, 0xc014, 0xc028, 0xc030, 0xc060, 0xc061, 0xc076, 0xc077, 0xcca8, 0x1305, 0x1304,
+  0x1303, 0xcc13, 0xc011, 0x000a, 0x002f, 0x003c, 0xc09c, 0xc0a0, 0x009c, 0x0035, 0x003d, 0xc09d,
+  0xc0a1, 0x009d, 0x0041, 0x00ba, 0x0084, 0x00c0, 0x0007, 0x0004, 0x0005,
+];
+
+// 计算指纹时密码套件的顺序
+const HASH_CIPHERS: [u16; 69] = [
+  0x0004, 0x0005, 0x0007, 0x000a, 0x0016, 0x002f, 0x0033, 0x0035, 0x0039, 0x003c, 0x003d, 0x0041,
+  0x0045, 0x0067, 0x006b, 0x0084, 0x0088, 0x009a, 0x009c, 0x009d, 0x009e, 0x009f, 0x00ba, 0x00be,
+  0x00c0, 0x00c4, 0xc007, 0xc008, 0xc009, 0xc00a, 0xc011, 0xc012, 0xc013, 0xc014, 0xc023, 0xc024,
+  0xc027, 0xc028, 0xc02b, 0xc02c, 0xc02f, 0xc030, 0xc060, 0xc061, 0xc072, 0xc073, 0xc076, 0xc077,
+  0xc09c, 0xc09d, 0xc09e, 0xc09f, 0xc0a0, 0xc0a1, 0xc0a2, 0xc0a3, 0xc0ac, 0xc0ad, 0xc0ae, 0xc0af,
+  0xcc13, 0xcc14, 0xcca8, 0xcca9, 0x1301, 0x1302, 0x1303, 0x1304, 0x1305,
+];
+
+const ALL_ALPN: [&[u8]; 9] = [
+  b"http/0.9",
+  b"http/1.0",
+  b"http/1.1",
+  b"spdy/1",
+  b"spdy/2",
+  b"spdy/3",
+  b"h2",
+  b"h2c",
+  b"hq",
+];
+// 去掉了h2和http/1.1
+const RARE_ALPN: [&[u8]; 7] = [
+  b"http/0.9",
+  b"http/1.0",
+  b"spdy/1",
+  b"spdy/2",
+  b"spdy/3",
+  b"h2c",
+  b"hq",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Order {
+  Forward,
+  Reverse,
+  TopHalf,
+  BottomHalf,
+  MiddleOut,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SupportedVersions {
+  // 不发送supported_versions扩展
+  None,
+  Tls12,
+  Tls13,
+}
+
+// 一个探针的参数
+struct Probe {
+  version: [u8; 2],
+  record_version: [u8; 2],
+  no_tls13_ciphers: bool,
+  cipher_order: Order,
+  grease: bool,
+  rare_alpn: bool,
+  supported_versions: SupportedVersions,
+  extension_order: Order,
+}
+
+const PROBES: [Probe; 10] = [
+  // tls1_2_forward
+  Probe {
+    version: [3, 3],
+    record_version: [3, 3],
+    no_tls13_ciphers: false,
+    cipher_order: Order::Forward,
+    grease: false,
+    rare_alpn: false,
+    supported_versions: SupportedVersions::Tls12,
+    extension_order: Order::Reverse,
+  },
+  // tls1_2_reverse
+  Probe {
+    version: [3, 3],
+    record_version: [3, 3],
+    no_tls13_ciphers: false,
+    cipher_order: Order::Reverse,
+    grease: false,
+    rare_alpn: false,
+    supported_versions: SupportedVersions::Tls12,
+    extension_order: Order::Forward,
+  },
+  // tls1_2_top_half
+  Probe {
+    version: [3, 3],
+    record_version: [3, 3],
+    no_tls13_ciphers: false,
+    cipher_order: Order::TopHalf,
+    grease: false,
+    rare_alpn: false,
+    supported_versions: SupportedVersions::None,
+    extension_order: Order::Forward,
+  },
+  // tls1_2_bottom_half
+  Probe {
+    version: [3, 3],
+    record_version: [3, 3],
+    no_tls13_ciphers: false,
+    cipher_order: Order::BottomHalf,
+    grease: false,
+    rare_alpn: true,
+    supported_versions: SupportedVersions::None,
+    extension_order: Order::Forward,
+  },
+  // tls1_2_middle_out
+  Probe {
+    version: [3, 3],
+    record_version: [3, 3],
+    no_tls13_ciphers: false,
+    cipher_order: Order::MiddleOut,
+    grease: true,
+    rare_alpn: true,
+    supported_versions: SupportedVersions::None,
+    extension_order: Order::Reverse,
+  },
+  // tls1_1_middle_out
+  Probe {
+    version: [3, 2],
+    record_version: [3, 2],
+    no_tls13_ciphers: false,
+    cipher_order: Order::Forward,
+    grease: false,
+    rare_alpn: false,
+    supported_versions: SupportedVersions::None,
+    extension_order: Order::Forward,
+  },
+  // tls1_3_forward
+  Probe {
+    version: [3, 3],
+    record_version: [3, 1],
+    no_tls13_ciphers: false,
+    cipher_order: Order::Forward,
+    grease: false,
+    rare_alpn: false,
+    supported_versions: SupportedVersions::Tls13,
+    extension_order: Order::Reverse,
+  },
+  // tls1_3_reverse
+  Probe {
+    version: [3, 3],
+    record_version: [3, 1],
+    no_tls13_ciphers: false,
+    cipher_order: Order::Reverse,
+    grease: false,
+    rare_alpn: false,
+    supported_versions: SupportedVersions::Tls13,
+    extension_order: Order::Forward,
+  },
+  // tls1_3_invalid
+  Probe {
+    version: [3, 3],
+    record_version: [3, 1],
+    no_tls13_ciphers: true,
+    cipher_order: Order::Forward,
+    grease: false,
+    rare_alpn: false,
+    supported_versions: SupportedVersions::Tls13,
+    extension_order: Order::Forward,
+  },
+  // tls1_3_middle_out
+  Probe {
+    version: [3, 3],
+    record_version: [3, 1],
+    no_tls13_ciphers: false,
+    cipher_order: Order::MiddleOut,
+    grease: true,
+    rare_alpn: false,
+    supported_versions: SupportedVersions::Tls13,
+    extension_order: Order::Reverse,
+  },
+];
+
+/// 目标的服务端TLS指纹
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct TlsFingerprint {
+  /// 62个字符的JARM指纹
+  pub jarm: String,
+  /// 第一个ServerHello的JA3S指纹
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub ja3s: Option<String>,
+}
+
+impl TlsFingerprint {
+  /// 根据十个探针的响应计算指纹，响应顺序和 [`client_hellos`] 一样，连接失败的响应为空
+  ///
+  /// 全部探针都没有ServerHello的时候目标不是TLS服务，返回None
+  pub fn from_responses(responses: &[Vec<u8>]) -> Option<TlsFingerprint> {
+    let raw: Vec<String> = responses.iter().map(|r| read_server_hello(r)).collect();
+    if raw.iter().all(|r| r == "|||") {
+      return None;
+    }
+    Some(TlsFingerprint {
+      jarm: jarm_hash(&raw),
+      ja3s: responses.iter().find_map(|r| ja3s(r)),
+    })
+  }
+  pub fn hash(&self) -> Vec<String> {
+    let mut hash = vec![self.jarm.clone()];
+    hash.extend(self.ja3s.clone());
+    hash
+  }
+}
+
+/// 计算JARM需要发送的十个ClientHello，每个都需要一个新的连接
+pub fn client_hellos(host: &str) -> Vec<Vec<u8>> {
+  PROBES
+    .iter()
+    .map(|probe| client_hello(probe, host))
+    .collect()
+}
+
+fn client_hello(probe: &Probe, host: &str) -> Vec<u8> {
+  let grease = GREASE[rand::random_range(0..GREASE.len())];
+  let mut hello = probe.version.to_vec();
+  hello.extend(rand::random::<[u8; 32]>());
+  // session id
+  hello.push(32);
+  hello.extend(rand::random::<[u8; 32]>());
+  let mut ciphers: Vec<u16> = ALL_CIPHERS
+    .iter()
+    .copied()
+    .filter(|c| !probe.no_tls13_ciphers || c >> 8 != 0x13)
+    .collect();
+  ciphers = mung(ciphers, probe.cipher_order);
+  if probe.grease {
+    ciphers.insert(0, u16::from_be_bytes(grease));
+  }
+  hello.extend(((ciphers.len() * 2) as u16).to_be_bytes());
+  ciphers.iter().for_each(|c| hello.extend(c.to_be_bytes()));
+  // compression methods
+  hello.extend([0x01, 0x00]);
+  let extensions = extensions(probe, host, grease);
+  hello.extend((extensions.len() as u16).to_be_bytes());
+  hello.extend(extensions);
+  let mut handshake = vec![0x01, 0x00];
+  handshake.extend((hello.len() as u16).to_be_bytes());
+  handshake.extend(hello);
+  let mut record = vec![0x16];
+  record.extend(probe.record_version);
+  record.extend((handshake.len() as u16).to_be_bytes());
+  record.extend(handshake);
+  record
+}
+
+fn extensions(probe: &Probe, host: &str, grease: [u8; 2]) -> Vec<u8> {
+  let mut ext = Vec::new();
+  if probe.grease {
+    ext.extend(grease);
+    ext.extend([0x00, 0x00]);
+  }
+  // server name
+  let host = host.as_bytes();
+  ext.extend([0x00, 0x00]);
+  ext.extend(((host.len() + 5) as u16).to_be_bytes());
+  ext.extend(((host.len() + 3) as u16).to_be_bytes());
+  ext.push(0x00);
+  ext.extend((host.len() as u16).to_be_bytes());
+  ext.extend(host);
+  // extended master secret, max fragment length, renegotiation info
+  ext.extend([0x00, 0x17, 0x00, 0x00]);
+  ext.extend([0x00, 0x01, 0x00, 0x01, 0x01]);
+  ext.extend([0xff, 0x01, 0x00, 0x01, 0x00]);
+  // supported groups, ec point formats, session ticket
+  ext.extend([
+    0x00, 0x0a, 0x00, 0x0a, 0x00, 0x08, 0x00, 0x1d, 0x00, 0x17, 0x00, 0x18, 0x00, 0x19,
+  ]);
+  ext.extend([0x00, 0x0b, 0x00, 0x02, 0x01, 0x00]);
+  ext.extend([0x00, 0x23, 0x00, 0x00]);
+  // application layer protocol negotiation
+  let alpn: Vec<&[u8]> = if probe.rare_alpn {
+    RARE_ALPN.to_vec()
+  } else {
+    ALL_ALPN.to_vec()
+  };
+  let alpn: Vec<u8> = mung(alpn, probe.extension_order)
+    .into_iter()
+    .flat_map(|p| {
+      let mut v = vec![p.len() as u8];
+      v.extend(p);
+      v
+    })
+    .collect();
+  ext.extend([0x00, 0x10]);
+  ext.extend(((alpn.len() + 2) as u16).to_be_bytes());
+  ext.extend((alpn.len() as u16).to_be_bytes());
+  ext.extend(alpn);
+  // signature algorithms
+  ext.extend([
+    0x00, 0x0d, 0x00, 0x14, 0x00, 0x12, 0x04, 0x03, 0x08, 0x04, 0x04, 0x01, 0x05, 0x03, 0x08, 0x05,
+    0x05, 0x01, 0x08, 0x06, 0x06, 0x01, 0x02, 0x01,
+  ]);
+  // key share
+  let mut share = Vec::new();
+  if probe.grease {
+    share.extend(grease);
+    share.extend([0x00, 0x01, 0x00]);
+  }
+  share.extend([0x00, 0x1d, 0x00, 0x20]);
+  share.extend(rand::random::<[u8; 32]>());
+  ext.extend([0x00, 0x33]);
+  ext.extend(((share.len() + 2) as u16).to_be_bytes());
+  ext.extend((share.len() as u16).to_be_bytes());
+  ext.extend(share);
+  // psk key exchange modes
+  ext.extend([0x00, 0x2d, 0x00, 0x02, 0x01, 0x01]);
+  // supported versions
+  let versions: Vec<[u8; 2]> = match probe.supported_versions {
+    SupportedVersions::None => vec![],
+    SupportedVersions::Tls12 => vec![[3, 1], [3, 2], [3, 3]],
+    SupportedVersions::Tls13 => vec![[3, 1], [3, 2], [3, 3], [3, 4]],
+  };
+  if !versions.is_empty() {
+    let mut list = Vec::new();
+    if probe.grease {
+      list.extend(grease);
+    }
+    mung(versions, probe.extension_order)
+      .into_iter()
+      .for_each(|v| list.extend(v));
+    ext.extend([0x00, 0x2b]);
+    ext.extend(((list.len() + 1) as u16).to_be_bytes());
+    ext.push(list.len() as u8);
+    ext.extend(list);
+  }
+  ext
+}
+
+// 按照探针的要求调整顺序
+fn mung<T: Copy>(items: Vec<T>, order: Order) -> Vec<T> {
+  let len = items.len();
+  match order {
+    Order::Forward => items,
+    Order::Reverse => items.into_iter().rev().collect(),
+    Order::BottomHalf => items[len / 2 + len % 2..].to_vec(),
+    Order::TopHalf => {
+      let mut output = Vec::new();
+      if len % 2 == 1 {
+        output.push(items[len / 2]);
+      }
+      output.extend(mung(mung(items, Order::Reverse), Order::BottomHalf));
+      output
+    }
+    Order::MiddleOut => {
+      let middle = len / 2;
+      let mut output = Vec::new();
+      if len % 2 == 1 {
+        output.push(items[middle]);
+        for i in 1..=middle {
+          output.push(items[middle + i]);
+          output.push(items[middle - i]);
+        }
+      } else {
+        for i in 1..=middle {
+          output.push(items[middle - 1 + i]);
+          output.push(items[middle - i]);
+        }
+      }
+      output
+    }
+  }
+}
+
+// 一个探针的原始结果：密码套件|版本|ALPN|扩展列表
+fn read_server_hello(data: &[u8]) -> String {
+  // 不是ServerHello，包括TLS告警
+  if data.len() < 44 || data[0] != 0x16 || data[5] != 0x02 {
+    return "|||".to_string();
+  }
+  let counter = data[43] as usize;
+  let (Some(cipher), Some(version)) = (data.get(counter + 44..counter + 46), data.get(9..11))
+  else {
+    return "|||".to_string();
+  };
+  let server_hello_length = u16::from_be_bytes([data[3], data[4]]) as usize;
+  format!(
+    "{}|{}|{}",
+    hex::encode(cipher),
+    hex::encode(version),
+    extension_info(data, counter, server_hello_length).unwrap_or_else(|| "|".to_string())
+  )
+}
+
+// ALPN|扩展类型，超出长度的时候返回None
+fn extension_info(data: &[u8], counter: usize, server_hello_length: usize) -> Option<String> {
+  if *data.get(counter + 47)? == 11
+    || data.get(counter + 50..counter + 53) == Some(b"\x0e\xac\x0b")
+    || data.get(82..85) == Some(b"\x0f\xf0\x0b")
+    || counter + 42 >= server_hello_length
+  {
+    return Some("|".to_string());
+  }
+  let length = u16::from_be_bytes(data.get(counter + 47..counter + 49)?.try_into().ok()?) as usize;
+  let mut count = counter + 49;
+  let maximum = length + count - 1;
+  let mut types = Vec::new();
+  let mut alpn = String::new();
+  while count < maximum {
+    let ext_type = data.get(count..count + 2)?;
+    let ext_length = u16::from_be_bytes(data.get(count + 2..count + 4)?.try_into().ok()?) as usize;
+    if ext_type == [0x00, 0x10] && alpn.is_empty() && ext_length > 0 {
+      let value = data.get(count + 4..count + 4 + ext_length)?;
+      alpn = String::from_utf8_lossy(value.get(3..).unwrap_or_default()).to_string();
+    }
+    types.push(hex::encode(ext_type));
+    count += ext_length + 4;
+  }
+  Some(format!("{alpn}|{}", types.join("-")))
+}
+
+fn jarm_hash(raw: &[String]) -> String {
+  let mut fuzzy_hash = String::new();
+  let mut alpns_and_ext = String::new();
+  for handshake in raw {
+    let components: Vec<&str> = handshake.split('|').collect();
+    fuzzy_hash.push_str(&cipher_byte(components[0]));
+    fuzzy_hash.push_str(&version_byte(components[1]));
+    alpns_and_ext.push_str(components[2]);
+    alpns_and_ext.push_str(components[3]);
+  }
+  let sha256 = hex::encode(Sha256::digest(alpns_and_ext.as_bytes()));
+  fuzzy_hash.push_str(&sha256[..32]);
+  fuzzy_hash
+}
+
+fn cipher_byte(cipher: &str) -> String {
+  if cipher.is_empty() {
+    return "00".to_string();
+  }
+  let index = HASH_CIPHERS
+    .iter()
+    .position(|c| format!("{c:04x}") == cipher)
+    .unwrap_or(HASH_CIPHERS.len());
+  format!("{:02x}", index + 1)
+}
+
+fn version_byte(version: &str) -> String {
+  version
+    .get(3..4)
+    .and_then(|v| v.parse::<usize>().ok())
+    .and_then(|v| "abcdef".get(v..v + 1))
+    .unwrap_or("0")
+    .to_string()
+}
+
+/// ServerHello的JA3S指纹：md5(版本,密码套件,扩展列表)
+fn ja3s(data: &[u8]) -> Option<String> {
+  if data.len() < 44 || data[0] != 0x16 || data[5] != 0x02 {
+    return None;
+  }
+  let version = u16::from_be_bytes(data.get(9..11)?.try_into().ok()?);
+  let counter = data[43] as usize;
+  let cipher = u16::from_be_bytes(data.get(counter + 44..counter + 46)?.try_into().ok()?);
+  // 压缩方法之后是扩展
+  let mut offset = counter + 47;
+  let mut extensions = Vec::new();
+  if let Some(length) = data.get(offset..offset + 2) {
+    let end = offset + 2 + u16::from_be_bytes(length.try_into().ok()?) as usize;
+    offset += 2;
+    while offset + 4 <= end {
+      let ext_type = u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?);
+      let ext_length = u16::from_be_bytes(data.get(offset + 2..offset + 4)?.try_into().ok()?);
+      extensions.push(ext_type.to_string());
+      offset += 4 + ext_length as usize;
+    }
+  }
+  let ja3s = format!("{version},{cipher},{}", extensions.join("-"));
+  Some(hex::encode(Md5::digest(ja3s.as_bytes())))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // TLS1.2的ServerHello：ECDHE-RSA-AES128-GCM-SHA256，扩展为renegotiation_info、ec_point_formats和ALPN(h2)
+  fn server_hello() -> Vec<u8> {
+    let extensions = [
+      vec![0xff, 0x01, 0x00, 0x01, 0x00],
+      vec![0x00, 0x0b, 0x00, 0x04, 0x03, 0x00, 0x01, 0x02],
+      vec![0x00, 0x10, 0x00, 0x05, 0x00, 0x03, 0x02, b'h', b'2'],
+    ]
+    .concat();
+    server_hello_record([0xc0, 0x2f], &extensions)
+  }
+
+  // TLS1.3的ServerHello：TLS_AES_128_GCM_SHA256，扩展为supported_versions和key_share(x25519)
+  fn tls13_server_hello() -> Vec<u8> {
+    let mut extensions = vec![0x00, 0x2b, 0x00, 0x02, 0x03, 0x04];
+    extensions.extend([0x00, 0x33, 0x00, 0x24, 0x00, 0x1d, 0x00, 0x20]);
+    extensions.extend([0x22; 32]);
+    server_hello_record([0x13, 0x01], &extensions)
+  }
+
+  fn server_hello_record(cipher: [u8; 2], extensions: &[u8]) -> Vec<u8> {
+    let mut hello = vec![0x03, 0x03];
+    hello.extend([0x11; 32]);
+    hello.push(0x00);
+    hello.extend(cipher);
+    hello.push(0x00);
+    hello.extend((extensions.len() as u16).to_be_bytes());
+    hello.extend(extensions);
+    let mut handshake = vec![0x02, 0x00];
+    handshake.extend((hello.len() as u16).to_be_bytes());
+    handshake.extend(hello);
+    let mut record = vec![0x16, 0x03, 0x03];
+    record.extend((handshake.len() as u16).to_be_bytes());
+    record.extend(handshake);
+    record
+  }
+
+  #[test]
+  fn jarm_client_hellos() {
+    let hellos = client_hellos("example.com");
+    assert_eq!(hellos.len(), 10);
+    for hello in hellos.iter() {
+      assert_eq!(hello[0], 0x16);
+      assert_eq!(hello[5], 0x01);
+      let length = u16::from_be_bytes([hello[3], hello[4]]) as usize;
+      assert_eq!(hello.len(), length + 5);
+    }
+    // tls1_3_invalid不包含TLS1.3的密码套件
+    assert!(!hellos[8].windows(2).any(|w| w == [0x13, 0x01]));
+    assert_eq!(
+      mung(vec![1, 2, 3, 4, 5], Order::MiddleOut),
+      vec![3, 4, 2, 5, 1]
+    );
+    assert_eq!(mung(vec![1, 2, 3, 4, 5], Order::TopHalf), vec![3, 2, 1]);
+    assert_eq!(mung(vec![1, 2, 3, 4, 5], Order::BottomHalf), vec![4, 5]);
+  }
+
+  #[test]
+  fn jarm_and_ja3s() {
+    let hello = server_hello();
+    assert_eq!(read_server_hello(&hello), "c02f|0303|h2|ff01-000b-0010");
+    assert_eq!(read_server_hello(b"\x15\x03\x03\x00\x02\x02\x28"), "|||");
+    let mut responses = vec![hello; 2];
+    responses.extend(vec![Vec::new(); 8]);
+    let fingerprint = TlsFingerprint::from_responses(&responses).unwrap();
+    assert_eq!(fingerprint.jarm.len(), 62);
+    assert!(
+      fingerprint
+        .jarm
+        .starts_with("29d29d000000000000000000000000")
+    );
+    // md5("771,49199,65281-11-16")
+    assert_eq!(
+      fingerprint.ja3s.as_deref(),
+      Some(hex::encode(Md5::digest(b"771,49199,65281-11-16")).as_str())
+    );
+    assert!(TlsFingerprint::from_responses(&vec![Vec::new(); 10]).is_none());
+  }
+
+  #[test]
+  fn jarm_reference_vector() {
+    let alert = b"\x15\x03\x03\x00\x02\x02\x46".to_vec();
+    let tls13 = tls13_server_hello();
+    assert_eq!(read_server_hello(&tls13), "1301|0303||002b-0033");
+    let mut responses = vec![server_hello(), server_hello(), tls13.clone(), alert, tls13];
+    responses.extend(vec![Vec::new(); 5]);
+    let fingerprint = TlsFingerprint::from_responses(&responses).unwrap();
+    // salesforce/jarm的jarm.py对同样原始结果计算的指纹：
+    // c02f|0303|h2|ff01-000b-0010,c02f|0303|h2|ff01-000b-0010,1301|0303||002b-0033,|||,
+    // 1301|0303||002b-0033,|||,|||,|||,|||,|||
+    assert_eq!(
+      fingerprint.jarm,
+      "29d29d41d00041d000000000000000ab17867f1597db81c089d8707fc3c2bb"
+    );
+    // JA3S使用第一个ServerHello：md5("771,49199,65281-11-16")
+    assert_eq!(
+      fingerprint.ja3s.as_deref(),
+      Some("1089ea6f0461a29006cc96dfe7a11d80")
+    );
+  }
+}
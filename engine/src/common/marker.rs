@@ -1,122 +1,226 @@
-use std::collections::{BTreeMap, VecDeque};
+use crate::common::Payload;
+use crate::operators::dsl::evaluate_expression;
+use slinger::http::uri::Uri;
+use std::collections::BTreeMap;
 
-// General marker (open/close)
-const GENERAL: &str = "§";
 // ParenthesisOpen marker - begin of a placeholder
 const PARENTHESIS_OPEN: &str = "{{";
 // ParenthesisClose marker - end of a placeholder
 const PARENTHESIS_CLOSE: &str = "}}";
 
-#[derive(Debug)]
-struct Func {
-  name: String,
-  args: Vec<String>,
-}
-
-#[derive(Debug)]
-enum Token {
-  Var(String),
-  Func(Func),
-}
-
-impl From<&String> for Token {
-  fn from(value: &String) -> Self {
-    if value.contains("(") && value.contains(")") {
-      Self::Func(Func { name: "".to_string(), args: vec![] })
-    } else {
-      Self::Var(value.to_string())
-    }
-  }
-}
-
-#[derive(Debug)]
-struct Bracket {
-  open_tag: String,
-  close_tag: String,
-  start: u8,
-  end: u8,
-  token: Token,
-  string: String,
-}
-
-impl PartialEq for Bracket {
-  fn eq(&self, other: &Self) -> bool {
-    self.string == other.string && self.open_tag == other.open_tag && self.close_tag == other.close_tag
-  }
-}
-
-struct Marker {
+/// 替换字符串中的 `{{...}}` 占位符
+///
+/// 占位符可以是上下文中的变量，例如 `{{BaseURL}}`，也可以是DSL表达式，例如
+/// `{{base64(Hostname)}}`、`{{rand_text_alpha(8)}}`。嵌套的占位符从最里层开始替换，
+/// 无法解析的占位符保持原样
+#[derive(Debug, Clone, Default)]
+pub struct Marker {
   // 上下文
   contexts: BTreeMap<String, String>,
-  template: String,
-  unresolved: Vec<Bracket>,
-  func: BTreeMap<String, Func>,
 }
 
 impl Marker {
-  fn new(template: String, contexts: BTreeMap<String, String>) -> Self {
-    Self {
-      contexts,
-      template,
-      unresolved: vec![],
-      func: Default::default(),
-    }
+  pub fn new(contexts: BTreeMap<String, String>) -> Self {
+    Self { contexts }
   }
-  fn match_block(&self, parenthesis_open: &str, parenthesis_close: &str) -> Option<Bracket> {
-    let mut open_start: Vec<(u8, &str)> = self.template.match_indices(parenthesis_open).collect();
-    let close_end: Vec<(u8, &str)> = self.template.match_indices(parenthesis_close).collect();
-    open_start.extend(close_end);
-    open_start.sort_by(|(a_i, _a_s), (b_i, _b_s)| a_i.partial_cmp(&b_i)?);
-    let mut sorted_bracket = VecDeque::from(open_start.clone());
-    let mut brackets: VecDeque<(u8, String)> = VecDeque::new();
-    while let Some((i, s)) = sorted_bracket.pop_front() {
-      if s == parenthesis_open {
-        brackets.push_back((i, s.to_string()));
+  /// 根据目标生成nuclei兼容的内置变量
+  ///
+  /// 以 `https://example.com:443/foo/bar.php` 为例：
+  /// - BaseURL: `https://example.com:443/foo/bar.php`
+  /// - RootURL: `https://example.com:443`
+  /// - Hostname: `example.com:443`
+  /// - Host: `example.com`
+  /// - FQDN: `example.com`，IPv6地址没有方括号
+  /// - Port: `443`
+  /// - Path: `/foo`
+  /// - File: `bar.php`
+  /// - Scheme: `https`
+  pub fn with_target(target: &Uri) -> Self {
+    let scheme = target.scheme_str().unwrap_or("http").to_string();
+    let host = target.host().unwrap_or_default().to_string();
+    let hostname = target
+      .authority()
+      .map_or(host.clone(), |a| a.as_str().to_string());
+    let port = target
+      .port_u16()
+      .unwrap_or(if scheme == "https" || scheme == "tls" {
+        443
       } else {
-        if let Some(open_bracket) = brackets.pop_back() {
-          let name = String::from(&self.template[open_bracket.0 + s.len()..i]);
-          let b = Bracket {
-            open_tag: parenthesis_open.to_string(),
-            close_tag: parenthesis_close.to_string(),
-            start: open_bracket.0,
-            end: i + s.len(),
-            token: Token::from(&name),
-            string: name,
-          };
-          if !self.unresolved.contains(&b) {
-            return Some(b);
-          }
+        80
+      });
+    let fqdn = host
+      .trim_start_matches('[')
+      .trim_end_matches(']')
+      .to_string();
+    let root_url = format!("{scheme}://{hostname}");
+    let path = target.path();
+    let (dir, file) = match path.rsplit_once('/') {
+      Some((dir, file)) => (dir, file),
+      None => ("", path),
+    };
+    let base_url = format!(
+      "{root_url}{}",
+      target
+        .path_and_query()
+        .map_or("", |p| p.as_str())
+        .trim_end_matches('/')
+    );
+    let contexts = BTreeMap::from_iter([
+      ("BaseURL".to_string(), base_url),
+      ("RootURL".to_string(), root_url),
+      ("Hostname".to_string(), hostname),
+      ("FQDN".to_string(), fqdn),
+      ("Host".to_string(), host),
+      ("Port".to_string(), port.to_string()),
+      ("Path".to_string(), dir.to_string()),
+      ("File".to_string(), file.to_string()),
+      ("Scheme".to_string(), scheme),
+    ]);
+    Self { contexts }
+  }
+  pub fn insert<K: Into<String>, V: Into<String>>(&mut self, key: K, value: V) {
+    self.contexts.insert(key.into(), value.into());
+  }
+  /// 加入模板变量，变量的值可以引用内置变量和其他模板变量
+  pub fn extend_resolved(&mut self, variables: &BTreeMap<String, String>) {
+    let mut pending: Vec<(&String, String)> =
+      variables.iter().map(|(k, v)| (k, v.clone())).collect();
+    // 变量之间可能互相引用，每一轮加入已经完全解析的变量，直到没有新的变量可以解析
+    loop {
+      let before = pending.len();
+      let mut unresolved = Vec::new();
+      for (key, value) in pending {
+        let value = self.replace(&value);
+        if value.contains(PARENTHESIS_OPEN) {
+          unresolved.push((key, value));
+        } else {
+          self.contexts.insert(key.clone(), value);
         }
       }
+      pending = unresolved;
+      if pending.is_empty() || pending.len() == before {
+        break;
+      }
+    }
+    for (key, value) in pending {
+      self.contexts.insert(key.clone(), value);
     }
-    return None;
   }
-  fn replaced(&mut self) {
-    while let Some(b) = self.match_block(PARENTHESIS_OPEN, PARENTHESIS_CLOSE) {
-      if let Some(value) = self.contexts.get(&b.string) {
-        self.template.replace_range(b.start..b.end, value);
-      } else {
-        self.unresolved.push(b);
+  /// 复制一份加入了payload取值的上下文
+  pub fn with_payload(&self, payload: &Payload) -> Self {
+    let mut marker = self.clone();
+    marker.contexts.extend(payload.values().clone());
+    marker
+  }
+  pub fn contexts(&self) -> &BTreeMap<String, String> {
+    &self.contexts
+  }
+  /// 替换全部能解析的占位符
+  pub fn replace(&self, template: &str) -> String {
+    let mut output = template.to_string();
+    // 无法解析的占位符之前的内容不再查找，避免死循环
+    let mut from = 0;
+    while let Some((start, end)) = match_block(&output, from) {
+      let expression = &output[start + PARENTHESIS_OPEN.len()..end - PARENTHESIS_CLOSE.len()];
+      match self.resolve(expression) {
+        Some(value) => output.replace_range(start..end, &value),
+        None => from = start + PARENTHESIS_OPEN.len(),
       }
-      println!("{}", self.template);
     }
+    output
+  }
+  fn resolve(&self, expression: &str) -> Option<String> {
+    let expression = expression.trim();
+    if let Some(value) = self.contexts.get(expression) {
+      return Some(value.clone());
+    }
+    // 单纯的变量名没有找到就保持原样，其他的当作DSL表达式计算
+    if expression
+      .chars()
+      .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    {
+      return None;
+    }
+    evaluate_expression(expression, &self.contexts).ok()
   }
 }
 
+// 查找 `from` 之后最里层的占位符，返回包含标记的范围
+fn match_block(template: &str, from: usize) -> Option<(usize, usize)> {
+  let mut open_start = Vec::new();
+  let mut index = from;
+  while index < template.len() {
+    let rest = &template[index..];
+    if rest.starts_with(PARENTHESIS_OPEN) {
+      open_start.push(index);
+      index += PARENTHESIS_OPEN.len();
+    } else if rest.starts_with(PARENTHESIS_CLOSE) {
+      if let Some(start) = open_start.pop() {
+        return Some((start, index + PARENTHESIS_CLOSE.len()));
+      }
+      index += PARENTHESIS_CLOSE.len();
+    } else {
+      index += rest.chars().next().map_or(1, char::len_utf8);
+    }
+  }
+  None
+}
 
 #[cfg(test)]
 mod tests {
-  use std::collections::BTreeMap;
   use super::*;
 
+  #[test]
+  fn target_variables() {
+    let uri: Uri = "https://example.com:8443/foo/bar.php".parse().unwrap();
+    let marker = Marker::with_target(&uri);
+    assert_eq!(
+      marker.replace("{{BaseURL}}/admin"),
+      "https://example.com:8443/foo/bar.php/admin"
+    );
+    assert_eq!(
+      marker.replace("{{RootURL}}/{{File}}"),
+      "https://example.com:8443/bar.php"
+    );
+    assert_eq!(
+      marker.replace("Host: {{Hostname}}\r\n{{Scheme}}:{{Host}}:{{Port}}{{Path}}"),
+      "Host: example.com:8443\r\nhttps:example.com:8443/foo"
+    );
+    let uri: Uri = "http://example.com/".parse().unwrap();
+    let marker = Marker::with_target(&uri);
+    assert_eq!(marker.replace("{{BaseURL}}/"), "http://example.com/");
+    assert_eq!(marker.replace("{{Port}}"), "80");
+    let uri: Uri = "tcp://[::1]:53".parse().unwrap();
+    let marker = Marker::with_target(&uri);
+    assert_eq!(marker.replace("{{Host}} {{FQDN}}"), "[::1] ::1");
+  }
+
   #[test]
   fn marker() {
-    let s = r#"rememberMe={{base64(concat(base64_decode("QUVTL0NCQy9QS0NTNVBhZA=="),aes_cbc(base64_decode(generate_java_gadget("dns", "http://{{interactsh-url}}", "base64")), base64_decode("kPH+bIxk5D2deZiIxcaaaA=="), base64_decode("QUVTL0NCQy9QS0NTNVBhZA=="))))}}"#;
-    let map: BTreeMap<String, String> = BTreeMap::from_iter([
-      ("test".to_string(), "random".to_string()),
-      ("interactsh-url".to_string(), "kali-team.cn".to_string())
-    ]);
-    let mut m = Marker::new(s.to_string(), map);
-    m.replaced()
+    let mut marker = Marker::new(BTreeMap::from_iter([(
+      "interactsh-url".to_string(),
+      "kali-team.cn".to_string(),
+    )]));
+    marker.extend_resolved(&BTreeMap::from_iter([
+      ("a".to_string(), "{{name}}-{{Host}}".to_string()),
+      ("name".to_string(), "{{to_upper(\"admin\")}}".to_string()),
+      ("url".to_string(), "http://{{interactsh-url}}".to_string()),
+    ]));
+    assert_eq!(marker.replace("{{name}}"), "ADMIN");
+    assert_eq!(marker.replace("{{a}}"), "ADMIN-{{Host}}");
+    assert_eq!(
+      marker.replace("{{base64(url)}}"),
+      "aHR0cDovL2thbGktdGVhbS5jbg=="
+    );
+    assert_eq!(
+      marker.replace(r#"{{base64("http://{{interactsh-url}}")}}"#),
+      "aHR0cDovL2thbGktdGVhbS5jbg=="
+    );
+    assert_eq!(marker.replace("{{rand_text_alpha(8)}}").len(), 8);
+    // 无法解析的保持原样，但不影响后面的占位符
+    assert_eq!(
+      marker.replace("{{unknown}}-{{concat({{missing}}, name)}}-{{name}}"),
+      "{{unknown}}-{{concat({{missing}}, name)}}-ADMIN"
+    );
   }
-}
\ No newline at end of file
+}
@@ -1,8 +1,13 @@
 pub mod cert;
+mod connection;
 mod generator;
 pub mod html;
 pub mod http;
+pub mod jarm;
+mod marker;
+pub mod nvd;
 pub mod uri;
-// mod marker;
 
-pub use generator::{PayloadAttack, PayloadIterator};
+pub use connection::ConnectionInfo;
+pub use generator::{Payload, PayloadAttack, PayloadIterator};
+pub use marker::Marker;
@@ -0,0 +1,293 @@
+use crate::error::{Error, Result};
+use crate::info::Cpe;
+use crate::operators::dsl::{compare_version, version_satisfies};
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+/// 关联到的漏洞
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Vulnerability {
+  /// CVE ID
+  pub id: String,
+  /// CVSS base score, prefer the newest CVSS version
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub score: Option<f64>,
+  /// CVSS base severity
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub severity: Option<String>,
+}
+
+// NVD JSON 2.0的数据，只解析关联需要的字段
+#[derive(Deserialize)]
+struct Feed {
+  #[serde(default)]
+  vulnerabilities: Vec<FeedItem>,
+}
+#[derive(Deserialize)]
+struct FeedItem {
+  cve: Cve,
+}
+#[derive(Deserialize)]
+struct Cve {
+  id: String,
+  #[serde(default)]
+  metrics: BTreeMap<String, Vec<Metric>>,
+  #[serde(default)]
+  configurations: Vec<Configuration>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Metric {
+  cvss_data: CvssData,
+  // CVSS 2.0的严重程度在外层
+  #[serde(default)]
+  base_severity: Option<String>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CvssData {
+  base_score: f64,
+  #[serde(default)]
+  base_severity: Option<String>,
+}
+#[derive(Deserialize)]
+struct Configuration {
+  #[serde(default)]
+  nodes: Vec<Node>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Node {
+  #[serde(default)]
+  negate: bool,
+  #[serde(default)]
+  cpe_match: Vec<CpeMatch>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CpeMatch {
+  vulnerable: bool,
+  criteria: String,
+  version_start_including: Option<String>,
+  version_start_excluding: Option<String>,
+  version_end_including: Option<String>,
+  version_end_excluding: Option<String>,
+}
+
+// 受影响的产品和版本范围
+#[derive(Debug)]
+struct Affected {
+  vulnerability: Arc<Vulnerability>,
+  cpe: Cpe,
+  start_including: Option<String>,
+  start_excluding: Option<String>,
+  end_including: Option<String>,
+  end_excluding: Option<String>,
+}
+
+impl Affected {
+  fn contains(&self, cpe: &Cpe) -> bool {
+    if self.cpe.part != cpe.part && self.cpe.part != "*" {
+      return false;
+    }
+    let version = cpe.version.as_str();
+    match self.cpe.version.as_str() {
+      "-" => false,
+      // 任意版本的时候使用版本范围，没有范围表示全部版本都受影响
+      "*" | "" => [
+        (">=", &self.start_including),
+        (">", &self.start_excluding),
+        ("<=", &self.end_including),
+        ("<", &self.end_excluding),
+      ]
+      .iter()
+      .all(|(operator, v)| {
+        v.as_ref()
+          .is_none_or(|v| version_satisfies(version, &format!("{operator}{v}")))
+      }),
+      affected => compare_version(version, affected).is_eq(),
+    }
+  }
+}
+
+/// 本地的NVD漏洞数据，按vendor和product索引，不需要联网
+#[derive(Debug, Default)]
+pub struct NvdDatabase {
+  products: HashMap<(String, String), Vec<Affected>>,
+}
+
+impl NvdDatabase {
+  /// 加载NVD JSON 2.0的数据文件，或者目录下全部的`.json`和`.json.gz`文件
+  pub fn load(path: &Path) -> Result<Self> {
+    let mut database = Self::default();
+    let files = if path.is_dir() {
+      let mut files: Vec<_> = std::fs::read_dir(path)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|p| {
+          let name = p.to_string_lossy();
+          name.ends_with(".json") || name.ends_with(".json.gz")
+        })
+        .collect();
+      files.sort();
+      files
+    } else {
+      vec![path.to_path_buf()]
+    };
+    for file in files {
+      let mut content = String::new();
+      if file.to_string_lossy().ends_with(".gz") {
+        GzDecoder::new(std::fs::File::open(&file)?).read_to_string(&mut content)?;
+      } else {
+        content = std::fs::read_to_string(&file)?;
+      }
+      database.add_feed(&content)?;
+    }
+    Ok(database)
+  }
+  /// 添加一个NVD JSON 2.0的数据，CVE API的返回和数据文件的格式一样
+  pub fn add_feed(&mut self, content: &str) -> Result<()> {
+    let feed: Feed = serde_json::from_str(content).map_err(|err| Error::IO(err.into()))?;
+    for item in feed.vulnerabilities {
+      let cve = item.cve;
+      // 优先使用新版本的CVSS评分
+      let metric = [
+        "cvssMetricV40",
+        "cvssMetricV31",
+        "cvssMetricV30",
+        "cvssMetricV2",
+      ]
+      .iter()
+      .find_map(|key| cve.metrics.get(*key).and_then(|m| m.first()));
+      let vulnerability = Arc::new(Vulnerability {
+        id: cve.id,
+        score: metric.map(|m| m.cvss_data.base_score),
+        severity: metric.and_then(|m| {
+          m.cvss_data
+            .base_severity
+            .clone()
+            .or_else(|| m.base_severity.clone())
+        }),
+      });
+      for cpe_match in cve
+        .configurations
+        .into_iter()
+        .flat_map(|c| c.nodes)
+        .filter(|node| !node.negate)
+        .flat_map(|node| node.cpe_match)
+        .filter(|m| m.vulnerable)
+      {
+        let Some(cpe) = Cpe::parse(&cpe_match.criteria) else {
+          continue;
+        };
+        self
+          .products
+          .entry((cpe.vendor.clone(), cpe.product.clone()))
+          .or_default()
+          .push(Affected {
+            vulnerability: vulnerability.clone(),
+            cpe,
+            start_including: cpe_match.version_start_including,
+            start_excluding: cpe_match.version_start_excluding,
+            end_including: cpe_match.version_end_including,
+            end_excluding: cpe_match.version_end_excluding,
+          });
+      }
+    }
+    Ok(())
+  }
+  /// 受影响的产品数量
+  pub fn len(&self) -> usize {
+    self.products.len()
+  }
+  pub fn is_empty(&self) -> bool {
+    self.products.is_empty()
+  }
+  /// 查找影响这个版本的漏洞，按评分从高到低排序，没有具体版本时不关联
+  pub fn lookup(&self, cpe: &Cpe) -> Vec<Vulnerability> {
+    if !cpe.has_version() {
+      return Vec::new();
+    }
+    let mut vulnerabilities: Vec<Vulnerability> = Vec::new();
+    for affected in self
+      .products
+      .get(&(cpe.vendor.clone(), cpe.product.clone()))
+      .into_iter()
+      .flatten()
+      .filter(|affected| affected.contains(cpe))
+    {
+      if !vulnerabilities
+        .iter()
+        .any(|v| v.id == affected.vulnerability.id)
+      {
+        vulnerabilities.push(affected.vulnerability.as_ref().clone());
+      }
+    }
+    vulnerabilities.sort_by(|a, b| {
+      b.score
+        .unwrap_or_default()
+        .total_cmp(&a.score.unwrap_or_default())
+        .then_with(|| a.id.cmp(&b.id))
+    });
+    vulnerabilities
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const FEED: &str = r#"{
+    "format": "NVD_CVE",
+    "version": "2.0",
+    "vulnerabilities": [
+      {"cve": {"id": "CVE-2021-23017",
+        "metrics": {"cvssMetricV31": [{"cvssData": {"baseScore": 7.7, "baseSeverity": "HIGH"}}],
+                    "cvssMetricV2": [{"cvssData": {"baseScore": 6.8}, "baseSeverity": "MEDIUM"}]},
+        "configurations": [{"nodes": [{"operator": "OR", "negate": false, "cpeMatch": [
+          {"vulnerable": true, "criteria": "cpe:2.3:a:f5:nginx:*:*:*:*:*:*:*:*",
+           "versionStartIncluding": "0.6.18", "versionEndExcluding": "1.20.1"}]}]}]}},
+      {"cve": {"id": "CVE-2019-20372",
+        "metrics": {"cvssMetricV2": [{"cvssData": {"baseScore": 4.3}, "baseSeverity": "MEDIUM"}]},
+        "configurations": [{"nodes": [{"operator": "OR", "negate": false, "cpeMatch": [
+          {"vulnerable": true, "criteria": "cpe:2.3:a:f5:nginx:*:*:*:*:*:*:*:*", "versionEndExcluding": "1.17.7"},
+          {"vulnerable": false, "criteria": "cpe:2.3:o:debian:debian_linux:9.0:*:*:*:*:*:*:*"}]}]}]}},
+      {"cve": {"id": "CVE-2000-0001",
+        "configurations": [{"nodes": [{"operator": "OR", "negate": false, "cpeMatch": [
+          {"vulnerable": true, "criteria": "cpe:2.3:a:f5:nginx:1.18.0:*:*:*:*:*:*:*"}]}]}]}}
+    ]
+  }"#;
+
+  #[test]
+  fn correlate_nvd_feed() {
+    let mut nvd = NvdDatabase::default();
+    nvd.add_feed(FEED).unwrap();
+    let nginx = Cpe::new("a", "f5", "nginx");
+    let ids = |version: &str| {
+      nvd
+        .lookup(&nginx.with_version(Some(version)))
+        .into_iter()
+        .map(|v| v.id)
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(ids("1.18.0"), vec!["CVE-2021-23017", "CVE-2000-0001"]);
+    assert_eq!(ids("1.16.1"), vec!["CVE-2021-23017", "CVE-2019-20372"]);
+    assert!(ids("1.20.1").is_empty());
+    // 没有版本不关联，非受影响的平台不会被索引
+    assert!(nvd.lookup(&nginx).is_empty());
+    assert!(
+      nvd
+        .lookup(&Cpe::new("o", "debian", "debian_linux").with_version(Some("9.0")))
+        .is_empty()
+    );
+    let vulnerability = &nvd.lookup(&nginx.with_version(Some("1.19.0")))[0];
+    assert_eq!(vulnerability.score, Some(7.7));
+    assert_eq!(vulnerability.severity.as_deref(), Some("HIGH"));
+  }
+}
@@ -3,6 +3,7 @@ use crate::operators::{OperatorResult, Operators};
 use crate::request::{PortRange, Requests};
 use crate::results::MatchEvent;
 use crate::template::Template;
+use crate::template::flow::Flow;
 use slinger::Request;
 use std::collections::BTreeMap;
 use std::sync::Arc;
@@ -24,41 +25,67 @@ impl ClusteredOperator {
       operators: t.requests.operators(),
     })
   }
+  pub fn info(&self) -> &Arc<Info> {
+    &self.info
+  }
   pub fn matcher(&self, results: &mut MatchEvent, operator_request: bool) {
-    let response = results.response().unwrap_or_default();
     for operator in self.operators.iter() {
-      // Response-only matching
-      let mut response_operator_result = OperatorResult::default();
-      if let Err(_err) = operator.matcher(&response, &mut response_operator_result) {
-        // continue to attempt request-aware matching below
-      } else {
-        operator.extractor(
-          self.info.get_version(),
-          &response,
-          &mut response_operator_result,
-        );
-        if response_operator_result.is_matched() || response_operator_result.is_extract() {
-          results.push(&self.template, &self.info, response_operator_result);
-        }
-      }
+      self.matcher_with(operator, results, operator_request);
       if !operator_request {
         return;
       }
-      if let Some(req_ref) = response.extensions().get::<Request>() {
-        let req = req_ref.clone();
-        let mut request_operator_result = OperatorResult::default();
-        if let Err(_err) =
-          operator.matcher_generic(&req, Some(&response), &mut request_operator_result)
-        {
-          // continue to next operator
-        } else {
-          operator.extractor_generic(self.info.get_version(), &req, &mut request_operator_result);
-          if request_operator_result.is_matched() || request_operator_result.is_extract() {
-            results.push(&self.template, &self.info, request_operator_result);
-          }
+    }
+  }
+  /// 只使用指定的operator进行匹配，flow中按请求下标执行时使用
+  pub fn matcher_with(
+    &self,
+    operator: &Operators,
+    results: &mut MatchEvent,
+    operator_request: bool,
+  ) -> bool {
+    let response = results.response().unwrap_or_default();
+    let mut matched = false;
+    // Response-only matching
+    let mut response_operator_result = OperatorResult::default();
+    if let Err(_err) = operator.matcher(&response, &mut response_operator_result) {
+      // continue to attempt request-aware matching below
+    } else {
+      operator.extractor(
+        self.info.get_version(),
+        &response,
+        &mut response_operator_result,
+      );
+      matched |= response_operator_result.is_matched();
+      results.insert_variables(response_operator_result.internal_result());
+      if response_operator_result.is_matched() || response_operator_result.is_extract() {
+        results.push(&self.template, &self.info, response_operator_result);
+      }
+    }
+    if !operator_request {
+      return matched;
+    }
+    if let Some(req_ref) = response.extensions().get::<Request>() {
+      let req = req_ref.clone();
+      let mut request_operator_result = OperatorResult::default();
+      if let Err(_err) =
+        operator.matcher_generic(&req, Some(&response), &mut request_operator_result)
+      {
+        // continue to next operator
+      } else {
+        operator.extractor_generic(
+          self.info.get_version(),
+          &req,
+          Some(&response),
+          &mut request_operator_result,
+        );
+        matched |= request_operator_result.is_matched();
+        results.insert_variables(request_operator_result.internal_result());
+        if request_operator_result.is_matched() || request_operator_result.is_extract() {
+          results.push(&self.template, &self.info, request_operator_result);
         }
       }
     }
+    matched
   }
 }
 
@@ -70,7 +97,15 @@ pub struct ClusterType {
   pub tcp_default: Option<Arc<ClusterExecute>>,
   pub tcp_other: BTreeMap<String, Arc<ClusterExecute>>,
   pub port_range: BTreeMap<String, Option<PortRange>>,
+  pub udp: Vec<Arc<ClusterExecute>>,
+  pub ssl: Vec<Arc<ClusterExecute>>,
+  pub dns: Vec<Arc<ClusterExecute>>,
+  pub websocket: Vec<Arc<ClusterExecute>>,
   pub code: Vec<Arc<ClusterExecute>>,
+  /// 所有模板的信息，用于处理指纹之间的implies、excludes和requires关系
+  pub infos: BTreeMap<String, Arc<Info>>,
+  /// 是否有模板使用了jarm匹配器，没有的时候不需要计算TLS指纹
+  pub jarm: bool,
 }
 
 impl ClusterType {
@@ -79,6 +114,10 @@ impl ClusterType {
       + self.web_other.len()
       + self.web_favicon.len()
       + self.tcp_other.len()
+      + self.udp.len()
+      + self.ssl.len()
+      + self.dns.len()
+      + self.websocket.len()
       + self.code.len();
     if self.tcp_default.is_some() {
       count += 1;
@@ -92,4 +131,8 @@ pub struct ClusterExecute {
   pub requests: Arc<Requests>,
   pub rarity: u8,
   pub operators: Vec<Arc<ClusteredOperator>>,
+  /// 模板的执行流程，有flow的模板不会和其他模板合并
+  pub flow: Option<Arc<Flow>>,
+  /// 模板的变量，有变量的模板不会和其他模板合并
+  pub variables: BTreeMap<String, String>,
 }
@@ -0,0 +1,93 @@
+use crate::info::Info;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+// 内置的分类表，FingerprintHub的vendor和product、wappalyzer的cats和nmap的设备类型都映射到同一套分类
+static TAXONOMY: Lazy<Taxonomy> =
+  Lazy::new(|| serde_json::from_str(include_str!("category.json")).unwrap_or_default());
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Taxonomy {
+  // 分类和描述
+  categories: BTreeMap<String, String>,
+  // `vendor:product`或者`product`对应的分类
+  products: BTreeMap<String, Vec<String>>,
+  // wappalyzer的cats编号对应的分类
+  wappalyzer: BTreeMap<String, String>,
+  // nmap的d/设备类型对应的分类
+  device_type: BTreeMap<String, String>,
+}
+
+/// 内置的分类和描述
+pub fn categories() -> &'static BTreeMap<String, String> {
+  &TAXONOMY.categories
+}
+
+/// 从内置分类表查找模板的分类
+pub fn lookup(info: &Info) -> Vec<String> {
+  let mut category = BTreeSet::new();
+  if let Some(vpf) = info.get_vpf() {
+    for key in [format!("{}:{}", vpf.vendor, vpf.product), vpf.product] {
+      if let Some(c) = TAXONOMY.products.get(&key) {
+        category.extend(c.iter().cloned());
+      }
+    }
+  }
+  if let Some(cats) = info.metadata.get("cats") {
+    category.extend(
+      cats
+        .to_vec()
+        .iter()
+        .filter_map(|cat| TAXONOMY.wappalyzer.get(cat).cloned()),
+    );
+  }
+  if let Some(device_type) = info.metadata.get("device_type") {
+    category.extend(TAXONOMY.device_type.get(&device_type.to_string()).cloned());
+  }
+  category.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::serde_format::Value;
+
+  #[test]
+  fn taxonomy_lookup() {
+    // 分类表里面只能出现已经定义的分类
+    for c in TAXONOMY
+      .products
+      .values()
+      .flatten()
+      .chain(TAXONOMY.wappalyzer.values())
+      .chain(TAXONOMY.device_type.values())
+    {
+      assert!(categories().contains_key(c), "{c}");
+    }
+    let mut info = Info::default();
+    info.metadata.insert(
+      "vendor".to_string(),
+      Value::String("00_unknown".to_string()),
+    );
+    info.metadata.insert(
+      "product".to_string(),
+      Value::String("cloudflare".to_string()),
+    );
+    assert_eq!(lookup(&info), vec!["cdn", "waf"]);
+    let mut info = Info::default();
+    info.metadata.insert(
+      "cats".to_string(),
+      Value::List(vec![Value::Num(1), Value::Num(11)]),
+    );
+    assert_eq!(lookup(&info), vec!["cms"]);
+    let mut info = Info::default();
+    info.metadata.insert(
+      "device_type".to_string(),
+      Value::String("router".to_string()),
+    );
+    assert_eq!(lookup(&info), vec!["network-device"]);
+    assert!(lookup(&Info::default()).is_empty());
+  }
+}
@@ -0,0 +1,168 @@
+use std::fmt::{Display, Formatter};
+
+/// CPE 2.3格式化字符串中的产品信息，也可以从CPE 2.2的URI解析
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cpe {
+  /// a: 应用，o: 操作系统，h: 硬件
+  pub part: String,
+  pub vendor: String,
+  pub product: String,
+  /// `*`表示任意版本，`-`表示没有版本
+  pub version: String,
+}
+
+// 格式化字符串中除了字母数字和`_-.`都需要转义，空格使用下划线代替
+fn escape(value: &str) -> String {
+  if value.is_empty() {
+    return "*".to_string();
+  }
+  if value == "*" || value == "-" {
+    return value.to_string();
+  }
+  let mut escaped = String::new();
+  for c in value.trim().to_lowercase().chars() {
+    match c {
+      ' ' => escaped.push('_'),
+      c if c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.') => escaped.push(c),
+      c => {
+        escaped.push('\\');
+        escaped.push(c);
+      }
+    }
+  }
+  escaped
+}
+
+// 按没有转义的冒号分割格式化字符串，并去掉转义
+fn split_formatted(cpe: &str) -> Vec<String> {
+  let mut fields = Vec::new();
+  let mut field = String::new();
+  let mut chars = cpe.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => field.extend(chars.next()),
+      ':' => fields.push(std::mem::take(&mut field)),
+      c => field.push(c),
+    }
+  }
+  fields.push(field);
+  fields
+}
+
+// CPE 2.2的URI使用百分号编码
+fn percent_decode(value: &str) -> String {
+  let bytes = value.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len());
+  let mut index = 0;
+  while index < bytes.len() {
+    if bytes[index] == b'%'
+      && let Some(b) = value
+        .get(index + 1..index + 3)
+        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+    {
+      decoded.push(b);
+      index += 3;
+      continue;
+    }
+    decoded.push(bytes[index]);
+    index += 1;
+  }
+  String::from_utf8_lossy(&decoded).to_string()
+}
+
+impl Cpe {
+  pub fn new(part: &str, vendor: &str, product: &str) -> Self {
+    Self {
+      part: part.to_string(),
+      vendor: vendor.to_lowercase(),
+      product: product.to_lowercase(),
+      version: "*".to_string(),
+    }
+  }
+  /// 解析`cpe:2.3:a:f5:nginx:1.18.0:*:*:*:*:*:*:*`或者`cpe:/a:openbsd:openssh:8.2p1`
+  pub fn parse(cpe: &str) -> Option<Self> {
+    let cpe = cpe.trim();
+    let fields: Vec<String> = if let Some(formatted) = cpe.strip_prefix("cpe:2.3:") {
+      split_formatted(formatted)
+    } else if let Some(uri) = cpe.strip_prefix("cpe:/") {
+      uri.split(':').map(percent_decode).collect()
+    } else {
+      return None;
+    };
+    let field = |index: usize| {
+      fields
+        .get(index)
+        .map(|x| x.to_lowercase())
+        .filter(|x| !x.is_empty())
+    };
+    let part = field(0).filter(|x| matches!(x.as_str(), "a" | "o" | "h"))?;
+    Some(Self {
+      part,
+      vendor: field(1)?,
+      product: field(2)?,
+      version: field(3).unwrap_or_else(|| "*".to_string()),
+    })
+  }
+  /// 替换版本，没有版本时为任意版本
+  pub fn with_version(&self, version: Option<&str>) -> Self {
+    Self {
+      version: version.filter(|x| !x.is_empty()).unwrap_or("*").to_string(),
+      ..self.clone()
+    }
+  }
+  /// 是否为具体的版本
+  pub fn has_version(&self) -> bool {
+    !matches!(self.version.as_str(), "*" | "-" | "") && !self.version.contains('$')
+  }
+}
+
+impl Display for Cpe {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "cpe:2.3:{}:{}:{}:{}:*:*:*:*:*:*:*",
+      self.part,
+      escape(&self.vendor),
+      escape(&self.product),
+      escape(&self.version)
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_and_format_cpe() {
+    let nginx = Cpe::parse("cpe:2.3:a:f5:nginx:*:*:*:*:*:*:*:*").unwrap();
+    assert_eq!(nginx, Cpe::new("a", "f5", "nginx"));
+    assert_eq!(
+      nginx.with_version(Some("1.18.0")).to_string(),
+      "cpe:2.3:a:f5:nginx:1.18.0:*:*:*:*:*:*:*"
+    );
+    // nmap使用的CPE 2.2
+    let openssh = Cpe::parse("cpe:/a:openbsd:openssh:8.2p1").unwrap();
+    assert_eq!(openssh.version, "8.2p1");
+    assert!(openssh.has_version());
+    let iis = Cpe::parse("cpe:/a:microsoft:internet%20information%20services:10.0").unwrap();
+    assert_eq!(
+      iis.to_string(),
+      "cpe:2.3:a:microsoft:internet_information_services:10.0:*:*:*:*:*:*:*"
+    );
+    // 转义的冒号不分割
+    let escaped = Cpe::parse("cpe:2.3:a:vendor:product\\:x:1.0:*:*:*:*:*:*:*").unwrap();
+    assert_eq!(escaped.product, "product:x");
+    assert_eq!(
+      escaped.to_string(),
+      "cpe:2.3:a:vendor:product\\:x:1.0:*:*:*:*:*:*:*"
+    );
+    assert!(
+      !Cpe::parse("cpe:/a:openbsd:openssh:$1")
+        .unwrap()
+        .has_version()
+    );
+    assert!(Cpe::parse("nginx").is_none());
+    assert!(Cpe::parse("cpe:/x:vendor:product").is_none());
+  }
+}
@@ -152,7 +152,7 @@ impl From<CSE> for Vec<Arc<Matcher>> {
       let m = Matcher {
         matcher_type: MatcherType::Word(Word {
           words: k,
-          automaton: None,
+          ..Word::default()
         }),
         ..Matcher::default()
       };
@@ -1,8 +1,12 @@
+mod category;
+mod cpe;
 mod cse;
 mod severity;
 mod version;
 mod vpf;
 
+pub use crate::info::category::categories;
+pub use crate::info::cpe::Cpe;
 pub use crate::info::cse::CSE;
 pub use crate::info::severity::Severity;
 pub use crate::info::version::Version;
@@ -68,6 +72,27 @@ pub struct Info {
   )]
   pub tags: Vec<String>,
   /// description: |
+  ///   Category of the technology detected by the template.
+  ///
+  ///   Multiple values can also be specified separated by commas.
+  ///
+  /// examples:
+  ///   - value: "\"waf,cdn\""
+  #[serde(
+    with = "string_vec_serde",
+    default,
+    skip_serializing_if = "Vec::is_empty"
+  )]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      with = "Vec<String>",
+      title = "category of the technology",
+      description = "Category of the technology, such as cms, web-server, waf, cdn, js-framework, database, os, network-device and vpn"
+    )
+  )]
+  pub category: Vec<String>,
+  /// description: |
   ///   Description of the template.
   ///
   ///   You can go in-depth here on what the template actually does.
@@ -254,6 +279,61 @@ impl Info {
       None
     }
   }
+  /// nmap的softmatch，只能确定服务，后面的探针只需要匹配这个服务的规则
+  pub fn is_softmatch(&self) -> bool {
+    matches!(self.metadata.get("softmatch"), Some(Value::Bool(true)))
+  }
+  /// 命中后可以推断出来的其他指纹
+  pub fn get_implies(&self) -> Vec<String> {
+    self
+      .metadata
+      .get("implies")
+      .map(|x| x.to_vec())
+      .unwrap_or_default()
+  }
+  /// 和当前指纹互斥的指纹，同时命中时会被剔除
+  pub fn get_excludes(&self) -> Vec<String> {
+    self
+      .metadata
+      .get("excludes")
+      .map(|x| x.to_vec())
+      .unwrap_or_default()
+  }
+  /// 当前指纹依赖的指纹，依赖没有命中时剔除当前指纹
+  pub fn get_requires(&self) -> Vec<String> {
+    self
+      .metadata
+      .get("requires")
+      .map(|x| x.to_vec())
+      .unwrap_or_default()
+  }
+  /// 模板的CPE，优先使用`cpe`，没有的时候使用已知的vendor和product生成
+  pub fn get_cpe(&self) -> Option<Cpe> {
+    if let Some(cpe) = self
+      .metadata
+      .get("cpe")
+      .and_then(|cpe| cpe.to_vec().iter().find_map(|x| Cpe::parse(x)))
+    {
+      return Some(cpe);
+    }
+    let vpf = self.get_vpf()?;
+    if vpf.vendor == UNKNOWN_00 {
+      return None;
+    }
+    let part = if self.get_category().iter().any(|c| c == "os") {
+      "o"
+    } else {
+      "a"
+    };
+    Some(Cpe::new(part, &vpf.vendor, &vpf.product))
+  }
+  /// 指纹的分类，模板没有指定的时候从内置的分类表查找
+  pub fn get_category(&self) -> Vec<String> {
+    if !self.category.is_empty() {
+      return self.category.clone();
+    }
+    category::lookup(self)
+  }
   pub fn get_rarity(&self) -> Option<u8> {
     self.metadata.get("rarity").and_then(|x| {
       if let Value::Num(n) = x {
@@ -1,7 +1,9 @@
 use crate::error::Result;
 use crate::info::Version;
-use crate::operators::matchers::Part;
+use crate::operators::dsl::{CompiledExpression, DslVariables, header_variable_name};
+use crate::operators::matchers::{Part, compile_dsl};
 use crate::operators::regex::RegexPattern;
+use crate::operators::xpath::{DocumentCache, parse_document};
 use crate::serde_format::is_default;
 use jsonpath_rust::JsonPath;
 use log::error;
@@ -74,6 +76,9 @@ impl Extractor {
     if let ExtractorType::Regex(regexps) = &mut self.extractor_type {
       regexps.compiled_regex = vec![OnceCell::new(); regexps.regex.len()]
     }
+    if let ExtractorType::DSL(dsl) = &mut self.extractor_type {
+      dsl.compiled = compile_dsl(dsl.dsl.iter())?;
+    }
     Ok(())
   }
   pub fn extract_json(
@@ -96,6 +101,76 @@ impl Extractor {
     }
     (extract_result, BTreeMap::new())
   }
+  /// 从头部和cookie中提取，名称不区分大小写，`-` 和 `_` 等价
+  pub(crate) fn extract_kval(
+    &self,
+    kval: &KVal,
+    headers: &str,
+  ) -> (HashSet<String>, BTreeMap<String, String>) {
+    let keys: HashSet<String> = kval.kval.iter().map(|k| header_variable_name(k)).collect();
+    let mut extract_result = HashSet::new();
+    for line in headers.lines() {
+      let Some((name, value)) = line.split_once(':') else {
+        continue;
+      };
+      let name = header_variable_name(name);
+      let value = value.trim();
+      if keys.contains(&name) {
+        extract_result.insert(value.to_string());
+      }
+      // 响应的Set-Cookie只有第一个键值对是cookie，请求的Cookie有多个
+      let cookies: Vec<&str> = match name.as_str() {
+        "set_cookie" => value.split(';').take(1).collect(),
+        "cookie" => value.split(';').collect(),
+        _ => continue,
+      };
+      for cookie in cookies {
+        if let Some((k, v)) = cookie.split_once('=')
+          && keys.contains(&header_variable_name(k))
+        {
+          extract_result.insert(v.trim().to_string());
+        }
+      }
+    }
+    (extract_result, BTreeMap::new())
+  }
+  pub(crate) fn extract_dsl(
+    &self,
+    dsl: &DSL,
+    vars: &DslVariables,
+  ) -> (HashSet<String>, BTreeMap<String, String>) {
+    let mut extract_result = HashSet::new();
+    let ctx = vars.context();
+    for compiled in dsl.compiled.iter() {
+      match compiled.value(&ctx) {
+        Ok(value) if !value.is_empty() => {
+          extract_result.insert(value);
+        }
+        Ok(_) => {}
+        Err(err) => error!("DSL extract error for '{}': {}", compiled.expression, err),
+      }
+    }
+    (extract_result, BTreeMap::new())
+  }
+  pub(crate) fn extract_xpath(
+    &self,
+    xpath: &XPath,
+    corpus: String,
+    documents: Option<&DocumentCache>,
+  ) -> (HashSet<String>, BTreeMap<String, String>) {
+    let mut extract_result = HashSet::new();
+    if let Some(document) = parse_document(documents, &corpus) {
+      for expr in xpath.xpath.iter() {
+        extract_result.extend(
+          document
+            .select(expr, xpath.attribute.as_deref())
+            .into_iter()
+            .filter(|v| !v.is_empty()),
+        );
+      }
+    }
+    (extract_result, BTreeMap::new())
+  }
   pub(crate) fn extract_regex(
     &self,
     regex_list: &RegexPattern,
@@ -212,7 +287,7 @@ pub struct XPath {
 }
 /// DSL extractor using expression evaluation
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 #[serde(deny_unknown_fields)]
 pub struct DSL {
@@ -225,4 +300,12 @@ pub struct DSL {
     )
   )]
   pub dsl: HashSet<String>,
+  /// Expressions compiled at template load time
+  #[serde(skip)]
+  pub compiled: Vec<CompiledExpression>,
+}
+impl PartialEq for DSL {
+  fn eq(&self, other: &Self) -> bool {
+    self.dsl == other.dsl
+  }
 }
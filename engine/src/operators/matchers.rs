@@ -1,14 +1,16 @@
-use crate::error::{Error, Result};
-use crate::operators::dsl::{DslVariables, evaluate_dsl};
+use crate::common::jarm::TlsFingerprint;
+use crate::error::{Error, Result, new_regex_error};
+use crate::operators::dsl::{CompiledExpression, DslVariables, version_in_range};
 use crate::operators::regex::RegexPattern;
 use crate::operators::target::OperatorTarget;
+use crate::operators::xpath::{DocumentCache, parse_document};
 use crate::serde_format::is_default;
 use aho_corasick::AhoCorasick;
 use log::error;
 use once_cell::sync::OnceCell;
 use serde::{Deserialize, Deserializer, Serialize, Serializer, de, ser};
 use slinger::Body;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt::{Display, Formatter};
 use std::hash::Hash;
 use std::str::FromStr;
@@ -155,6 +157,20 @@ impl Matcher {
     if let MatcherType::Regex(regexps) = &mut self.matcher_type {
       regexps.compiled_regex = vec![OnceCell::new(); regexps.regex.len()]
     }
+    if let MatcherType::DSL(dsl) = &mut self.matcher_type {
+      dsl.compiled = compile_dsl(dsl.dsl.iter())?;
+    }
+    if let MatcherType::Binary(binary) = &mut self.matcher_type {
+      binary.compiled = decode_hex(&binary.binary)?;
+    }
+    // 十六进制编码的单词按照字节匹配，不需要构建字符串的自动机
+    let is_hex = self.is_hex_encoding();
+    if let MatcherType::Word(word) = &mut self.matcher_type
+      && is_hex
+    {
+      word.compiled = decode_hex(&word.words)?;
+      return Ok(());
+    }
     if let MatcherType::Word(word) = &mut self.matcher_type
       && self.case_insensitive
     {
@@ -176,6 +192,12 @@ impl Matcher {
     }
     Ok(())
   }
+  pub(crate) fn is_hex_encoding(&self) -> bool {
+    self
+      .encoding
+      .as_deref()
+      .is_some_and(|e| e.eq_ignore_ascii_case("hex"))
+  }
   pub(crate) fn match_favicon(
     &self,
     fav: &Favicon,
@@ -193,6 +215,19 @@ impl Matcher {
     }
     (false, matched_words)
   }
+  pub(crate) fn match_jarm(
+    &self,
+    jarm: &Jarm,
+    fingerprint: &TlsFingerprint,
+  ) -> (bool, Vec<String>) {
+    let corpus = fingerprint.hash();
+    for hash in jarm.hash.iter() {
+      if corpus.iter().any(|h| h.eq_ignore_ascii_case(hash)) {
+        return (true, vec![hash.to_string()]);
+      }
+    }
+    (false, Vec::new())
+  }
   pub(crate) fn match_word(&self, word: &Word, corpus: String) -> (bool, Vec<String>) {
     if let Some(ac) = &word.automaton {
       let mut matched_words = Vec::new();
@@ -307,6 +342,40 @@ impl Matcher {
       (false, matched_regexes)
     }
   }
+  /// 按照字节匹配，`names` 是命中时返回的原始十六进制字符串
+  pub(crate) fn match_binary(
+    &self,
+    patterns: &[Vec<u8>],
+    names: &[String],
+    corpus: &[u8],
+  ) -> (bool, Vec<String>) {
+    let mut matched_binary = Vec::new();
+    for (pattern, name) in patterns.iter().zip(names) {
+      let found = !pattern.is_empty()
+        && corpus.windows(pattern.len()).any(|window| {
+          if self.case_insensitive {
+            window.eq_ignore_ascii_case(pattern)
+          } else {
+            window == pattern.as_slice()
+          }
+        });
+      if found {
+        matched_binary.push(name.clone());
+        if matches!(self.condition, Condition::Or) && !self.match_all {
+          return (true, matched_binary);
+        }
+      } else if matches!(self.condition, Condition::And) {
+        return (false, matched_binary);
+      }
+    }
+    if (!matched_binary.is_empty() && !self.match_all)
+      || (matched_binary.len() == patterns.len() && self.match_all)
+    {
+      (true, matched_binary)
+    } else {
+      (false, matched_binary)
+    }
+  }
   pub(crate) fn match_status_code(&self, status: &Status, status_code: u16) -> bool {
     for code in status.status.iter() {
       if code != &status_code {
@@ -319,8 +388,10 @@ impl Matcher {
 
   pub(crate) fn match_dsl(&self, dsl: &DSL, vars: &DslVariables) -> (bool, Vec<String>) {
     let mut matched_expressions = Vec::new();
-    for expr in &dsl.dsl {
-      match evaluate_dsl(expr, vars) {
+    let ctx = vars.context();
+    for compiled in &dsl.compiled {
+      let expr = &compiled.expression;
+      match compiled.evaluate(&ctx) {
         Ok(true) => {
           matched_expressions.push(expr.clone());
           if matches!(self.condition, Condition::Or) && !self.match_all {
@@ -357,6 +428,73 @@ impl Matcher {
     }
   }
 
+  pub(crate) fn match_xpath(
+    &self,
+    xpath: &MatcherXPath,
+    corpus: &str,
+    documents: Option<&DocumentCache>,
+  ) -> (bool, Vec<String>) {
+    let mut matched_xpath = Vec::new();
+    let Some(document) = parse_document(documents, corpus) else {
+      return (false, matched_xpath);
+    };
+    for expr in xpath.xpath.iter() {
+      // 查询到元素或者指定的属性就算命中
+      if !document.select(expr, xpath.attribute.as_deref()).is_empty() {
+        matched_xpath.push(expr.clone());
+        if matches!(self.condition, Condition::Or) && !self.match_all {
+          return (true, matched_xpath);
+        }
+      } else if matches!(self.condition, Condition::And) {
+        return (false, matched_xpath);
+      }
+    }
+    if (!matched_xpath.is_empty() && !self.match_all)
+      || (matched_xpath.len() == xpath.xpath.len() && self.match_all)
+    {
+      (true, matched_xpath)
+    } else {
+      (false, matched_xpath)
+    }
+  }
+
+  /// 比较提取器提取到的版本，任意一个提取到的值在范围内就算命中这个范围
+  pub(crate) fn match_version(
+    &self,
+    version: &VersionMatcher,
+    extracted: &BTreeMap<String, HashSet<String>>,
+  ) -> (bool, Vec<String>) {
+    // 没有指定提取器的时候比较所有提取到的值
+    let captured: Vec<&String> = match &version.extractor {
+      Some(name) => extracted.get(name).into_iter().flatten().collect(),
+      None => extracted.values().flatten().collect(),
+    };
+    let mut matched_versions = Vec::new();
+    let mut hits = 0;
+    for range in version.version.iter() {
+      match captured.iter().find(|v| version_in_range(v, range)) {
+        Some(v) => {
+          hits += 1;
+          if !matched_versions.contains(*v) {
+            matched_versions.push(v.to_string());
+          }
+          if matches!(self.condition, Condition::Or) && !self.match_all {
+            return (true, matched_versions);
+          }
+        }
+        None if matches!(self.condition, Condition::And) => {
+          return (false, matched_versions);
+        }
+        None => continue,
+      }
+    }
+    if (hits > 0 && !self.match_all) || (hits == version.version.len() && self.match_all) {
+      (true, matched_versions)
+    } else {
+      (false, matched_versions)
+    }
+  }
+
   pub(crate) fn negative(&self, is_match: bool) -> bool {
     if self.negative { !is_match } else { is_match }
   }
@@ -368,12 +506,14 @@ pub enum MatcherType {
   #[default]
   None,
   Favicon(Favicon),
+  Jarm(Jarm),
   Word(Word),
   Status(Status),
   Regex(RegexPattern),
   DSL(DSL),
   Binary(Binary),
   XPath(MatcherXPath),
+  Version(VersionMatcher),
 }
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -389,12 +529,45 @@ pub struct MatcherXPath {
     )
   )]
   pub xpath: HashSet<String>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "optional attribute required on matched elements",
+      description = "Attribute is an optional attribute that the elements matched by xpath must have"
+    )
+  )]
   pub attribute: Option<String>,
 }
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 #[serde(deny_unknown_fields)]
+pub struct VersionMatcher {
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "version ranges to match",
+      description = "Version ranges compared with the extracted version, conditions in one range are separated by commas and must all be satisfied",
+      example = r#"&["<5.8.3, >=5.0", "<4.9.22"]"#
+    )
+  )]
+  pub version: Vec<String>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "name of the extractor providing the version",
+      description = "Name of the extractor whose captured value is compared, all extracted values are compared when empty",
+      example = r#"&"version""#
+    )
+  )]
+  pub extractor: Option<String>,
+}
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
 pub struct Binary {
   #[cfg_attr(
     feature = "mcp",
@@ -405,6 +578,9 @@ pub struct Binary {
     )
   )]
   pub binary: Vec<String>,
+  /// 解码后的字节
+  #[serde(skip)]
+  pub compiled: Vec<Vec<u8>>,
 }
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -423,6 +599,9 @@ pub struct Word {
   /// Aho-Corasick automaton for efficient multi-pattern matching (not serialized)
   #[serde(skip)]
   pub automaton: Option<AhoCorasick>,
+  /// Decoded bytes of the words when encoding is hex (not serialized)
+  #[serde(skip)]
+  pub compiled: Vec<Vec<u8>>,
 }
 impl PartialEq for Word {
   fn eq(&self, other: &Self) -> bool {
@@ -497,6 +676,21 @@ pub struct Favicon {
   pub hash: Vec<String>,
 }
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct Jarm {
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "hash to match in tls fingerprint",
+      description = "The JARM or JA3S hash of the TLS server, and if there is one in the collection, the match is successful",
+      example = r#"&["07d14d16d21d21d07c42d41d00041d24a458a375eef0c576d23a7bab9a9fb1", "e35df3e00ca4ef31d42b34bebaa2f86e"]"#
+    )
+  )]
+  pub hash: Vec<String>,
+}
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 #[serde(deny_unknown_fields)]
@@ -532,7 +726,7 @@ pub struct Status {
   pub status: Vec<u16>,
 }
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 #[serde(deny_unknown_fields)]
 pub struct DSL {
@@ -545,6 +739,14 @@ pub struct DSL {
     )
   )]
   pub dsl: Vec<String>,
+  /// 加载模板时预编译的表达式
+  #[serde(skip)]
+  pub compiled: Vec<CompiledExpression>,
+}
+impl PartialEq for DSL {
+  fn eq(&self, other: &Self) -> bool {
+    self.dsl == other.dsl
+  }
 }
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
@@ -554,6 +756,27 @@ pub enum Condition {
   Or,
   And,
 }
+// 编译DSL表达式，语法错误作为模板加载错误
+pub(crate) fn compile_dsl<'a>(
+  expressions: impl Iterator<Item = &'a String>,
+) -> Result<Vec<CompiledExpression>> {
+  expressions
+    .map(|expr| {
+      CompiledExpression::compile(expr)
+        .map_err(|err| Error::IO(std::io::Error::new(std::io::ErrorKind::InvalidInput, err)))
+    })
+    .collect()
+}
+// 十六进制字符串解码为字节，忽略中间的空白
+fn decode_hex(values: &[String]) -> Result<Vec<Vec<u8>>> {
+  values
+    .iter()
+    .map(|v| {
+      let v: String = v.chars().filter(|c| !c.is_whitespace()).collect();
+      hex::decode(v).map_err(new_regex_error)
+    })
+    .collect()
+}
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Default, PartialEq)]
 pub enum Part {
@@ -561,6 +784,7 @@ pub enum Part {
   Body,
   Header,
   Response,
+  Url,
   Name(String),
 }
 
@@ -581,6 +805,7 @@ impl Part {
       Part::Response => {
         format!("{header_string}\r\n\r\n{body_string}")
       }
+      Part::Url => target.get_url(),
       Part::Name(name) => target.get_header(name).ok_or_else(|| {
         Error::IO(std::io::Error::new(
           std::io::ErrorKind::InvalidData,
@@ -600,6 +825,7 @@ impl FromStr for Part {
       "body" => Ok(Self::Body),
       "header" => Ok(Self::Header),
       "response" => Ok(Self::Response),
+      "url" => Ok(Self::Url),
       name => Ok(Self::Name(name.to_string())),
     }
   }
@@ -611,6 +837,7 @@ impl Display for Part {
       Self::Body => "body".to_string(),
       Self::Header => "header".to_string(),
       Self::Response => "response".to_string(),
+      Self::Url => "url".to_string(),
       Self::Name(name) => name.to_string(),
     };
     f.write_str(&s)
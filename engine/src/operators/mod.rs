@@ -1,9 +1,11 @@
+use crate::common::jarm::TlsFingerprint;
 use crate::error::{Result, new_regex_error};
 use crate::info::Version;
 use crate::operators::dsl::DslVariables;
 use crate::operators::extractors::{Extractor, ExtractorType};
-use crate::operators::matchers::{Condition, FaviconMap, Matcher, MatcherType};
+use crate::operators::matchers::{Condition, FaviconMap, Matcher, MatcherType, Part};
 use crate::operators::target::OperatorTarget;
+use crate::operators::xpath::DocumentCache;
 use crate::serde_format::is_default;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -16,6 +18,7 @@ pub mod extractors;
 pub mod matchers;
 pub mod regex;
 pub mod target;
+pub mod xpath;
 
 /// Operators for the current request go here.
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
@@ -91,12 +94,21 @@ impl Operators {
   }
 
   /// Generic extractor that works with any OperatorTarget (Response or Request)
+  /// DSL extractors can access status code and variables from the response extensions
   pub fn extractor_generic<T: OperatorTarget>(
     &self,
     version: Option<Version>,
     target: &T,
+    response_for_extensions: Option<&Response>,
     result: &mut OperatorResult,
   ) {
+    let has_dsl = self
+      .extractors
+      .iter()
+      .any(|e| matches!(e.extractor_type, ExtractorType::DSL(..)));
+    let dsl_vars = has_dsl.then(|| dsl_variables(target, response_for_extensions));
+    let documents =
+      response_for_extensions.and_then(|r| r.extensions().get::<DocumentCache>().cloned());
     for (index, extractor) in self.extractors.iter().enumerate() {
       let (words, body) =
         if let Ok((words, body)) = extractor.part.get_matcher_word_from_part(target) {
@@ -107,17 +119,22 @@ impl Operators {
       let (extract_result, version) = match &extractor.extractor_type {
         ExtractorType::Regex(re) => extractor.extract_regex(re, words, body, &version),
         ExtractorType::JSON(json) => extractor.extract_json(json, words),
-        ExtractorType::KVal(..) | ExtractorType::XPath(..) | ExtractorType::DSL(..) => {
-          (HashSet::new(), BTreeMap::new())
-        }
+        ExtractorType::XPath(xpath) => extractor.extract_xpath(xpath, words, documents.as_ref()),
+        ExtractorType::KVal(kval) => extractor.extract_kval(kval, &target.get_headers()),
+        ExtractorType::DSL(dsl) => match &dsl_vars {
+          Some(vars) => extractor.extract_dsl(dsl, vars),
+          None => (HashSet::new(), BTreeMap::new()),
+        },
       };
       if !extract_result.is_empty() {
         let key = extractor.name.clone().unwrap_or(index.to_string());
-        if let Some(er) = result.extract_result.get_mut(&key) {
-          er.extend(extract_result);
+        // 内部提取器的值只给后面的请求使用，不输出
+        let results = if extractor.internal {
+          &mut result.internal_result
         } else {
-          result.extract_result.insert(key.clone(), extract_result);
-        }
+          &mut result.extract_result
+        };
+        results.entry(key).or_default().extend(extract_result);
       }
       for (k, v) in version {
         result.extract_result.insert(k, HashSet::from_iter([v]));
@@ -131,7 +148,7 @@ impl Operators {
     response: &Response,
     result: &mut OperatorResult,
   ) {
-    self.extractor_generic(version, response, result)
+    self.extractor_generic(version, response, Some(response), result)
   }
 
   /// Generic matcher that works with any OperatorTarget (Response or Request)
@@ -151,28 +168,21 @@ impl Operators {
       .matchers
       .iter()
       .any(|m| matches!(m.matcher_type, MatcherType::DSL(..)));
-    let dsl_vars = if has_dsl {
-      let body_string = target.get_body_string();
-      let headers_string = target.get_headers();
-      let status_code = response_for_extensions
-        .map(|r| r.status_code().as_u16())
-        .unwrap_or(0);
-      let content_type = target.get_header("content-type").unwrap_or_default();
-      let content_length = target
-        .get_body()
-        .map(|b| b.as_ref().len() as i64)
-        .unwrap_or(0);
-      Some(Arc::new(DslVariables {
-        body: body_string,
-        all_headers: headers_string,
-        status_code,
-        content_length,
-        content_type,
-        extra: Default::default(),
-      }))
-    } else {
-      None
-    };
+    let dsl_vars = has_dsl.then(|| Arc::new(dsl_variables(target, response_for_extensions)));
+    // 版本匹配器比较提取器的结果，所以先提取一次，内部提取器的值也可以用来比较
+    let has_version = self
+      .matchers
+      .iter()
+      .any(|m| matches!(m.matcher_type, MatcherType::Version(..)));
+    let extracted = has_version.then(|| {
+      let mut extracted = OperatorResult::default();
+      self.extractor_generic(None, target, response_for_extensions, &mut extracted);
+      let mut values = extracted.internal_result;
+      for (name, value) in extracted.extract_result {
+        values.entry(name).or_default().extend(value);
+      }
+      values
+    });
     let mut inputs: Vec<(Arc<Matcher>, String, Body, Option<u16>)> =
       Vec::with_capacity(self.matchers.len());
     for matcher in self.matchers.iter() {
@@ -186,11 +196,27 @@ impl Operators {
     }
     let favicon_map: Option<HashSet<FaviconMap>> =
       response_for_extensions.and_then(|r| r.extensions().get::<HashSet<FaviconMap>>().cloned());
+    let tls_fingerprint: Option<TlsFingerprint> =
+      response_for_extensions.and_then(|r| r.extensions().get::<TlsFingerprint>().cloned());
+    let documents: Option<DocumentCache> =
+      response_for_extensions.and_then(|r| r.extensions().get::<DocumentCache>().cloned());
     let results: Vec<(bool, Vec<String>, Option<String>)> = inputs
       .into_par_iter()
       .map(|(matcher, words, body, status)| {
+        // 二进制匹配body使用原始的字节，其他部分本来就是字符串
+        let bytes = if matches!(matcher.part, Part::Body) {
+          body.as_ref()
+        } else {
+          words.as_bytes()
+        };
         let (is_match, mw) = match &matcher.matcher_type {
+          MatcherType::Word(word) if matcher.is_hex_encoding() => {
+            matcher.match_binary(&word.compiled, &word.words, bytes)
+          }
           MatcherType::Word(word) => matcher.match_word(word, words.clone()),
+          MatcherType::Binary(binary) => {
+            matcher.match_binary(&binary.compiled, &binary.binary, bytes)
+          }
           MatcherType::Favicon(fav) => {
             if let Some(ref hm) = favicon_map {
               matcher.match_favicon(fav, hm)
@@ -198,6 +224,13 @@ impl Operators {
               (false, Vec::new())
             }
           }
+          MatcherType::Jarm(jarm) => {
+            if let Some(ref fingerprint) = tls_fingerprint {
+              matcher.match_jarm(jarm, fingerprint)
+            } else {
+              (false, Vec::new())
+            }
+          }
           MatcherType::Status(status_pat) => {
             if let Some(code) = status {
               (
@@ -216,9 +249,15 @@ impl Operators {
               (false, Vec::new())
             }
           }
-          MatcherType::None | MatcherType::Binary(..) | MatcherType::XPath(..) => {
-            (false, Vec::new())
+          MatcherType::XPath(xpath) => matcher.match_xpath(xpath, &words, documents.as_ref()),
+          MatcherType::Version(version) => {
+            if let Some(ref values) = extracted {
+              matcher.match_version(version, values)
+            } else {
+              (false, Vec::new())
+            }
           }
+          MatcherType::None => (false, Vec::new()),
         };
         let is_match = matcher.negative(is_match);
         let name = matcher.name.clone();
@@ -261,6 +300,15 @@ impl Operators {
   }
 }
 
+// DSL使用的变量，响应扩展中的请求上下文、payload和历史响应也加进去
+fn dsl_variables<T: OperatorTarget>(target: &T, response: Option<&Response>) -> DslVariables {
+  let vars = DslVariables::from_target(target, response);
+  match response {
+    Some(r) => vars.with_extensions(r),
+    None => vars,
+  }
+}
+
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct OperatorResult {
@@ -308,6 +356,18 @@ pub struct OperatorResult {
     )
   )]
   extract_result: BTreeMap<String, HashSet<String>>,
+  /// Description: Values extracted by internal extractors, only used by subsequent requests
+  /// Example: {"csrf": ["d41d8cd98f00b204"]}
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "Internal Extracted Results",
+      description = "Values extracted by internal extractors, only used by subsequent requests",
+      example = r#"{"csrf": ["d41d8cd98f00b204"]}"#
+    )
+  )]
+  internal_result: BTreeMap<String, HashSet<String>>,
 }
 
 impl OperatorResult {
@@ -331,4 +391,204 @@ impl OperatorResult {
   pub fn extract_result(&self) -> BTreeMap<String, HashSet<String>> {
     self.extract_result.clone()
   }
+  /// 内部提取器的结果，每个名称只取一个值，多个值时取排序后的第一个保证结果稳定
+  pub fn internal_result(&self) -> BTreeMap<String, String> {
+    self
+      .internal_result
+      .iter()
+      .filter_map(|(k, v)| v.iter().min().map(|v| (k.clone(), v.clone())))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn operators(matchers: serde_json::Value) -> Operators {
+    let mut operators: Operators =
+      serde_json::from_value(serde_json::json!({ "matchers": matchers })).unwrap();
+    operators.compile().unwrap();
+    operators
+  }
+
+  fn matched(operators: &Operators, response: &Response) -> bool {
+    let mut result = OperatorResult::default();
+    operators.matcher(response, &mut result).unwrap();
+    result.is_matched()
+  }
+
+  #[test]
+  fn binary_matcher() {
+    // MySQL的握手包，body不是utf8
+    let banner = b"\x4a\x00\x00\x00\x0a5.7.44\x00\x08\x00\x00\x00\xff\xf7".to_vec();
+    let response: Response = slinger::http::Response::builder()
+      .body(banner)
+      .unwrap()
+      .into();
+    let mysql = operators(serde_json::json!([
+      {"type": "binary", "binary": ["0a352e37", "ff f7"], "condition": "and"}
+    ]));
+    assert!(matched(&mysql, &response));
+    let missing = operators(serde_json::json!([
+      {"type": "binary", "binary": ["0a352e37", "5244500d"], "condition": "and"}
+    ]));
+    assert!(!matched(&missing, &response));
+    let or = operators(serde_json::json!([
+      {"type": "binary", "binary": ["5244500d", "352e37"]}
+    ]));
+    assert!(matched(&or, &response));
+    let negative = operators(serde_json::json!([
+      {"type": "binary", "binary": ["5244500d"], "negative": true}
+    ]));
+    assert!(matched(&negative, &response));
+    let hex_word = operators(serde_json::json!([
+      {"type": "word", "words": ["0a352e372e3434"], "encoding": "hex"}
+    ]));
+    assert!(matched(&hex_word, &response));
+    let mut invalid: Operators = serde_json::from_value(serde_json::json!({
+      "matchers": [{"type": "binary", "binary": ["zz"]}]
+    }))
+    .unwrap();
+    assert!(invalid.compile().is_err());
+  }
+
+  #[test]
+  fn version_matcher() {
+    let response: Response = slinger::http::Response::builder()
+      .header("Server", "Apache/2.4.49 (Unix)")
+      .body(b"<meta name=\"generator\" content=\"WordPress 5.8.2\">".to_vec())
+      .unwrap()
+      .into();
+    let outdated = |matchers: serde_json::Value| {
+      let mut operators: Operators = serde_json::from_value(serde_json::json!({
+        "matchers": matchers,
+        "extractors": [
+          {"type": "regex", "name": "apache", "part": "header", "regex": ["Apache/([\\d.]+)"], "group": 1},
+          {"type": "regex", "name": "wordpress", "internal": true, "regex": ["WordPress ([\\d.]+)"], "group": 1}
+        ]
+      }))
+      .unwrap();
+      operators.compile().unwrap();
+      matched(&operators, &response)
+    };
+    assert!(outdated(serde_json::json!([
+      {"type": "version", "extractor": "wordpress", "version": ["<5.8.3, >=5.0"]}
+    ])));
+    assert!(!outdated(serde_json::json!([
+      {"type": "version", "extractor": "wordpress", "version": ["<5.8.2", ">=6.0"]}
+    ])));
+    assert!(outdated(serde_json::json!([
+      {"type": "version", "version": ["<2.4.50", ">=9.0"]}
+    ])));
+    assert!(!outdated(serde_json::json!([
+      {"type": "version", "version": ["<2.4.50", ">=9.0"], "condition": "and"}
+    ])));
+    assert!(!outdated(serde_json::json!([
+      {"type": "version", "extractor": "missing", "version": [">0"]}
+    ])));
+  }
+
+  #[test]
+  fn dsl_compile_error() {
+    let mut invalid: Operators = serde_json::from_value(serde_json::json!({
+      "matchers": [{"type": "dsl", "dsl": ["status_code == 200 &&"]}]
+    }))
+    .unwrap();
+    assert!(invalid.compile().is_err());
+    let dsl = operators(serde_json::json!([
+      {"type": "dsl", "dsl": ["status_code == 404", "contains(body, 'ok')"], "condition": "and"}
+    ]));
+    let response: Response = slinger::http::Response::builder()
+      .status(404)
+      .body(b"ok".to_vec())
+      .unwrap()
+      .into();
+    assert!(matched(&dsl, &response));
+  }
+
+  #[test]
+  fn repeated_header_part() {
+    let response: Response = slinger::http::Response::builder()
+      .header("Set-Cookie", "PHPSESSID=abc123; path=/")
+      .header("Set-Cookie", "lang=en")
+      .header("Via", "1.1 varnish")
+      .header("Via", "1.1 squid")
+      .body(Vec::new())
+      .unwrap()
+      .into();
+    assert_eq!(
+      response.get_header("set-cookie").as_deref(),
+      Some("PHPSESSID=abc123; path=/, lang=en")
+    );
+    assert_eq!(response.get_header("missing"), None);
+    // 同名的响应头都可以匹配，不只是第一个
+    assert!(matched(
+      &operators(serde_json::json!([
+        {"type": "word", "part": "set-cookie", "words": ["PHPSESSID=", "lang=en"], "condition": "and"}
+      ])),
+      &response
+    ));
+    assert!(matched(
+      &operators(serde_json::json!([
+        {"type": "regex", "part": "via", "regex": ["varnish, 1\\.1 squid$"]}
+      ])),
+      &response
+    ));
+  }
+
+  #[test]
+  fn kval_and_dsl_extractor() {
+    let response: Response = slinger::http::Response::builder()
+      .status(200)
+      .header("X-Powered-By", "PHP/8.1.2")
+      .header("Set-Cookie", "PHPSESSID=abc123; path=/; HttpOnly")
+      .header("Set-Cookie", "lang=en")
+      .body(b"<title>Admin</title>".to_vec())
+      .unwrap()
+      .into();
+    let mut operators: Operators = serde_json::from_value(serde_json::json!({
+      "extractors": [
+        {"type": "kval", "name": "powered", "kval": ["x_powered_by"]},
+        {"type": "kval", "name": "session", "kval": ["phpsessid", "Lang"]},
+        {"type": "dsl", "name": "computed", "dsl": ["to_upper(trim_suffix(trim_prefix(body, '<title>'), '</title>'))", "status_code + 1"]}
+      ]
+    }))
+    .unwrap();
+    operators.compile().unwrap();
+    let mut result = OperatorResult::default();
+    operators.extractor(None, &response, &mut result);
+    let extract = result.extract_result();
+    assert_eq!(extract["powered"], HashSet::from(["PHP/8.1.2".to_string()]));
+    assert_eq!(
+      extract["session"],
+      HashSet::from(["abc123".to_string(), "en".to_string()])
+    );
+    assert_eq!(
+      extract["computed"],
+      HashSet::from(["ADMIN".to_string(), "201".to_string()])
+    );
+  }
+
+  #[test]
+  fn jarm_matcher() {
+    let mut response: Response = slinger::http::Response::builder()
+      .body(Vec::new())
+      .unwrap()
+      .into();
+    let jarm = operators(serde_json::json!([
+      {"type": "jarm", "hash": ["07D14D16D21D21D07C42D41D00041D24A458A375EEF0C576D23A7BAB9A9FB1"]}
+    ]));
+    // 没有TLS指纹的响应不匹配
+    assert!(!matched(&jarm, &response));
+    response.extensions_mut().insert(TlsFingerprint {
+      jarm: "07d14d16d21d21d07c42d41d00041d24a458a375eef0c576d23a7bab9a9fb1".to_string(),
+      ja3s: Some("e35df3e00ca4ef31d42b34bebaa2f86e".to_string()),
+    });
+    assert!(matched(&jarm, &response));
+    let ja3s = operators(serde_json::json!([
+      {"type": "jarm", "hash": ["e35df3e00ca4ef31d42b34bebaa2f86e"]}
+    ]));
+    assert!(matched(&ja3s, &response));
+  }
 }
@@ -12,6 +12,9 @@ pub trait OperatorTarget {
   /// Get a specific header value by name
   fn get_header(&self, name: &str) -> Option<String>;
 
+  /// Get the url of the request
+  fn get_url(&self) -> String;
+
   /// Get the full content (headers + body) for matching
   fn get_full_content(&self) -> String {
     let body = self.get_body();
@@ -54,12 +57,26 @@ impl OperatorTarget for Response {
     self.body().clone()
   }
 
+  // 同名的响应头使用逗号连接，和DSL的变量一样
   fn get_header(&self, name: &str) -> Option<String> {
-    self
+    let values: Vec<&str> = self
       .headers()
-      .get(name)
-      .and_then(|v| v.to_str().ok())
-      .map(|s| s.to_string())
+      .get_all(name)
+      .iter()
+      .filter_map(|v| v.to_str().ok())
+      .collect();
+    if values.is_empty() {
+      return None;
+    }
+    Some(values.join(", "))
+  }
+
+  // 响应扩展中有请求时使用请求的地址
+  fn get_url(&self) -> String {
+    match self.request() {
+      Some(request) => request.uri().to_string(),
+      None => self.uri().to_string(),
+    }
   }
 }
 
@@ -84,6 +101,10 @@ impl OperatorTarget for Request {
       .and_then(|v| v.to_str().ok())
       .map(|s| s.to_string())
   }
+
+  fn get_url(&self) -> String {
+    self.uri().to_string()
+  }
 }
 
 /// Part represents which part of the target to extract/match from
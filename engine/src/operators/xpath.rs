@@ -0,0 +1,809 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tl::{HTMLTag, Node, NodeHandle, Parser, VDom, VDomGuard};
+
+/// 解析后的HTML文档，支持XPath和CSS选择器查询
+///
+/// 以 `/` 开头的按照XPath查询，例如 `//meta[@name='generator']/@content`，
+/// 其他的按照CSS选择器查询，例如 `meta[name=generator]`，
+/// CSS选择器后面可以加上 `/@content` 或者 `/text()` 取属性值或者文本
+pub struct Document {
+  dom: VDomGuard,
+}
+
+/// 响应解析过的HTML文档，放在响应的扩展中
+///
+/// 同一个响应上的XPath匹配器和提取器只解析一次，克隆的响应共享同一个缓存，
+/// 按照匹配的内容区分，例如body和header是不同的文档
+#[derive(Clone, Default)]
+pub struct DocumentCache(Arc<Mutex<HashMap<String, Option<Arc<Document>>>>>);
+
+impl DocumentCache {
+  /// 返回缓存的文档，没有缓存时解析并且缓存，解析失败的内容也会缓存
+  pub fn get_or_parse(&self, corpus: &str) -> Option<Arc<Document>> {
+    let Ok(mut documents) = self.0.lock() else {
+      return Document::parse(corpus).map(Arc::new);
+    };
+    documents
+      .entry(corpus.to_string())
+      .or_insert_with(|| Document::parse(corpus).map(Arc::new))
+      .clone()
+  }
+}
+
+/// 有缓存的时候使用缓存的文档，否则直接解析
+pub(crate) fn parse_document(cache: Option<&DocumentCache>, corpus: &str) -> Option<Arc<Document>> {
+  match cache {
+    Some(cache) => cache.get_or_parse(corpus),
+    None => Document::parse(corpus).map(Arc::new),
+  }
+}
+
+// 查询到的元素或者属性值、文本
+enum Item {
+  Node(NodeHandle),
+  Value(String),
+}
+
+impl Document {
+  pub fn parse(html: &str) -> Option<Self> {
+    // 文档持有输入的字符串，解析后可以放到响应的扩展中复用
+    unsafe { tl::parse_owned(html.to_string(), tl::ParserOptions::default()) }
+      .ok()
+      .map(|dom| Self { dom })
+  }
+  fn dom(&self) -> &VDom<'_> {
+    self.dom.get_ref()
+  }
+  /// 返回查询到的值，没有指定属性时元素返回它的文本
+  ///
+  /// 查询到的元素即使文本为空也会返回一个空字符串，用来判断元素是否存在
+  pub fn select(&self, expression: &str, attribute: Option<&str>) -> Vec<String> {
+    let expression = expression.trim();
+    let items = if expression.starts_with('/') {
+      match parse_path(expression) {
+        Some(steps) => self.evaluate(&steps),
+        None => Vec::new(),
+      }
+    } else {
+      self.query_selector(expression)
+    };
+    let parser = self.dom().parser();
+    items
+      .into_iter()
+      .filter_map(|item| match item {
+        Item::Node(handle) => {
+          let node = handle.get(parser)?;
+          match attribute {
+            Some(name) => node.as_tag().and_then(|tag| get_attribute(tag, name)),
+            None => Some(node.inner_text(parser).trim().to_string()),
+          }
+        }
+        Item::Value(value) => Some(value),
+      })
+      .collect()
+  }
+  fn query_selector(&self, expression: &str) -> Vec<Item> {
+    let (selector, last) = match expression.rsplit_once('/') {
+      Some((selector, last)) if last == "text()" || last.starts_with('@') => (selector, Some(last)),
+      _ => (expression, None),
+    };
+    let parser = self.dom().parser();
+    let mut handles = Vec::new();
+    for group in split_outside(selector, |c| c == ',') {
+      handles.extend(self.css_select(group));
+    }
+    let mut items = Vec::new();
+    for handle in handles {
+      match last.and_then(|last| last.strip_prefix('@')) {
+        Some(name) => items.extend(
+          handle
+            .get(parser)
+            .and_then(Node::as_tag)
+            .and_then(|tag| get_attribute(tag, name))
+            .map(Item::Value),
+        ),
+        None if last.is_some() => {
+          items.extend(text_children(parser, self.children(Some(handle))).map(Item::Value))
+        }
+        None => items.push(Item::Node(handle)),
+      }
+    }
+    items
+  }
+  // tl只支持单个的复合选择器，后代和子元素组合器在这里处理
+  fn css_select(&self, selector: &str) -> Vec<NodeHandle> {
+    let parser = self.dom().parser();
+    let mut context: Vec<Option<NodeHandle>> = vec![None];
+    let mut child = false;
+    for compound in split_outside(selector, |c| c.is_whitespace() || c == '>') {
+      if compound == ">" {
+        child = true;
+        continue;
+      }
+      let Some(selector) = tl::parse_query_selector(compound) else {
+        return Vec::new();
+      };
+      let mut next = Vec::new();
+      let mut seen = HashSet::new();
+      for node in context {
+        let mut nodes = Vec::new();
+        if child {
+          nodes.extend(self.children(node).into_iter().map(Some));
+        } else {
+          self.descendants(node, &mut nodes);
+        }
+        for handle in nodes.into_iter().flatten() {
+          if handle
+            .get(parser)
+            .is_some_and(|n| n.as_tag().is_some() && selector.matches(n))
+            && seen.insert(handle)
+          {
+            next.push(Some(handle));
+          }
+        }
+      }
+      context = next;
+      child = false;
+    }
+    context.into_iter().flatten().collect()
+  }
+  fn evaluate(&self, steps: &[Step]) -> Vec<Item> {
+    let parser = self.dom().parser();
+    // None 表示文档的根节点
+    let mut context: Vec<Option<NodeHandle>> = vec![None];
+    for (index, step) in steps.iter().enumerate() {
+      let mut candidates = Vec::new();
+      let mut seen = HashSet::new();
+      for node in context {
+        let mut nodes = vec![node];
+        if matches!(step.axis, Axis::Descendant) {
+          self.descendants(node, &mut nodes);
+        }
+        candidates.extend(nodes.into_iter().filter(|n| seen.insert(*n)));
+      }
+      let name = match &step.test {
+        NodeTest::Element(name) => name,
+        // 属性和文本只能是最后一步
+        NodeTest::Attribute(_) | NodeTest::Text if index + 1 != steps.len() => return Vec::new(),
+        NodeTest::Attribute(name) => {
+          let tags = candidates
+            .iter()
+            .filter_map(|n| n.and_then(|h| h.get(parser)).and_then(Node::as_tag));
+          return tags
+            .flat_map(|tag| match name.as_str() {
+              "*" => tag
+                .attributes()
+                .iter()
+                .filter_map(|(_, v)| v.map(|v| v.to_string()))
+                .collect(),
+              name => get_attribute(tag, name).into_iter().collect::<Vec<_>>(),
+            })
+            .map(Item::Value)
+            .collect();
+        }
+        NodeTest::Text => {
+          return candidates
+            .into_iter()
+            .flat_map(|n| text_children(parser, self.children(n)).collect::<Vec<_>>())
+            .map(Item::Value)
+            .collect();
+        }
+      };
+      let mut next = Vec::new();
+      for node in candidates {
+        // 位置是相对于同一个父节点下满足条件的元素
+        let mut selected: Vec<NodeHandle> = self
+          .children(node)
+          .iter()
+          .filter(|h| {
+            h.get(parser)
+              .and_then(Node::as_tag)
+              .is_some_and(|tag| name == "*" || tag_name(tag).eq_ignore_ascii_case(name))
+          })
+          .copied()
+          .collect();
+        for predicate in step.predicates.iter() {
+          let last = selected.len();
+          selected = selected
+            .into_iter()
+            .enumerate()
+            .filter(|(position, handle)| {
+              let context = Context {
+                document: self,
+                handle: *handle,
+                position: position + 1,
+                last,
+              };
+              context.test(predicate)
+            })
+            .map(|(_, handle)| handle)
+            .collect();
+        }
+        next.extend(selected.into_iter().map(Some));
+      }
+      context = next;
+    }
+    context.into_iter().flatten().map(Item::Node).collect()
+  }
+  fn children(&self, node: Option<NodeHandle>) -> Vec<NodeHandle> {
+    match node {
+      None => self.dom().children().to_vec(),
+      Some(handle) => handle
+        .get(self.dom().parser())
+        .and_then(Node::as_tag)
+        .map_or(Vec::new(), |tag| tag.children().top().to_vec()),
+    }
+  }
+  fn descendants(&self, node: Option<NodeHandle>, all: &mut Vec<Option<NodeHandle>>) {
+    for child in self.children(node) {
+      if child
+        .get(self.dom().parser())
+        .and_then(Node::as_tag)
+        .is_some()
+      {
+        all.push(Some(child));
+        self.descendants(Some(child), all);
+      }
+    }
+  }
+}
+
+fn tag_name<'t>(tag: &'t HTMLTag) -> std::borrow::Cow<'t, str> {
+  tag.name().as_utf8_str()
+}
+
+// HTML的属性名不区分大小写
+fn get_attribute(tag: &HTMLTag, name: &str) -> Option<String> {
+  tag
+    .attributes()
+    .iter()
+    .find(|(k, _)| k.eq_ignore_ascii_case(name))
+    .map(|(_, v)| v.map(|v| v.to_string()).unwrap_or_default())
+}
+
+// 直接子节点中的文本
+fn text_children<'p>(
+  parser: &'p Parser,
+  children: Vec<NodeHandle>,
+) -> impl Iterator<Item = String> + 'p {
+  children
+    .into_iter()
+    .filter_map(|h| h.get(parser).and_then(Node::as_raw))
+    .map(|raw| raw.as_utf8_str().trim().to_string())
+    .filter(|text| !text.is_empty())
+}
+
+#[derive(Debug)]
+enum Axis {
+  Child,
+  Descendant,
+}
+
+#[derive(Debug)]
+enum NodeTest {
+  Element(String),
+  Attribute(String),
+  Text,
+}
+
+#[derive(Debug)]
+struct Step {
+  axis: Axis,
+  test: NodeTest,
+  predicates: Vec<Expr>,
+}
+
+// 只支持常用的XPath子集：child和descendant轴，元素、属性和text()，以及谓词
+fn parse_path(expression: &str) -> Option<Vec<Step>> {
+  let mut steps = Vec::new();
+  let mut rest = expression;
+  while !rest.is_empty() {
+    let axis = if let Some(r) = rest.strip_prefix("//") {
+      rest = r;
+      Axis::Descendant
+    } else if let Some(r) = rest.strip_prefix('/') {
+      rest = r;
+      Axis::Child
+    } else {
+      return None;
+    };
+    let end = step_end(rest);
+    let (step, r) = rest.split_at(end);
+    rest = r;
+    let (test, mut predicates) = match step.find('[') {
+      Some(i) => (&step[..i], &step[i..]),
+      None => (step, ""),
+    };
+    let test = match test.trim() {
+      "text()" => NodeTest::Text,
+      "" => return None,
+      test => match test.strip_prefix('@') {
+        Some(name) => NodeTest::Attribute(name.to_string()),
+        None => NodeTest::Element(test.to_string()),
+      },
+    };
+    let mut exprs = Vec::new();
+    while let Some(p) = predicates.strip_prefix('[') {
+      let close = step_end_bracket(p)?;
+      exprs.push(ExprParser::new(&p[..close]).parse()?);
+      predicates = &p[close + 1..];
+    }
+    if !predicates.trim().is_empty() {
+      return None;
+    }
+    steps.push(Step {
+      axis,
+      test,
+      predicates: exprs,
+    });
+  }
+  Some(steps)
+}
+
+// 按照括号和引号外面的分隔符拆分，`>` 作为单独的一项保留
+fn split_outside(s: &str, separator: impl Fn(char) -> bool) -> Vec<&str> {
+  let mut parts = Vec::new();
+  let mut depth = 0;
+  let mut quote = None;
+  let mut start = 0;
+  for (i, c) in s.char_indices() {
+    match (quote, c) {
+      (Some(q), c) if c == q => quote = None,
+      (Some(_), _) => {}
+      (None, '\'' | '"') => quote = Some(c),
+      (None, '[' | '(') => depth += 1,
+      (None, ']' | ')') => depth -= 1,
+      (None, c) if depth == 0 && separator(c) => {
+        parts.push(&s[start..i]);
+        if c == '>' {
+          parts.push(">");
+        }
+        start = i + c.len_utf8();
+      }
+      _ => {}
+    }
+  }
+  parts.push(&s[start..]);
+  parts
+    .into_iter()
+    .map(str::trim)
+    .filter(|p| !p.is_empty())
+    .collect()
+}
+
+// 当前步骤的结束位置，谓词和字符串中的 `/` 不算
+fn step_end(s: &str) -> usize {
+  let mut depth = 0;
+  let mut quote = None;
+  for (i, c) in s.char_indices() {
+    match (quote, c) {
+      (Some(q), c) if c == q => quote = None,
+      (Some(_), _) => {}
+      (None, '\'' | '"') => quote = Some(c),
+      (None, '[') => depth += 1,
+      (None, ']') => depth -= 1,
+      (None, '/') if depth == 0 => return i,
+      _ => {}
+    }
+  }
+  s.len()
+}
+
+// 和开头的 `[` 对应的 `]`
+fn step_end_bracket(s: &str) -> Option<usize> {
+  let mut depth = 0;
+  let mut quote = None;
+  for (i, c) in s.char_indices() {
+    match (quote, c) {
+      (Some(q), c) if c == q => quote = None,
+      (Some(_), _) => {}
+      (None, '\'' | '"') => quote = Some(c),
+      (None, '[') => depth += 1,
+      (None, ']') if depth == 0 => return Some(i),
+      (None, ']') => depth -= 1,
+      _ => {}
+    }
+  }
+  None
+}
+
+#[derive(Debug)]
+enum Expr {
+  Or(Box<Expr>, Box<Expr>),
+  And(Box<Expr>, Box<Expr>),
+  Compare(Box<Expr>, bool, Box<Expr>),
+  Call(String, Vec<Expr>),
+  Attribute(String),
+  Child(String),
+  Text,
+  Current,
+  Literal(String),
+  Number(f64),
+}
+
+enum Value {
+  Bool(bool),
+  Number(f64),
+  // None 表示节点不存在
+  String(Option<String>),
+}
+
+impl Value {
+  fn as_bool(&self) -> bool {
+    match self {
+      Value::Bool(b) => *b,
+      Value::Number(n) => *n != 0.0,
+      Value::String(s) => s.is_some(),
+    }
+  }
+  fn as_string(&self) -> String {
+    match self {
+      Value::Bool(b) => b.to_string(),
+      Value::Number(n) => n.to_string(),
+      Value::String(s) => s.clone().unwrap_or_default(),
+    }
+  }
+}
+
+// 谓词计算时的上下文节点
+struct Context<'d> {
+  document: &'d Document,
+  handle: NodeHandle,
+  position: usize,
+  last: usize,
+}
+
+impl Context<'_> {
+  fn test(&self, expr: &Expr) -> bool {
+    match self.value(expr) {
+      // 数字谓词表示位置，例如 `[1]`
+      Value::Number(n) => n == self.position as f64,
+      v => v.as_bool(),
+    }
+  }
+  fn tag(&self) -> Option<&HTMLTag<'_>> {
+    self
+      .handle
+      .get(self.document.dom().parser())
+      .and_then(Node::as_tag)
+  }
+  fn value(&self, expr: &Expr) -> Value {
+    let parser = self.document.dom().parser();
+    match expr {
+      Expr::Or(l, r) => Value::Bool(self.value(l).as_bool() || self.value(r).as_bool()),
+      Expr::And(l, r) => Value::Bool(self.value(l).as_bool() && self.value(r).as_bool()),
+      Expr::Compare(l, eq, r) => {
+        let (l, r) = (self.value(l), self.value(r));
+        let equal = match (&l, &r) {
+          (Value::Number(a), b) | (b, Value::Number(a)) => b.as_string().trim().parse() == Ok(*a),
+          _ => l.as_string() == r.as_string(),
+        };
+        Value::Bool(equal == *eq)
+      }
+      Expr::Attribute(name) => Value::String(self.tag().and_then(|tag| get_attribute(tag, name))),
+      Expr::Child(name) => Value::String(
+        self
+          .document
+          .children(Some(self.handle))
+          .iter()
+          .filter_map(|h| h.get(parser))
+          .find(|n| {
+            n.as_tag()
+              .is_some_and(|tag| name == "*" || tag_name(tag).eq_ignore_ascii_case(name))
+          })
+          .map(|n| n.inner_text(parser).to_string()),
+      ),
+      Expr::Text => {
+        let mut texts = text_children(parser, self.document.children(Some(self.handle)));
+        Value::String(texts.next())
+      }
+      Expr::Current => Value::String(
+        self
+          .handle
+          .get(parser)
+          .map(|n| n.inner_text(parser).to_string()),
+      ),
+      Expr::Literal(s) => Value::String(Some(s.clone())),
+      Expr::Number(n) => Value::Number(*n),
+      Expr::Call(name, args) => self.call(name, args),
+    }
+  }
+  fn call(&self, name: &str, args: &[Expr]) -> Value {
+    let string = |i: usize| {
+      args
+        .get(i)
+        .map(|a| self.value(a).as_string())
+        .unwrap_or_default()
+    };
+    match name {
+      "contains" => Value::Bool(string(0).contains(&string(1))),
+      "starts-with" => Value::Bool(string(0).starts_with(&string(1))),
+      "ends-with" => Value::Bool(string(0).ends_with(&string(1))),
+      "not" => Value::Bool(!args.first().is_some_and(|a| self.value(a).as_bool())),
+      "normalize-space" => {
+        let s = if args.is_empty() {
+          self.value(&Expr::Current).as_string()
+        } else {
+          string(0)
+        };
+        Value::String(Some(s.split_whitespace().collect::<Vec<_>>().join(" ")))
+      }
+      "lower-case" => Value::String(Some(string(0).to_lowercase())),
+      "string" => Value::String(Some(string(0))),
+      "string-length" => Value::Number(string(0).chars().count() as f64),
+      "position" => Value::Number(self.position as f64),
+      "last" => Value::Number(self.last as f64),
+      _ => Value::Bool(false),
+    }
+  }
+}
+
+// 谓词表达式的递归下降解析
+struct ExprParser<'s> {
+  input: &'s str,
+  pos: usize,
+}
+
+impl<'s> ExprParser<'s> {
+  fn new(input: &'s str) -> Self {
+    Self { input, pos: 0 }
+  }
+  fn parse(mut self) -> Option<Expr> {
+    let expr = self.or()?;
+    self.skip_whitespace();
+    (self.pos == self.input.len()).then_some(expr)
+  }
+  fn rest(&self) -> &'s str {
+    &self.input[self.pos..]
+  }
+  fn skip_whitespace(&mut self) {
+    let trimmed = self.rest().trim_start();
+    self.pos = self.input.len() - trimmed.len();
+  }
+  fn eat(&mut self, token: &str) -> bool {
+    self.skip_whitespace();
+    if self.rest().starts_with(token) {
+      self.pos += token.len();
+      true
+    } else {
+      false
+    }
+  }
+  // `and`、`or` 后面必须是分隔符，避免把 `order` 之类的元素名当成关键字
+  fn eat_keyword(&mut self, keyword: &str) -> bool {
+    self.skip_whitespace();
+    let rest = self.rest();
+    if rest.starts_with(keyword)
+      && rest[keyword.len()..]
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_whitespace() || c == '(')
+    {
+      self.pos += keyword.len();
+      true
+    } else {
+      false
+    }
+  }
+  fn or(&mut self) -> Option<Expr> {
+    let mut left = self.and()?;
+    while self.eat_keyword("or") {
+      left = Expr::Or(Box::new(left), Box::new(self.and()?));
+    }
+    Some(left)
+  }
+  fn and(&mut self) -> Option<Expr> {
+    let mut left = self.compare()?;
+    while self.eat_keyword("and") {
+      left = Expr::And(Box::new(left), Box::new(self.compare()?));
+    }
+    Some(left)
+  }
+  fn compare(&mut self) -> Option<Expr> {
+    let left = self.primary()?;
+    if self.eat("!=") {
+      return Some(Expr::Compare(
+        Box::new(left),
+        false,
+        Box::new(self.primary()?),
+      ));
+    }
+    if self.eat("=") {
+      return Some(Expr::Compare(
+        Box::new(left),
+        true,
+        Box::new(self.primary()?),
+      ));
+    }
+    Some(left)
+  }
+  fn primary(&mut self) -> Option<Expr> {
+    self.skip_whitespace();
+    let rest = self.rest();
+    let first = rest.chars().next()?;
+    match first {
+      '(' => {
+        self.pos += 1;
+        let expr = self.or()?;
+        self.eat(")").then_some(expr)
+      }
+      '\'' | '"' => {
+        let end = rest[1..].find(first)?;
+        self.pos += end + 2;
+        Some(Expr::Literal(rest[1..end + 1].to_string()))
+      }
+      '@' => {
+        self.pos += 1;
+        Some(Expr::Attribute(self.name()?))
+      }
+      '.' if !rest[1..].starts_with(|c: char| c.is_ascii_digit()) => {
+        self.pos += 1;
+        Some(Expr::Current)
+      }
+      c if c.is_ascii_digit() || c == '.' => {
+        let len = rest
+          .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+          .unwrap_or(rest.len());
+        self.pos += len;
+        rest[..len].parse().ok().map(Expr::Number)
+      }
+      _ => {
+        let name = self.name()?;
+        if !self.eat("(") {
+          return Some(Expr::Child(name));
+        }
+        let mut args = Vec::new();
+        if !self.eat(")") {
+          loop {
+            args.push(self.or()?);
+            if self.eat(")") {
+              break;
+            }
+            if !self.eat(",") {
+              return None;
+            }
+          }
+        }
+        Some(match name.as_str() {
+          "text" => Expr::Text,
+          _ => Expr::Call(name, args),
+        })
+      }
+    }
+  }
+  fn name(&mut self) -> Option<String> {
+    let rest = self.rest();
+    let len = rest
+      .find(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | ':' | '*')))
+      .unwrap_or(rest.len());
+    if len == 0 {
+      return None;
+    }
+    self.pos += len;
+    Some(rest[..len].to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const HTML: &str = r#"<html><head>
+<title> Welcome </title>
+<meta name="generator" content="WordPress 6.4.2">
+<meta property="og:site_name" content="Blog">
+</head><body>
+<ul id="menu"><li class="item">Home</li><li class="item active">About</li><li>Contact</li></ul>
+<a href="/login" target="_blank">Sign in</a>
+<div><a href="/docs">Docs <b>v2</b></a></div>
+</body></html>"#;
+
+  #[test]
+  fn xpath() {
+    let doc = Document::parse(HTML).unwrap();
+    assert_eq!(doc.select("/html/head/title", None), vec!["Welcome"]);
+    assert_eq!(
+      doc.select("//meta[@name='generator']/@content", None),
+      vec!["WordPress 6.4.2"]
+    );
+    assert_eq!(
+      doc.select("//meta[@name=\"generator\"]", Some("content")),
+      vec!["WordPress 6.4.2"]
+    );
+    assert_eq!(doc.select("//li[2]", None), vec!["About"]);
+    assert_eq!(doc.select("//li[last()]", None), vec!["Contact"]);
+    assert_eq!(
+      doc.select(
+        "//ul[@id='menu']/li[contains(@class, 'item') and not(contains(@class, 'active'))]/text()",
+        None
+      ),
+      vec!["Home"]
+    );
+    assert_eq!(
+      doc.select(
+        "//a[starts-with(@href, '/') or @target='_blank']/@href",
+        None
+      ),
+      vec!["/login", "/docs"]
+    );
+    assert_eq!(
+      doc.select("//a[contains(text(), 'Sign')]", None),
+      vec!["Sign in"]
+    );
+    assert_eq!(doc.select("//div[a]//b", None), vec!["v2"]);
+    assert!(doc.select("//script", None).is_empty());
+    assert!(
+      doc
+        .select("//meta[@name='robots']/@content", None)
+        .is_empty()
+    );
+    // 不支持的语法不会匹配
+    assert!(doc.select("//a[", None).is_empty());
+  }
+
+  #[test]
+  fn document_cache() {
+    use crate::operators::{OperatorResult, Operators};
+    use crate::results::MatchEvent;
+    use slinger::Response;
+    let mut operators: Operators = serde_json::from_value(serde_json::json!({
+      "matchers-condition": "and",
+      "matchers": [
+        {"type": "xpath", "xpath": ["/html/head/title"]},
+        {"type": "xpath", "xpath": ["//meta[@name='generator']"]}
+      ],
+      "extractors": [
+        {"type": "xpath", "name": "version", "xpath": ["//meta[@name='generator']/@content"]}
+      ]
+    }))
+    .unwrap();
+    operators.compile().unwrap();
+    let response: Response = slinger::http::Response::builder()
+      .body(HTML)
+      .unwrap()
+      .into();
+    // MatchEvent的响应带上缓存，所有模板共享
+    let response = MatchEvent::new(&response).response().unwrap();
+    let cache = response
+      .extensions()
+      .get::<DocumentCache>()
+      .unwrap()
+      .clone();
+    let mut result = OperatorResult::default();
+    operators.matcher(&response, &mut result).unwrap();
+    operators.extractor(None, &response, &mut result);
+    assert!(result.is_matched());
+    assert_eq!(
+      result.extract_result().get("version"),
+      Some(&HashSet::from(["WordPress 6.4.2".to_string()]))
+    );
+    // 两个匹配器和一个提取器只解析了一次body
+    assert_eq!(cache.0.lock().unwrap().len(), 1);
+    let document = cache.get_or_parse(HTML).unwrap();
+    assert!(Arc::ptr_eq(
+      &document,
+      &parse_document(Some(&cache), HTML).unwrap()
+    ));
+  }
+
+  #[test]
+  fn css_selector() {
+    let doc = Document::parse(HTML).unwrap();
+    assert_eq!(
+      doc.select("meta[name=generator]/@content", None),
+      vec!["WordPress 6.4.2"]
+    );
+    assert_eq!(
+      doc.select("meta[property=\"og:site_name\"]", Some("content")),
+      vec!["Blog"]
+    );
+    assert_eq!(doc.select("li.active", None), vec!["About"]);
+    assert_eq!(doc.select("#menu li", None).len(), 3);
+    assert_eq!(
+      doc.select("body > a, ul>li.active", None),
+      vec!["Sign in", "About"]
+    );
+    assert_eq!(doc.select("div a/text()", None), vec!["Docs"]);
+  }
+}
@@ -0,0 +1,417 @@
+use std::fmt::{Display, Formatter};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+// 报文头的标志位
+const FLAG_QR: u16 = 0x8000;
+const FLAG_AA: u16 = 0x0400;
+const FLAG_TC: u16 = 0x0200;
+const FLAG_RD: u16 = 0x0100;
+const FLAG_RA: u16 = 0x0080;
+// 压缩指针最多跳转的次数，防止恶意报文死循环
+const MAX_POINTER_JUMPS: usize = 32;
+
+/// DNS报文的问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsQuestion {
+  pub name: String,
+  pub record_type: u16,
+  pub class: u16,
+}
+
+impl Display for DnsQuestion {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{}\t{}\t{}",
+      self.name,
+      class_name(self.class),
+      type_name(self.record_type)
+    )
+  }
+}
+
+/// DNS报文的资源记录，数据已经转换成和dig一样的文本格式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsRecord {
+  pub name: String,
+  pub record_type: u16,
+  pub class: u16,
+  pub ttl: u32,
+  pub data: String,
+}
+
+impl Display for DnsRecord {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{}\t{}\t{}\t{}\t{}",
+      self.name,
+      self.ttl,
+      class_name(self.class),
+      type_name(self.record_type),
+      self.data
+    )
+  }
+}
+
+/// 一个DNS报文，只支持查询和解析应答，不支持更新和签名
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DnsMessage {
+  pub id: u16,
+  pub flags: u16,
+  pub questions: Vec<DnsQuestion>,
+  pub answers: Vec<DnsRecord>,
+  pub authorities: Vec<DnsRecord>,
+  pub additionals: Vec<DnsRecord>,
+}
+
+impl DnsMessage {
+  /// 只有一个问题的查询报文
+  pub fn query(id: u16, question: DnsQuestion, recursion: bool) -> Self {
+    Self {
+      id,
+      flags: if recursion { FLAG_RD } else { 0 },
+      questions: vec![question],
+      ..Default::default()
+    }
+  }
+  /// 编码成wire格式，域名的标签超过63字节时返回None
+  pub fn encode(&self) -> Option<Vec<u8>> {
+    let mut buf = Vec::with_capacity(512);
+    buf.extend_from_slice(&self.id.to_be_bytes());
+    buf.extend_from_slice(&self.flags.to_be_bytes());
+    for count in [
+      self.questions.len(),
+      self.answers.len(),
+      self.authorities.len(),
+      self.additionals.len(),
+    ] {
+      buf.extend_from_slice(&(count as u16).to_be_bytes());
+    }
+    // 查询报文不需要编码资源记录
+    for question in self.questions.iter() {
+      encode_name(&question.name, &mut buf)?;
+      buf.extend_from_slice(&question.record_type.to_be_bytes());
+      buf.extend_from_slice(&question.class.to_be_bytes());
+    }
+    Some(buf)
+  }
+  /// 解析wire格式的报文，报文不完整时返回None
+  pub fn parse(data: &[u8]) -> Option<Self> {
+    let mut reader = Reader { data, offset: 0 };
+    let id = reader.u16()?;
+    let flags = reader.u16()?;
+    let qd = reader.u16()?;
+    let an = reader.u16()?;
+    let ns = reader.u16()?;
+    let ar = reader.u16()?;
+    let mut message = Self {
+      id,
+      flags,
+      ..Default::default()
+    };
+    for _ in 0..qd {
+      message.questions.push(DnsQuestion {
+        name: reader.name()?,
+        record_type: reader.u16()?,
+        class: reader.u16()?,
+      });
+    }
+    for (count, section) in [
+      (an, &mut message.answers),
+      (ns, &mut message.authorities),
+      (ar, &mut message.additionals),
+    ] {
+      for _ in 0..count {
+        let record = reader.record()?;
+        // EDNS的OPT伪记录不是真正的资源记录
+        if record.record_type != 41 {
+          section.push(record);
+        }
+      }
+    }
+    Some(message)
+  }
+  /// 响应码的名称，例如：NOERROR、NXDOMAIN
+  pub fn rcode(&self) -> String {
+    match self.flags & 0x000f {
+      0 => "NOERROR".to_string(),
+      1 => "FORMERR".to_string(),
+      2 => "SERVFAIL".to_string(),
+      3 => "NXDOMAIN".to_string(),
+      4 => "NOTIMP".to_string(),
+      5 => "REFUSED".to_string(),
+      6 => "YXDOMAIN".to_string(),
+      7 => "YXRRSET".to_string(),
+      8 => "NXRRSET".to_string(),
+      9 => "NOTAUTH".to_string(),
+      10 => "NOTZONE".to_string(),
+      code => format!("RCODE{code}"),
+    }
+  }
+  /// 应答被截断，需要使用TCP重新查询
+  pub fn is_truncated(&self) -> bool {
+    self.flags & FLAG_TC != 0
+  }
+}
+
+// 和dig的输出一样
+impl Display for DnsMessage {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let opcode = match (self.flags >> 11) & 0x0f {
+      0 => "QUERY".to_string(),
+      1 => "IQUERY".to_string(),
+      2 => "STATUS".to_string(),
+      4 => "NOTIFY".to_string(),
+      5 => "UPDATE".to_string(),
+      code => code.to_string(),
+    };
+    writeln!(
+      f,
+      ";; ->>HEADER<<- opcode: {opcode}, status: {}, id: {}",
+      self.rcode(),
+      self.id
+    )?;
+    let flags: Vec<&str> = [
+      (FLAG_QR, "qr"),
+      (FLAG_AA, "aa"),
+      (FLAG_TC, "tc"),
+      (FLAG_RD, "rd"),
+      (FLAG_RA, "ra"),
+    ]
+    .into_iter()
+    .filter(|(flag, _)| self.flags & flag != 0)
+    .map(|(_, name)| name)
+    .collect();
+    writeln!(
+      f,
+      ";; flags: {}; QUERY: {}, ANSWER: {}, AUTHORITY: {}, ADDITIONAL: {}",
+      flags.join(" "),
+      self.questions.len(),
+      self.answers.len(),
+      self.authorities.len(),
+      self.additionals.len()
+    )?;
+    if !self.questions.is_empty() {
+      writeln!(f, "\n;; QUESTION SECTION:")?;
+      for question in self.questions.iter() {
+        writeln!(f, ";{question}")?;
+      }
+    }
+    for (title, section) in [
+      ("ANSWER", &self.answers),
+      ("AUTHORITY", &self.authorities),
+      ("ADDITIONAL", &self.additionals),
+    ] {
+      if section.is_empty() {
+        continue;
+      }
+      writeln!(f, "\n;; {title} SECTION:")?;
+      for record in section.iter() {
+        writeln!(f, "{record}")?;
+      }
+    }
+    Ok(())
+  }
+}
+
+/// 记录类型的名称，不认识的类型和dig一样显示为TYPE123
+pub fn type_name(record_type: u16) -> String {
+  let name = match record_type {
+    1 => "A",
+    2 => "NS",
+    5 => "CNAME",
+    6 => "SOA",
+    12 => "PTR",
+    15 => "MX",
+    16 => "TXT",
+    28 => "AAAA",
+    33 => "SRV",
+    41 => "OPT",
+    43 => "DS",
+    52 => "TLSA",
+    255 => "ANY",
+    257 => "CAA",
+    _ => return format!("TYPE{record_type}"),
+  };
+  name.to_string()
+}
+
+/// 类的名称，不认识的类显示为CLASS123
+pub fn class_name(class: u16) -> String {
+  let name = match class {
+    1 => "IN",
+    3 => "CH",
+    4 => "HS",
+    254 => "NONE",
+    255 => "ANY",
+    _ => return format!("CLASS{class}"),
+  };
+  name.to_string()
+}
+
+// 把文本格式的域名编码成标签序列
+fn encode_name(name: &str, buf: &mut Vec<u8>) -> Option<()> {
+  for label in name.trim_end_matches('.').split('.') {
+    if label.is_empty() {
+      continue;
+    }
+    if label.len() > 63 {
+      return None;
+    }
+    buf.push(label.len() as u8);
+    buf.extend_from_slice(label.as_bytes());
+  }
+  buf.push(0);
+  Some(())
+}
+
+// 不可打印的字节和dig一样转义成\DDD
+fn escape_bytes(bytes: &[u8], special: &[u8]) -> String {
+  let mut s = String::with_capacity(bytes.len());
+  for &b in bytes {
+    if special.contains(&b) || b == b'\\' {
+      s.push('\\');
+      s.push(b as char);
+    } else if b.is_ascii_graphic() || b == b' ' {
+      s.push(b as char);
+    } else {
+      s.push_str(&format!("\\{b:03}"));
+    }
+  }
+  s
+}
+
+struct Reader<'a> {
+  data: &'a [u8],
+  offset: usize,
+}
+
+impl Reader<'_> {
+  fn bytes(&mut self, len: usize) -> Option<&[u8]> {
+    let bytes = self.data.get(self.offset..self.offset.checked_add(len)?)?;
+    self.offset += len;
+    Some(bytes)
+  }
+  fn u8(&mut self) -> Option<u8> {
+    self.bytes(1).map(|b| b[0])
+  }
+  fn u16(&mut self) -> Option<u16> {
+    self.bytes(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+  }
+  fn u32(&mut self) -> Option<u32> {
+    self
+      .bytes(4)
+      .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+  }
+  // 读取域名，支持压缩指针，根域名是 `.`
+  fn name(&mut self) -> Option<String> {
+    let mut labels = Vec::new();
+    let mut offset = self.offset;
+    let mut jumps = 0;
+    // 第一次跳转之后，读取位置停在指针后面
+    let mut end = None;
+    loop {
+      let len = *self.data.get(offset)? as usize;
+      match len & 0xc0 {
+        0x00 => {
+          offset += 1;
+          if len == 0 {
+            break;
+          }
+          let label = self.data.get(offset..offset + len)?;
+          labels.push(escape_bytes(label, b"."));
+          offset += len;
+        }
+        0xc0 => {
+          let pointer = u16::from_be_bytes([*self.data.get(offset)?, *self.data.get(offset + 1)?]);
+          end.get_or_insert(offset + 2);
+          jumps += 1;
+          if jumps > MAX_POINTER_JUMPS {
+            return None;
+          }
+          offset = (pointer & 0x3fff) as usize;
+        }
+        _ => return None,
+      }
+    }
+    self.offset = end.unwrap_or(offset);
+    if labels.is_empty() {
+      return Some(".".to_string());
+    }
+    Some(format!("{}.", labels.join(".")))
+  }
+  // 读取<character-string>，带引号
+  fn character_string(&mut self) -> Option<String> {
+    let len = self.u8()? as usize;
+    let bytes = self.bytes(len)?;
+    Some(format!("\"{}\"", escape_bytes(bytes, b"\"")))
+  }
+  fn record(&mut self) -> Option<DnsRecord> {
+    let name = self.name()?;
+    let record_type = self.u16()?;
+    let class = self.u16()?;
+    let ttl = self.u32()?;
+    let len = self.u16()? as usize;
+    let start = self.offset;
+    let end = start.checked_add(len)?;
+    let rdata = self.data.get(start..end)?;
+    let data = match record_type {
+      1 if len == 4 => Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]).to_string(),
+      28 if len == 16 => {
+        let octets: [u8; 16] = rdata.try_into().ok()?;
+        Ipv6Addr::from(octets).to_string()
+      }
+      // NS、CNAME、PTR
+      2 | 5 | 12 => self.name()?,
+      6 => {
+        let mname = self.name()?;
+        let rname = self.name()?;
+        let mut values = Vec::new();
+        for _ in 0..5 {
+          values.push(self.u32()?.to_string());
+        }
+        format!("{mname} {rname} {}", values.join(" "))
+      }
+      15 => {
+        let preference = self.u16()?;
+        format!("{preference} {}", self.name()?)
+      }
+      16 => {
+        let mut strings = Vec::new();
+        while self.offset < end {
+          strings.push(self.character_string()?);
+        }
+        strings.join(" ")
+      }
+      33 => {
+        let priority = self.u16()?;
+        let weight = self.u16()?;
+        let port = self.u16()?;
+        format!("{priority} {weight} {port} {}", self.name()?)
+      }
+      257 if len >= 2 => {
+        let flags = rdata[0];
+        let tag_len = rdata[1] as usize;
+        let tag = rdata.get(2..2 + tag_len)?;
+        let value = rdata.get(2 + tag_len..)?;
+        format!(
+          "{flags} {} \"{}\"",
+          String::from_utf8_lossy(tag),
+          escape_bytes(value, b"\"")
+        )
+      }
+      // RFC3597的未知类型格式
+      _ => format!("\\# {len} {}", hex::encode(rdata)),
+    };
+    // 域名可能是压缩指针，读取完成后直接跳到记录末尾
+    self.offset = end;
+    Some(DnsRecord {
+      name,
+      record_type,
+      class,
+      ttl,
+      data,
+    })
+  }
+}
@@ -1 +1,476 @@
-// #TODO
+mod message;
+
+use crate::common::{ConnectionInfo, Marker};
+use crate::operators::Operators;
+use crate::serde_format::is_default;
+pub use message::{DnsMessage, DnsQuestion, DnsRecord};
+use serde::{Deserialize, Serialize};
+use slinger::{Request, Response};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const DEFAULT_NAME: &str = "{{FQDN}}";
+const DEFAULT_PORT: u16 = 53;
+const DEFAULT_RETRIES: u8 = 2;
+// 读取不到系统配置时使用的公共解析服务器
+const FALLBACK_RESOLVERS: [&str; 2] = ["1.1.1.1:53", "8.8.8.8:53"];
+// UDP应答的最大长度，超过的应答会被截断
+const MAX_UDP_SIZE: usize = 4096;
+
+/// DNS请求，和nuclei的dns模板兼容
+///
+/// 应答的每个部分都是一个匹配的part：`rcode`、`question`、`answer`、`ns`和`extra`，
+/// 每条记录都是和dig一样使用制表符分隔的文本格式，例如：`example.com.\t300\tIN\tA\t93.184.216.34`，
+/// 响应体是完整的dig格式的报文
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct DNSRequest {
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(title = "id of the request", description = "ID of the dns request")
+  )]
+  pub id: Option<String>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "hostname to make dns request for",
+      description = "Name is the hostname to make the dns request for, default is `{{FQDN}}`",
+      example = r#""{{FQDN}}""#
+    )
+  )]
+  pub name: Option<String>,
+  #[serde(rename = "type", default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "type of dns request to make",
+      description = "Type is the type of dns request to make, default is `A`",
+      example = r#""CNAME""#
+    )
+  )]
+  pub request_type: DnsType,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "class of dns request",
+      description = "Class is the class of the dns request, use `chaos` for `version.bind`",
+      example = r#""inet""#
+    )
+  )]
+  pub class: DnsClass,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "retries for dns request",
+      description = "Retries is the number of retries for the dns request when the resolver does not answer, default is 2",
+      example = 2
+    )
+  )]
+  pub retries: Option<u8>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "recurse all servers",
+      description = "Recursion determines if resolver should recurse all records to get fresh results, default is true",
+    )
+  )]
+  pub recursion: Option<bool>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "resolvers for the dns request",
+      description = "Resolvers to use for the dns requests, default port is 53 and default resolvers are the system resolvers",
+      example = r#"&["1.1.1.1", "8.8.8.8:53"]"#
+    )
+  )]
+  pub resolvers: Vec<String>,
+  // Operators for the current request go here.
+  #[serde(flatten)]
+  pub operators: Arc<Operators>,
+}
+
+impl DNSRequest {
+  /// 替换占位符后的问题，PTR查询的IP地址会转换成反向域名
+  pub fn question(&self, marker: &Marker) -> DnsQuestion {
+    let name = marker.replace(self.name.as_deref().unwrap_or(DEFAULT_NAME));
+    let name = name.trim();
+    let name = match name
+      .trim_start_matches('[')
+      .trim_end_matches(']')
+      .parse::<IpAddr>()
+    {
+      Ok(ip) if self.request_type == DnsType::PTR => reverse_name(ip),
+      _ if name.ends_with('.') => name.to_string(),
+      _ => format!("{name}."),
+    };
+    DnsQuestion {
+      name,
+      record_type: self.request_type.code(),
+      class: self.class.code(),
+    }
+  }
+  /// 查询报文，每次的报文ID都是随机的
+  pub fn query(&self, marker: &Marker) -> DnsMessage {
+    DnsMessage::query(
+      rand::random(),
+      self.question(marker),
+      self.recursion.unwrap_or(true),
+    )
+  }
+  /// 替换占位符后的解析服务器地址，没有端口的默认使用53
+  pub fn resolvers(&self, marker: &Marker) -> Vec<String> {
+    self
+      .resolvers
+      .iter()
+      .map(|resolver| resolver_address(&marker.replace(resolver)))
+      .collect()
+  }
+  pub fn retries(&self) -> u8 {
+    self.retries.unwrap_or(DEFAULT_RETRIES)
+  }
+  // 查询参数一样的请求可以合并
+  pub(crate) fn same_query(&self, other: &DNSRequest) -> bool {
+    self.name.as_deref().unwrap_or(DEFAULT_NAME) == other.name.as_deref().unwrap_or(DEFAULT_NAME)
+      && self.request_type == other.request_type
+      && self.class == other.class
+      && self.recursion.unwrap_or(true) == other.recursion.unwrap_or(true)
+      && self.resolvers == other.resolvers
+  }
+}
+
+/// 解析服务器的地址，没有端口的默认使用53
+pub fn resolver_address(resolver: &str) -> String {
+  let resolver = resolver.trim();
+  if resolver.parse::<SocketAddr>().is_ok() {
+    return resolver.to_string();
+  }
+  if let Ok(ip) = resolver
+    .trim_start_matches('[')
+    .trim_end_matches(']')
+    .parse::<IpAddr>()
+  {
+    return SocketAddr::new(ip, DEFAULT_PORT).to_string();
+  }
+  if resolver.contains(':') {
+    return resolver.to_string();
+  }
+  format!("{resolver}:{DEFAULT_PORT}")
+}
+
+/// 系统配置的解析服务器，读取`/etc/resolv.conf`，读取不到时使用公共解析服务器
+pub fn system_resolvers() -> Vec<String> {
+  let resolvers: Vec<String> = std::fs::read_to_string("/etc/resolv.conf")
+    .unwrap_or_default()
+    .lines()
+    .filter_map(|line| line.trim().strip_prefix("nameserver"))
+    .map(|ns| resolver_address(ns.trim()))
+    .collect();
+  if resolvers.is_empty() {
+    return FALLBACK_RESOLVERS.iter().map(|r| r.to_string()).collect();
+  }
+  resolvers
+}
+
+/// 同步的UDP查询，给DSL的`resolve`使用，每个解析服务器最多等待`timeout`，返回第一个应答
+pub fn lookup(
+  resolvers: &[String],
+  name: &str,
+  record_type: DnsType,
+  timeout: Duration,
+) -> Option<DnsMessage> {
+  let name = if name.ends_with('.') {
+    name.to_string()
+  } else {
+    format!("{name}.")
+  };
+  let question = DnsQuestion {
+    name,
+    record_type: record_type.code(),
+    class: DnsClass::Inet.code(),
+  };
+  resolvers.iter().find_map(|resolver| {
+    let query = DnsMessage::query(rand::random(), question.clone(), true);
+    exchange_udp(resolver, &query, timeout)
+  })
+}
+
+fn exchange_udp(resolver: &str, query: &DnsMessage, timeout: Duration) -> Option<DnsMessage> {
+  let addr: SocketAddr = resolver.parse().ok()?;
+  let bind: SocketAddr = if addr.is_ipv4() {
+    ([0, 0, 0, 0], 0).into()
+  } else {
+    ([0u16; 8], 0).into()
+  };
+  let socket = UdpSocket::bind(bind).ok()?;
+  socket.connect(addr).ok()?;
+  socket.send(&query.encode()?).ok()?;
+  let deadline = Instant::now() + timeout;
+  let mut buffer = vec![0; MAX_UDP_SIZE];
+  // 忽略ID不一样和解析不了的报文，直到超时
+  loop {
+    let remaining = deadline.checked_duration_since(Instant::now())?;
+    socket.set_read_timeout(Some(remaining)).ok()?;
+    let size = socket.recv(&mut buffer).ok()?;
+    if let Some(message) = DnsMessage::parse(&buffer[..size])
+      && message.id == query.id
+    {
+      return Some(message);
+    }
+  }
+}
+
+// 1.2.3.4转换成4.3.2.1.in-addr.arpa.，IPv6按照半字节反转
+fn reverse_name(ip: IpAddr) -> String {
+  match ip {
+    IpAddr::V4(ip) => {
+      let o = ip.octets();
+      format!("{}.{}.{}.{}.in-addr.arpa.", o[3], o[2], o[1], o[0])
+    }
+    IpAddr::V6(ip) => {
+      let mut name = String::new();
+      for b in ip.octets().iter().rev() {
+        name.push_str(&format!("{:x}.{:x}.", b & 0x0f, b >> 4));
+      }
+      name.push_str("ip6.arpa.");
+      name
+    }
+  }
+}
+
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, Hash)]
+#[serde(rename_all = "UPPERCASE")]
+#[allow(clippy::upper_case_acronyms)]
+pub enum DnsType {
+  #[default]
+  A,
+  NS,
+  DS,
+  CNAME,
+  SOA,
+  PTR,
+  MX,
+  TXT,
+  AAAA,
+  CAA,
+  TLSA,
+  SRV,
+  ANY,
+}
+
+impl DnsType {
+  /// 记录类型的编号
+  pub fn code(&self) -> u16 {
+    match self {
+      DnsType::A => 1,
+      DnsType::NS => 2,
+      DnsType::CNAME => 5,
+      DnsType::SOA => 6,
+      DnsType::PTR => 12,
+      DnsType::MX => 15,
+      DnsType::TXT => 16,
+      DnsType::AAAA => 28,
+      DnsType::SRV => 33,
+      DnsType::DS => 43,
+      DnsType::TLSA => 52,
+      DnsType::ANY => 255,
+      DnsType::CAA => 257,
+    }
+  }
+}
+
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsClass {
+  #[default]
+  Inet,
+  Csnet,
+  Chaos,
+  Hesiod,
+  None,
+  Any,
+}
+
+impl DnsClass {
+  /// 类的编号
+  pub fn code(&self) -> u16 {
+    match self {
+      DnsClass::Inet => 1,
+      DnsClass::Csnet => 2,
+      DnsClass::Chaos => 3,
+      DnsClass::Hesiod => 4,
+      DnsClass::None => 254,
+      DnsClass::Any => 255,
+    }
+  }
+}
+
+impl DnsMessage {
+  /// 转换成可以匹配的响应，应答的每个部分放到响应头，响应体是dig格式的报文
+  ///
+  /// 同一个部分有多条记录时，响应头有多个值，匹配时使用逗号连接
+  pub fn into_response(self, request: Request, connection: ConnectionInfo) -> Response {
+    let mut builder = Response::builder().header("rcode", self.rcode());
+    for question in self.questions.iter() {
+      builder = builder.header("question", question.to_string());
+    }
+    for (name, section) in [
+      ("answer", &self.answers),
+      ("ns", &self.authorities),
+      ("extra", &self.additionals),
+    ] {
+      for record in section.iter() {
+        builder = builder.header(name, record.to_string());
+      }
+    }
+    let mut response: Response = builder
+      .body(self.to_string().into_bytes())
+      .unwrap_or_default()
+      .into();
+    response.extensions_mut().insert(connection);
+    response.extensions_mut().insert(request);
+    response
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::operators::OperatorResult;
+  use crate::request::Requests;
+
+  // 应答：www.example.com. CNAME example.herokudns.com.，A记录的名称使用压缩指针
+  fn heroku_answer(id: u16) -> Vec<u8> {
+    let mut data = vec![];
+    data.extend_from_slice(&id.to_be_bytes());
+    data.extend_from_slice(&[0x81, 0x80, 0, 1, 0, 2, 0, 0, 0, 0]);
+    // 问题在偏移12
+    data.extend_from_slice(b"\x03www\x07example\x03com\x00\x00\x01\x00\x01");
+    // CNAME
+    data.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 0x0e, 0x10, 0, 20]);
+    let cname = data.len();
+    data.extend_from_slice(b"\x07example\x09herokudns\xc0\x18");
+    // A
+    data.extend_from_slice(&[0xc0, cname as u8, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 1, 2, 3, 4]);
+    data
+  }
+
+  #[test]
+  fn dns_question() {
+    let requests: Requests = serde_json::from_value(serde_json::json!({
+      "dns": [{"type": "PTR", "resolvers": ["127.0.0.1", "[::1]", "ns.example.com:5353"]}]
+    }))
+    .unwrap();
+    let dns = requests.is_dns().unwrap();
+    let marker = Marker::with_target(&"https://192.168.1.10:8443/".parse().unwrap());
+    assert_eq!(dns.question(&marker).name, "10.1.168.192.in-addr.arpa.");
+    assert_eq!(
+      dns.resolvers(&marker),
+      vec!["127.0.0.1:53", "[::1]:53", "ns.example.com:5353"]
+    );
+    let version: DNSRequest = serde_json::from_value(serde_json::json!({
+      "name": "version.bind", "type": "TXT", "class": "chaos", "recursion": false
+    }))
+    .unwrap();
+    let query = version.query(&marker);
+    let data = query.encode().unwrap();
+    // 没有RD标志
+    assert_eq!(&data[2..4], &[0, 0]);
+    assert_eq!(&data[12..], b"\x07version\x04bind\x00\x00\x10\x00\x03");
+    assert_eq!(DnsMessage::parse(&data).unwrap(), query);
+    assert_eq!(
+      reverse_name("2001:db8::1".parse().unwrap()),
+      "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa."
+    );
+  }
+
+  #[test]
+  fn match_dns_answer() {
+    let requests: Requests = serde_json::from_value(serde_json::json!({
+      "dns": [{
+        "name": "www.{{FQDN}}",
+        "type": "CNAME",
+        "matchers-condition": "and",
+        "matchers": [
+          {"type": "word", "part": "answer", "words": ["IN\tCNAME\texample.herokudns.com."]},
+          {"type": "regex", "part": "answer", "regex": ["IN\tA\t1\\.2\\.3\\.4$"]},
+          {"type": "dsl", "dsl": ["rcode == 'NOERROR' && contains(answer, 'herokudns.com')"]}
+        ],
+        "extractors": [
+          {"type": "regex", "part": "answer", "group": 1, "regex": ["IN\tCNAME\t([^,\\s]+)"]}
+        ]
+      }]
+    }))
+    .unwrap();
+    let dns = requests.is_dns().unwrap();
+    let marker = Marker::with_target(&"http://example.com".parse().unwrap());
+    assert_eq!(dns.question(&marker).name, "www.example.com.");
+    let message = DnsMessage::parse(&heroku_answer(0x1234)).unwrap();
+    assert_eq!(message.rcode(), "NOERROR");
+    assert_eq!(
+      message.answers[0].to_string(),
+      "www.example.com.\t3600\tIN\tCNAME\texample.herokudns.com."
+    );
+    assert_eq!(
+      message.answers[1].to_string(),
+      "example.herokudns.com.\t60\tIN\tA\t1.2.3.4"
+    );
+    assert!(
+      message
+        .to_string()
+        .contains(";; flags: qr rd ra; QUERY: 1, ANSWER: 2")
+    );
+    let request = Request::raw(
+      "dns://127.0.0.1:53".parse::<slinger::http::Uri>().unwrap(),
+      dns.query(&marker).to_string(),
+      false,
+    );
+    let response = message.into_response(request, ConnectionInfo::default());
+    let mut operators = (*dns.operators).clone();
+    operators.compile().unwrap();
+    let mut result = OperatorResult::default();
+    operators.matcher(&response, &mut result).unwrap();
+    assert!(result.is_matched());
+    operators.extractor(None, &response, &mut result);
+    assert!(
+      result
+        .extract_result()
+        .values()
+        .any(|v| v.contains("example.herokudns.com."))
+    );
+  }
+
+  #[test]
+  fn parse_record_data() {
+    // 截断的报文
+    assert!(DnsMessage::parse(&heroku_answer(1)[..40]).is_none());
+    // 指向自己的压缩指针
+    let mut data = vec![0, 1, 0x81, 0x80, 0, 1, 0, 0, 0, 0, 0, 0];
+    data.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1]);
+    assert!(DnsMessage::parse(&data).is_none());
+    let mut data = vec![0, 1, 0x81, 0x83, 0, 0, 0, 2, 0, 0, 0, 0];
+    // TXT: "v=spf1" "a\"b"
+    data.extend_from_slice(&[0, 0, 16, 0, 3, 0, 0, 0, 0, 0, 11]);
+    data.extend_from_slice(b"\x06v=spf1\x03a\"b");
+    // CAA: 0 issue "letsencrypt.org"
+    data.extend_from_slice(&[0, 1, 1, 0, 1, 0, 0, 0, 0, 0, 22, 0, 5]);
+    data.extend_from_slice(b"issueletsencrypt.org");
+    let message = DnsMessage::parse(&data).unwrap();
+    assert_eq!(message.rcode(), "NXDOMAIN");
+    assert_eq!(message.answers[0].data, r#""v=spf1" "a\"b""#);
+    assert_eq!(
+      message.answers[0].to_string(),
+      ".\t0\tCH\tTXT\t\"v=spf1\" \"a\\\"b\""
+    );
+    assert_eq!(message.answers[1].data, r#"0 issue "letsencrypt.org""#);
+  }
+}
@@ -2,7 +2,7 @@ mod option;
 mod path;
 mod raw;
 
-use crate::common::{PayloadAttack, PayloadIterator};
+use crate::common::{Marker, Payload, PayloadAttack};
 use crate::operators::Operators;
 use crate::request::http::option::HttpOption;
 pub use crate::request::http::path::Http;
@@ -10,7 +10,7 @@ pub use crate::request::http::raw::Raw;
 use crate::serde_format::is_default;
 use serde::{Deserialize, Serialize};
 use slinger::Request;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::fmt::Debug;
 use std::sync::Arc;
 
@@ -80,9 +80,18 @@ pub struct HTTPRequest {
   pub operators: Arc<Operators>,
 }
 
+/// 按照路径和payload组合逐个生成请求，每个请求带上生成它的payload
+///
+/// 请求在迭代时才替换占位符，所以中途加入的变量对后面的请求生效
 #[derive(Debug)]
 pub struct RequestGenerator {
-  requests: VecDeque<Request>,
+  target: slinger::http::uri::Uri,
+  http_raw: HttpRaw,
+  marker: Marker,
+  // 还没有生成的请求：payload和路径下标
+  pending: VecDeque<(Payload, usize)>,
+  // 最后生成的请求的路径下标
+  index: usize,
 }
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -92,21 +101,126 @@ pub enum HttpRaw {
   Raw(Raw),
 }
 
+impl HttpRaw {
+  fn len(&self) -> usize {
+    match self {
+      HttpRaw::Path(paths) => paths.path.len(),
+      HttpRaw::Raw(raws) => raws.raw.len(),
+    }
+  }
+}
+
 impl RequestGenerator {
-  pub fn new(value: &HTTPRequest, uri: &slinger::http::uri::Uri) -> Self {
-    let _payload = value.payload_attack.as_ref().map(PayloadIterator::from);
-    let requests = match &value.http_raw {
-      HttpRaw::Path(paths) => paths.to_requests(uri),
-      HttpRaw::Raw(raws) => raws.to_requests(uri),
-    };
-    RequestGenerator { requests }
+  /// `variables` 是模板的变量，会在目标的内置变量之后解析
+  pub fn new(
+    value: &HTTPRequest,
+    uri: &slinger::http::uri::Uri,
+    variables: &BTreeMap<String, String>,
+  ) -> Self {
+    let mut marker = Marker::with_target(uri);
+    marker.extend_resolved(variables);
+    let mut pending = VecDeque::new();
+    for payload in Payload::generate(value.payload_attack.as_ref()) {
+      for index in 0..value.http_raw.len() {
+        pending.push_back((payload.clone(), index));
+      }
+    }
+    RequestGenerator {
+      target: uri.clone(),
+      http_raw: value.http_raw.clone(),
+      marker,
+      pending,
+      index: 0,
+    }
+  }
+  /// 当前的变量上下文，不包含payload
+  pub fn marker(&self) -> &Marker {
+    &self.marker
+  }
+  /// 最后生成的请求是第几个路径，从1开始
+  pub fn index(&self) -> usize {
+    self.index + 1
+  }
+  /// 当前payload的全部路径都已经生成
+  pub fn is_round_end(&self) -> bool {
+    self.pending.front().is_none_or(|(_, index)| *index == 0)
+  }
+  /// 加入新的变量，例如内部提取器提取到的值，之后生成的请求都可以使用
+  pub fn insert_variables(&mut self, variables: &BTreeMap<String, String>) {
+    for (key, value) in variables.iter() {
+      self.marker.insert(key.clone(), value.clone());
+    }
   }
 }
 
 impl Iterator for RequestGenerator {
-  type Item = Request;
+  type Item = (Request, Payload);
 
   fn next(&mut self) -> Option<Self::Item> {
-    self.requests.pop_front()
+    while let Some((payload, index)) = self.pending.pop_front() {
+      let marker = self.marker.with_payload(&payload);
+      let request = match &self.http_raw {
+        HttpRaw::Path(paths) => paths.to_request(&self.target, index, &marker),
+        HttpRaw::Raw(raws) => raws.to_request(&self.target, index, &marker),
+      };
+      if let Some(request) = request {
+        self.index = index;
+        return Some((request, payload));
+      }
+    }
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn generate_with_payload_and_variables() {
+    let http: HTTPRequest = serde_json::from_value(serde_json::json!({
+      "method": "POST",
+      "path": ["{{BaseURL}}/login", "{{RootURL}}/{{next}}"],
+      "headers": {"X-Token": "{{token}}"},
+      "body": "user={{username}}&host={{Hostname}}",
+      "attack": "batteringram",
+      "payloads": {"username": ["admin", "root"]}
+    }))
+    .unwrap();
+    let uri: slinger::http::uri::Uri = "http://example.com:8080/app/".parse().unwrap();
+    let variables = BTreeMap::from_iter([("token".to_string(), "{{to_upper(\"t\")}}".to_string())]);
+    let mut generator = RequestGenerator::new(&http, &uri, &variables);
+    let (request, payload) = generator.next().unwrap();
+    assert_eq!(
+      request.uri().to_string(),
+      "http://example.com:8080/app/login"
+    );
+    assert_eq!(request.headers().get("X-Token").unwrap(), "T");
+    assert_eq!(
+      request.body().unwrap().as_ref(),
+      b"user=admin&host=example.com:8080"
+    );
+    assert_eq!(payload.values().get("username").unwrap(), "admin");
+    assert_eq!(generator.index(), 1);
+    assert!(!generator.is_round_end());
+    // 中途加入的变量对后面的请求生效
+    generator.insert_variables(&BTreeMap::from_iter([(
+      "next".to_string(),
+      "admin/index.php".to_string(),
+    )]));
+    let (request, _) = generator.next().unwrap();
+    assert_eq!(
+      request.uri().to_string(),
+      "http://example.com:8080/admin/index.php"
+    );
+    assert_eq!(generator.index(), 2);
+    assert!(generator.is_round_end());
+    let (request, payload) = generator.next().unwrap();
+    assert_eq!(
+      request.body().unwrap().as_ref(),
+      b"user=root&host=example.com:8080"
+    );
+    assert_eq!(payload.values().get("username").unwrap(), "root");
+    assert_eq!(generator.count(), 1);
   }
 }
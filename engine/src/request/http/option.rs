@@ -39,6 +39,20 @@ pub struct HttpOption {
   )]
   pub race_count: Option<u8>,
   // description: |
+  //   ReqCondition automatically assigns numbers to requests and preserves their history.
+  //
+  //   This allows matching on request 1 or 2 by using body_1 or body_2 as well as
+  //   request name, eg: header_login. Matchers are evaluated after all requests complete.
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "preserve request history",
+      description = "Automatically assigns numbers to requests and preserves their history for correlated matching"
+    )
+  )]
+  pub req_condition: bool,
+  // description: |
   //   MaxRedirects is the maximum number of redirects that should be followed.
   // examples:
   //   - name: Follow up to 5 redirects
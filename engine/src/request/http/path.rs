@@ -1,3 +1,4 @@
+use crate::common::Marker;
 use crate::request::input_to_byte;
 use crate::serde_format::Value;
 use crate::serde_format::is_default;
@@ -5,7 +6,7 @@ use serde::{Deserialize, Serialize};
 use slinger::Request;
 use slinger::http::Method;
 use slinger::http_serde;
-use std::collections::{BTreeMap, VecDeque};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -75,10 +76,9 @@ pub struct Http {
 }
 
 fn join(cur_uri: &slinger::http::uri::Uri, val: String) -> Option<slinger::http::uri::Uri> {
-  // Remove the {{BaseURL}} prefix, then ensure we don't treat an absolute path
-  // (starting with '/') as replacing the base path. If the remainder starts
-  // with '/', strip that leading slash so join() appends it correctly.
-  let mut rel = val.trim_start_matches("{{BaseURL}}");
+  // Relative paths are appended to the current path. If the value starts with
+  // '/', strip that leading slash so join() does not replace the base path.
+  let mut rel = val.as_str();
   if rel.starts_with('/') && rel != "/" {
     rel = &rel[1..];
   }
@@ -96,25 +96,36 @@ fn join(cur_uri: &slinger::http::uri::Uri, val: String) -> Option<slinger::http:
 }
 
 impl Http {
-  pub(crate) fn to_requests(&self, target: &slinger::http::uri::Uri) -> VecDeque<Request> {
-    let mut requests = VecDeque::new();
-    for path in self.path.clone().into_iter() {
-      let target = join(target, path).unwrap_or(target.clone());
-      let mut builder = Request::builder()
-        .method(self.method.clone())
-        .uri(target.clone())
-        .header(
-          "Accept",
-          "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8",
-        );
-      for (key, value) in self.headers.clone().into_iter() {
-        builder = builder.header(key, &value);
-      }
-      let body = slinger::Body::from(input_to_byte(&self.body.clone().unwrap_or_default()));
-      if let Ok(request) = builder.body(body) {
-        requests.push_back(Request::from(request));
+  /// 生成第 `index` 个路径的请求
+  pub(crate) fn to_request(
+    &self,
+    target: &slinger::http::uri::Uri,
+    index: usize,
+    marker: &Marker,
+  ) -> Option<Request> {
+    let url = marker.replace(self.path.get(index)?);
+    // 解析后是完整的URL就直接使用，否则当作相对路径拼接
+    let target = url
+      .parse::<slinger::http::uri::Uri>()
+      .ok()
+      .filter(|u| u.scheme().is_some() && u.authority().is_some())
+      .or_else(|| join(target, url))
+      .unwrap_or(target.clone());
+    let mut builder = Request::builder()
+      .method(self.method.clone())
+      .uri(target.clone())
+      .header(
+        "Accept",
+        "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8",
+      );
+    for (key, value) in self.headers.iter() {
+      builder = match value {
+        Value::String(s) => builder.header(key, marker.replace(s)),
+        _ => builder.header(key, value),
       };
     }
-    requests
+    let body = marker.replace(self.body.as_deref().unwrap_or_default());
+    let body = slinger::Body::from(input_to_byte(&body));
+    builder.body(body).ok().map(Request::from)
   }
 }
@@ -1,7 +1,7 @@
+use crate::common::Marker;
 use crate::serde_format::is_default;
 use serde::{Deserialize, Serialize};
 use slinger::Request;
-use std::collections::VecDeque;
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "kebab-case")]
@@ -36,12 +36,14 @@ pub struct Raw {
   pub r#unsafe: bool,
 }
 impl Raw {
-  pub(crate) fn to_requests(&self, target: &slinger::http::uri::Uri) -> VecDeque<Request> {
-    let mut requests = VecDeque::new();
-    for raw in self.raw.clone().into_iter() {
-      let r = Request::raw(target.clone(), raw, true);
-      requests.push_back(r);
-    }
-    requests
+  /// 生成第 `index` 个原始请求
+  pub(crate) fn to_request(
+    &self,
+    target: &slinger::http::uri::Uri,
+    index: usize,
+    marker: &Marker,
+  ) -> Option<Request> {
+    let raw = marker.replace(self.raw.get(index)?);
+    Some(Request::raw(target.clone(), raw, true))
   }
 }
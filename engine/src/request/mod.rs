@@ -5,11 +5,18 @@ mod headless;
 mod http;
 #[cfg(feature = "mitm")]
 pub mod mitm;
+mod ssl;
 mod tcp;
+mod udp;
+mod websocket;
 
 use crate::operators::Operators;
 #[cfg(feature = "code")]
 pub use crate::request::code::CodeRequest;
+pub use crate::request::dns::{
+  DNSRequest, DnsClass, DnsMessage, DnsQuestion, DnsRecord, DnsType, lookup, resolver_address,
+  system_resolvers,
+};
 use crate::request::headless::HeadlessRequest;
 pub use crate::request::http::{HTTPRequest, Http, HttpRaw, Raw, RequestGenerator};
 #[cfg(feature = "mitm")]
@@ -18,7 +25,12 @@ pub use crate::request::mitm::{
   MitmReplacement, MitmReplacementTarget, MitmReplacementType, MitmRequest, MitmRequestContext,
   MitmResponseContext, MitmRuleMatcher, MitmTarget, MitmWordMatch,
 };
+pub use crate::request::ssl::{SSLRequest, TlsHandshake, TlsVersion};
 pub use crate::request::tcp::{Input, PortRange, TCPRequest};
+pub use crate::request::udp::UDPRequest;
+pub use crate::request::websocket::{
+  Opcode, WebSocketFrame, WebSocketHandshake, WebSocketRequest, websocket_accept, websocket_key,
+};
 use crate::serde_format::is_default;
 use rustc_lexer::unescape;
 use serde::{Deserialize, Serialize};
@@ -44,11 +56,17 @@ pub struct Requests {
     )
   )]
   pub http: Vec<Arc<HTTPRequest>>,
-  // description: |
-  //   DNS contains the dns request to make in the template
-  // examples:
-  //   - value: exampleNormalDNSRequest
-  // DNS(),
+  /// description: |
+  ///   DNS contains the dns request to make in the template
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "dns requests to make",
+      description = "DNS requests to make for the template"
+    )
+  )]
+  pub dns: Vec<Arc<DNSRequest>>,
   // description: |
   //   File contains the file request to make in the template
   // examples:
@@ -67,6 +85,17 @@ pub struct Requests {
     )
   )]
   pub tcp: Vec<Arc<TCPRequest>>,
+  /// description: |
+  ///   UDP contains the udp probes to send in the template
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "udp probes to send",
+      description = "UDP probes to send for the template"
+    )
+  )]
+  pub udp: Vec<Arc<UDPRequest>>,
   ///description: |
   ///   Headless contains the headless request to make in the template.
   #[serde(default, skip_serializing_if = "is_default")]
@@ -78,13 +107,29 @@ pub struct Requests {
     )
   )]
   pub headless: Vec<Arc<HeadlessRequest>>,
-  #[cfg(feature = "code")]
-  ///description: |
+  /// description: |
   ///   SSL contains the SSL request to make in the template.
-  ///SSL(),
-  ///description: |
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "ssl requests to make",
+      description = "SSL requests to make for the template"
+    )
+  )]
+  pub ssl: Vec<Arc<SSLRequest>>,
+  /// description: |
   ///   Websocket contains the Websocket request to make in the template.
-  ///WEBSOCKET(),
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "websocket requests to make",
+      description = "Websocket requests to make for the template"
+    )
+  )]
+  pub websocket: Vec<Arc<WebSocketRequest>>,
+  #[cfg(feature = "code")]
   ///description: |
   ///   WHOIS contains the WHOIS request to make in the template.
   ///WHOIS(),
@@ -147,6 +192,30 @@ impl Requests {
         return sn == on;
       }
     }
+    // 握手参数一样的ssl请求只需要握手一次
+    if self.ssl.len() == 1 && other.ssl.len() == 1 {
+      return self.ssl[0].same_handshake(&other.ssl[0]);
+    }
+    if self.udp.len() == 1 && other.udp.len() == 1 {
+      let self_udp = &self.udp[0];
+      let other_udp = &other.udp[0];
+      // 存在请求探针名称并且相同，直接合并优化
+      if let (Some(sn), Some(on)) = (&self_udp.name, &other_udp.name) {
+        return sn == on;
+      }
+    }
+    if self.websocket.len() == 1 && other.websocket.len() == 1 {
+      let self_websocket = &self.websocket[0];
+      let other_websocket = &other.websocket[0];
+      // 存在请求探针名称并且相同，直接合并优化
+      if let (Some(sn), Some(on)) = (&self_websocket.name, &other_websocket.name) {
+        return sn == on;
+      }
+    }
+    // 查询一样的dns请求只需要查询一次
+    if self.dns.len() == 1 && other.dns.len() == 1 {
+      return self.dns[0].same_query(&other.dns[0]);
+    }
     false
   }
   pub fn is_web_default(&self) -> bool {
@@ -165,6 +234,18 @@ impl Requests {
   pub fn is_tcp(&self) -> Option<&Arc<TCPRequest>> {
     self.tcp.first()
   }
+  pub fn is_udp(&self) -> Option<&Arc<UDPRequest>> {
+    self.udp.first()
+  }
+  pub fn is_ssl(&self) -> Option<&Arc<SSLRequest>> {
+    self.ssl.first()
+  }
+  pub fn is_dns(&self) -> Option<&Arc<DNSRequest>> {
+    self.dns.first()
+  }
+  pub fn is_websocket(&self) -> Option<&Arc<WebSocketRequest>> {
+    self.websocket.first()
+  }
   #[cfg(feature = "code")]
   pub fn is_code(&self) -> Option<&CodeRequest> {
     self.code.first()
@@ -191,6 +272,34 @@ impl Requests {
         .map(|t| t.operators.clone())
         .collect::<Vec<_>>(),
     );
+    all.extend(
+      self
+        .udp
+        .iter()
+        .map(|u| u.operators.clone())
+        .collect::<Vec<_>>(),
+    );
+    all.extend(
+      self
+        .ssl
+        .iter()
+        .map(|s| s.operators.clone())
+        .collect::<Vec<_>>(),
+    );
+    all.extend(
+      self
+        .dns
+        .iter()
+        .map(|d| d.operators.clone())
+        .collect::<Vec<_>>(),
+    );
+    all.extend(
+      self
+        .websocket
+        .iter()
+        .map(|w| w.operators.clone())
+        .collect::<Vec<_>>(),
+    );
     #[cfg(feature = "code")]
     {
       all.extend(
@@ -233,8 +342,12 @@ impl Requests {
         http_option: Default::default(),
         operators: Default::default(),
       })],
+      dns: vec![],
       tcp: vec![],
+      udp: vec![],
       headless: vec![],
+      ssl: vec![],
+      websocket: vec![],
       #[cfg(feature = "code")]
       code: vec![],
       #[cfg(feature = "mitm")]
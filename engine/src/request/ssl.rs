@@ -0,0 +1,275 @@
+use crate::common::cert::X509Certificate;
+use crate::common::{ConnectionInfo, Marker};
+use crate::operators::Operators;
+use crate::serde_format::is_default;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use slinger::tls::PeerCertificate;
+use slinger::{Request, Response};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+const DEFAULT_ADDRESS: &str = "{{Host}}:{{Port}}";
+
+/// SSL请求只做TLS握手，匹配证书和协商的参数
+///
+/// 握手结果的每个字段都是一个匹配的part，例如：`cert.subject_cn`、`tls.version`，
+/// 响应体是全部字段的JSON
+///
+/// rustls握手失败时会回退到native-tls，`tls.backend` 是 `native-tls`，
+/// 这时拿不到协商的版本和密码套件，`tls.version`、`tls.cipher` 和 `tls_version` 为空
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct SSLRequest {
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(title = "id of the request", description = "ID of the ssl request")
+  )]
+  pub id: Option<String>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  pub name: Option<String>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "address for the ssl request",
+      description = "Address contains address for the request, default is `{{Host}}:{{Port}}`",
+      example = r#""{{Host}}:{{Port}}""#
+    )
+  )]
+  pub address: Option<String>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "server name indication",
+      description = "Server name sent in the client hello, default is the target host when it is a domain"
+    )
+  )]
+  pub server_name: Option<String>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "minimum tls version",
+      description = "Minimum tls version that is acceptable",
+      example = r#""tls12""#
+    )
+  )]
+  pub min_version: Option<TlsVersion>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "maximum tls version",
+      description = "Maximum tls version that is acceptable",
+      example = r#""tls13""#
+    )
+  )]
+  pub max_version: Option<TlsVersion>,
+  // Operators for the current request go here.
+  #[serde(flatten)]
+  pub operators: Arc<Operators>,
+}
+
+impl SSLRequest {
+  /// 替换占位符后的握手地址
+  pub fn address(&self, marker: &Marker) -> String {
+    marker.replace(self.address.as_deref().unwrap_or(DEFAULT_ADDRESS))
+  }
+  // 握手参数一样的请求可以合并
+  pub(crate) fn same_handshake(&self, other: &SSLRequest) -> bool {
+    self.address.as_deref().unwrap_or(DEFAULT_ADDRESS)
+      == other.address.as_deref().unwrap_or(DEFAULT_ADDRESS)
+      && self.server_name == other.server_name
+      && self.min_version == other.min_version
+      && self.max_version == other.max_version
+  }
+}
+
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsVersion {
+  #[serde(alias = "ssl30")]
+  Sslv3,
+  Tls10,
+  Tls11,
+  Tls12,
+  Tls13,
+}
+
+impl Display for TlsVersion {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let s = match self {
+      TlsVersion::Sslv3 => "sslv3",
+      TlsVersion::Tls10 => "tls10",
+      TlsVersion::Tls11 => "tls11",
+      TlsVersion::Tls12 => "tls12",
+      TlsVersion::Tls13 => "tls13",
+    };
+    f.write_str(s)
+  }
+}
+
+/// 一次TLS握手的结果
+#[derive(Debug, Clone, Default)]
+pub struct TlsHandshake {
+  // 协商的版本
+  pub version: Option<TlsVersion>,
+  // 协商的密码套件，例如：TLS13_AES_128_GCM_SHA256
+  pub cipher: Option<String>,
+  // 协商的应用层协议，例如：h2
+  pub alpn: Option<String>,
+  // 发送的SNI
+  pub server_name: Option<String>,
+  // 对端的证书链，第一个是服务器证书，DER编码
+  pub certificates: Vec<Vec<u8>>,
+  // 握手使用的TLS实现，例如：rustls、native-tls
+  pub backend: Option<String>,
+}
+
+impl TlsHandshake {
+  /// 握手的全部字段，键就是匹配时使用的part名称
+  pub fn fields(&self) -> BTreeMap<String, String> {
+    let mut fields = BTreeMap::new();
+    let mut insert = |key: &str, value: Option<String>| {
+      if let Some(value) = value.filter(|v| !v.is_empty()) {
+        fields.insert(key.to_string(), value);
+      }
+    };
+    insert("tls.version", self.version.map(|v| v.to_string()));
+    insert("tls.cipher", self.cipher.clone());
+    insert("tls.alpn", self.alpn.clone());
+    insert("tls.sni", self.server_name.clone());
+    insert("tls.backend", self.backend.clone());
+    insert(
+      "cert.chain_length",
+      Some(self.certificates.len().to_string()),
+    );
+    let Some(der) = self.certificates.first() else {
+      return fields;
+    };
+    insert(
+      "cert.fingerprint_sha256",
+      Some(hex::encode(Sha256::digest(der))),
+    );
+    let Some(cert) = X509Certificate::from_der(der) else {
+      return fields;
+    };
+    let subject = cert.subject_name();
+    let issuer = cert.issuer_name();
+    insert("cert.subject_cn", subject.get("common_name").cloned());
+    insert(
+      "cert.subject_org",
+      subject.get("organization_name").cloned(),
+    );
+    insert("cert.issuer_cn", issuer.get("common_name").cloned());
+    insert("cert.issuer_org", issuer.get("organization_name").cloned());
+    insert("cert.subject_an", Some(cert.dns_names().join(", ")));
+    insert("cert.serial", cert.serial_number().cloned());
+    insert("cert.not_before", Some(cert.not_before().to_string()));
+    insert("cert.not_after", Some(cert.not_after().to_string()));
+    insert(
+      "cert.signature_algorithm",
+      Some(cert.signature_algorithm().to_string()),
+    );
+    insert("cert.self_signed", Some((subject == issuer).to_string()));
+    fields
+  }
+  /// 转换成可以匹配的响应，字段放到响应头，响应体是字段的JSON
+  ///
+  /// 证书链和连接信息也会放到响应的扩展中，DSL可以使用 `cert_*` 和 `tls_version` 变量
+  pub fn into_response(self, request: Request, mut connection: ConnectionInfo) -> Response {
+    let fields = self.fields();
+    let mut builder = Response::builder();
+    for (name, value) in fields.iter() {
+      builder = builder.header(name.as_str(), value.as_str());
+    }
+    let mut response: Response = builder
+      .body(serde_json::to_vec(&fields).unwrap_or_default())
+      .unwrap_or_default()
+      .into();
+    connection.tls_version = self.version.map(|v| v.to_string());
+    let certificates: Vec<PeerCertificate> = self
+      .certificates
+      .into_iter()
+      .map(|inner| PeerCertificate { inner })
+      .collect();
+    if !certificates.is_empty() {
+      response.extensions_mut().insert(certificates);
+    }
+    response.extensions_mut().insert(connection);
+    response.extensions_mut().insert(request);
+    response
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::operators::OperatorResult;
+  use crate::request::Requests;
+
+  #[test]
+  fn match_handshake_parts() {
+    let requests: Requests = serde_json::from_value(serde_json::json!({
+      "ssl": [{
+        "address": "{{Host}}:{{Port}}",
+        "min-version": "tls10",
+        "matchers-condition": "and",
+        "matchers": [
+          {"type": "word", "part": "cert.subject_cn", "words": ["FortiGate"]},
+          {"type": "word", "part": "tls.version", "words": ["tls13"]},
+          {"type": "dsl", "dsl": ["cert_issuer_cn == 'FortiGate' && tls_version == 'tls13'"]}
+        ]
+      }]
+    }))
+    .unwrap();
+    let ssl = requests.is_ssl().unwrap();
+    assert_eq!(ssl.min_version, Some(TlsVersion::Tls10));
+    let target = "tls://192.168.1.1:10443".parse().unwrap();
+    assert_eq!(
+      ssl.address(&Marker::with_target(&target)),
+      "192.168.1.1:10443"
+    );
+    let mut params = rcgen::CertificateParams::new(vec!["fortinet.example".to_string()]).unwrap();
+    params
+      .distinguished_name
+      .push(rcgen::DnType::CommonName, "FortiGate");
+    let key = rcgen::KeyPair::generate().unwrap();
+    let cert = params.self_signed(&key).unwrap();
+    let handshake = TlsHandshake {
+      version: Some(TlsVersion::Tls13),
+      cipher: Some("TLS13_AES_128_GCM_SHA256".to_string()),
+      alpn: None,
+      server_name: None,
+      certificates: vec![cert.der().to_vec()],
+      backend: Some("rustls".to_string()),
+    };
+    let fields = handshake.fields();
+    assert_eq!(
+      fields.get("cert.subject_an").map(String::as_str),
+      Some("fortinet.example")
+    );
+    assert_eq!(
+      fields.get("cert.self_signed").map(String::as_str),
+      Some("true")
+    );
+    assert_eq!(
+      fields.get("tls.backend").map(String::as_str),
+      Some("rustls")
+    );
+    let request = Request::raw(target.clone(), vec![], false);
+    let response = handshake.into_response(request, ConnectionInfo::default());
+    assert!(response.certificate().is_some());
+    let mut operators = (*ssl.operators).clone();
+    operators.compile().unwrap();
+    let mut result = OperatorResult::default();
+    operators.matcher(&response, &mut result).unwrap();
+    assert!(result.is_matched());
+  }
+}
@@ -1,12 +1,13 @@
 mod port;
 
-use crate::common::PayloadAttack;
+use crate::common::{Marker, PayloadAttack};
 use crate::operators::Operators;
 use crate::request::input_to_byte;
 use crate::serde_format::is_default;
 pub use port::PortRange;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::time::Duration;
 
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -48,6 +49,15 @@ pub struct TCPRequest {
     )
   )]
   pub port: Option<PortRange>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "ports of the tls services",
+      description = "Ports where the service is usually wrapped in TLS, same as sslports of nmap-service-probes",
+    )
+  )]
+  pub ssl_port: Option<PortRange>,
   // Operators for the current request go here.
   #[serde(flatten)]
   pub operators: Arc<Operators>,
@@ -93,6 +103,32 @@ pub struct TCPRequest {
     )
   )]
   pub read_all: bool,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "time to wait for response",
+      description = "Milliseconds to wait for the response of each input, same as totalwaitms of nmap-service-probes",
+      example = 5000
+    )
+  )]
+  pub wait: Option<u64>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "fallback probes",
+      description = "Names of the probes whose matchers are also used for the response of this probe, same as fallback of nmap-service-probes"
+    )
+  )]
+  pub fallback: Vec<String>,
+}
+
+impl TCPRequest {
+  /// 每个输入等待响应的总时间，没有设置的时候只受连接的读超时限制
+  pub fn wait(&self) -> Option<Duration> {
+    self.wait.map(Duration::from_millis)
+  }
 }
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -105,7 +141,8 @@ pub struct Input {
 }
 
 impl Input {
-  pub fn data(&self) -> Vec<u8> {
-    input_to_byte(self.data.clone().unwrap_or_default().as_str())
+  /// 替换占位符后的输入数据
+  pub fn data(&self, marker: &Marker) -> Vec<u8> {
+    input_to_byte(&marker.replace(self.data.as_deref().unwrap_or_default()))
   }
 }
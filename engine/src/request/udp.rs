@@ -0,0 +1,108 @@
+use crate::common::PayloadAttack;
+use crate::operators::Operators;
+use crate::request::{Input, PortRange};
+use crate::serde_format::is_default;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+const DEFAULT_WAIT: u64 = 2000;
+const DEFAULT_RETRANSMITS: u8 = 1;
+
+/// UDP探针，按顺序发送输入的数据报，没有响应的时候重发
+///
+/// 和TCP的探针一样，响应体是收到的数据报
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct UDPRequest {
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(title = "id of the request", description = "ID of the udp request")
+  )]
+  pub id: Option<String>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  pub name: Option<String>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "inputs for the udp request",
+      description = "Inputs are sent as datagrams in order, `read` is the maximum size of the response datagram"
+    )
+  )]
+  pub inputs: Vec<Input>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "port to send probes to",
+      description = "Ports of the services, the probe is only sent to the matched ports if it is set",
+      example = r#""53,161-162""#
+    )
+  )]
+  pub port: Option<PortRange>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "time to wait for response",
+      description = "Milliseconds to wait for the response of each datagram, default is 2000",
+      example = 2000
+    )
+  )]
+  pub wait: Option<u64>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "retransmits for the datagram",
+      description = "Times to resend the datagram when there is no response, default is 1",
+      example = 1
+    )
+  )]
+  pub retransmits: Option<u8>,
+  // Operators for the current request go here.
+  #[serde(flatten)]
+  pub operators: Arc<Operators>,
+  #[serde(flatten, skip_serializing_if = "is_default")]
+  pub payload_attack: Option<PayloadAttack>,
+}
+
+impl UDPRequest {
+  /// 每个数据报等待响应的时间
+  pub fn wait(&self) -> Duration {
+    Duration::from_millis(self.wait.unwrap_or(DEFAULT_WAIT))
+  }
+  pub fn retransmits(&self) -> u8 {
+    self.retransmits.unwrap_or(DEFAULT_RETRANSMITS)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::request::Requests;
+  use std::time::Duration;
+
+  #[test]
+  fn udp_probe() {
+    let requests: Requests = serde_json::from_value(serde_json::json!({
+      "udp": [{
+        "name": "snmp",
+        "port": "U:161-162",
+        "wait": 500,
+        "inputs": [{"data": "0\\x26\\x02\\x01\\x01\\x04\\x06public", "read": 1024}],
+        "matchers": [{"type": "word", "words": ["public"]}]
+      }]
+    }))
+    .unwrap();
+    let udp = requests.is_udp().unwrap();
+    assert!(udp.port.as_ref().is_some_and(|p| p.contains(161)));
+    assert_eq!(udp.wait(), Duration::from_millis(500));
+    assert_eq!(udp.retransmits(), 1);
+    let data = udp.inputs[0].data(&Default::default());
+    assert_eq!(&data[..4], b"0\x26\x02\x01");
+    assert!(data.ends_with(b"public"));
+  }
+}
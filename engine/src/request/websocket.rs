@@ -0,0 +1,409 @@
+use crate::common::{ConnectionInfo, Marker, PayloadAttack};
+use crate::operators::Operators;
+use crate::request::Input;
+use crate::serde_format::is_default;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use slinger::http::Uri;
+use slinger::{Request, Response};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+const DEFAULT_ADDRESS: &str = "{{BaseURL}}";
+const DEFAULT_READ_FRAMES: usize = 1;
+// RFC6455规定的GUID，用于计算Sec-WebSocket-Accept
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// WebSocket请求，握手之后按顺序发送输入的文本帧，再读取服务端的帧
+///
+/// 握手响应的状态码和响应头就是响应的状态码和响应头，响应体是读取到的全部帧，每帧一行
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct WebSocketRequest {
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "id of the request",
+      description = "ID of the websocket request"
+    )
+  )]
+  pub id: Option<String>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  pub name: Option<String>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "address for the websocket request",
+      description = "Address contains address for the request, `http` and `ws` are plain connections, `https` and `wss` are tls connections, default is `{{BaseURL}}`",
+      example = r#""{{BaseURL}}/api/live/ws""#
+    )
+  )]
+  pub address: Option<String>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "headers for the handshake request",
+      description = "Headers contains headers for the websocket handshake request",
+      example = r#"&[("Origin", "{{RootURL}}")]"#
+    )
+  )]
+  pub headers: BTreeMap<String, String>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "subprotocols for the handshake request",
+      description = "Subprotocols sent in the Sec-WebSocket-Protocol header",
+      example = r#"&["graphql-ws"]"#
+    )
+  )]
+  pub subprotocols: Vec<String>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "inputs for the websocket request",
+      description = "Inputs are sent as text frames in order after the handshake"
+    )
+  )]
+  pub inputs: Vec<Input>,
+  #[serde(default, skip_serializing_if = "is_default")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "number of frames to read",
+      description = "Number of data frames to read after the inputs are sent, default is 1",
+      example = 2
+    )
+  )]
+  pub read_frames: Option<usize>,
+  // Operators for the current request go here.
+  #[serde(flatten)]
+  pub operators: Arc<Operators>,
+  #[serde(flatten, skip_serializing_if = "is_default")]
+  pub payload_attack: Option<PayloadAttack>,
+}
+
+impl WebSocketRequest {
+  /// 替换占位符后的地址，ws转换成http，wss转换成https，连接时可以直接使用
+  pub fn address(&self, marker: &Marker) -> Option<Uri> {
+    let address = marker.replace(self.address.as_deref().unwrap_or(DEFAULT_ADDRESS));
+    let address = if let Some(rest) = address.strip_prefix("wss://") {
+      format!("https://{rest}")
+    } else if let Some(rest) = address.strip_prefix("ws://") {
+      format!("http://{rest}")
+    } else {
+      address
+    };
+    address.parse().ok()
+  }
+  /// 握手请求，`key`是Sec-WebSocket-Key
+  pub fn handshake(&self, uri: &Uri, marker: &Marker, key: &str) -> Vec<u8> {
+    let path = uri.path_and_query().map_or("/", |p| p.as_str());
+    let host = uri.authority().map_or("", |a| a.as_str());
+    let mut request = format!(
+      "GET {path} HTTP/1.1\r\nHost: {host}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {key}\r\nSec-WebSocket-Version: 13\r\n"
+    );
+    if !self.subprotocols.is_empty() {
+      request.push_str(&format!(
+        "Sec-WebSocket-Protocol: {}\r\n",
+        self.subprotocols.join(", ")
+      ));
+    }
+    for (name, value) in self.headers.iter() {
+      request.push_str(&format!("{}: {}\r\n", name, marker.replace(value)));
+    }
+    request.push_str("\r\n");
+    request.into_bytes()
+  }
+  pub fn read_frames(&self) -> usize {
+    self.read_frames.unwrap_or(DEFAULT_READ_FRAMES)
+  }
+}
+
+/// 随机的Sec-WebSocket-Key
+pub fn websocket_key() -> String {
+  base64::engine::general_purpose::STANDARD.encode(rand::random::<[u8; 16]>())
+}
+
+/// 服务端应该返回的Sec-WebSocket-Accept
+pub fn websocket_accept(key: &str) -> String {
+  let hash = Sha1::digest(format!("{key}{WEBSOCKET_GUID}"));
+  base64::engine::general_purpose::STANDARD.encode(hash)
+}
+
+/// 帧的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+  Continuation,
+  Text,
+  Binary,
+  Close,
+  Ping,
+  Pong,
+  Other(u8),
+}
+
+impl From<u8> for Opcode {
+  fn from(value: u8) -> Self {
+    match value {
+      0x0 => Opcode::Continuation,
+      0x1 => Opcode::Text,
+      0x2 => Opcode::Binary,
+      0x8 => Opcode::Close,
+      0x9 => Opcode::Ping,
+      0xa => Opcode::Pong,
+      other => Opcode::Other(other),
+    }
+  }
+}
+
+impl From<Opcode> for u8 {
+  fn from(value: Opcode) -> Self {
+    match value {
+      Opcode::Continuation => 0x0,
+      Opcode::Text => 0x1,
+      Opcode::Binary => 0x2,
+      Opcode::Close => 0x8,
+      Opcode::Ping => 0x9,
+      Opcode::Pong => 0xa,
+      Opcode::Other(other) => other,
+    }
+  }
+}
+
+/// 一个WebSocket帧
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebSocketFrame {
+  pub fin: bool,
+  pub opcode: Opcode,
+  pub payload: Vec<u8>,
+}
+
+impl WebSocketFrame {
+  pub fn new(opcode: Opcode, payload: Vec<u8>) -> Self {
+    Self {
+      fin: true,
+      opcode,
+      payload,
+    }
+  }
+  /// 编码成客户端的帧，客户端发送的帧必须使用随机的掩码
+  pub fn encode(&self) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(self.payload.len() + 14);
+    let opcode: u8 = self.opcode.into();
+    buf.push(if self.fin { 0x80 } else { 0 } | (opcode & 0x0f));
+    let len = self.payload.len();
+    if len < 126 {
+      buf.push(0x80 | len as u8);
+    } else if len <= u16::MAX as usize {
+      buf.push(0x80 | 126);
+      buf.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+      buf.push(0x80 | 127);
+      buf.extend_from_slice(&(len as u64).to_be_bytes());
+    }
+    let mask: [u8; 4] = rand::random();
+    buf.extend_from_slice(&mask);
+    buf.extend(
+      self
+        .payload
+        .iter()
+        .enumerate()
+        .map(|(i, b)| b ^ mask[i % 4]),
+    );
+    buf
+  }
+  /// 从缓冲区解析一个帧，返回帧和使用的字节数，数据不完整时返回None
+  pub fn decode(data: &[u8]) -> Option<(Self, usize)> {
+    let first = *data.first()?;
+    let second = *data.get(1)?;
+    let mut offset = 2;
+    let len = match second & 0x7f {
+      126 => {
+        let len = u16::from_be_bytes(data.get(2..4)?.try_into().ok()?) as usize;
+        offset += 2;
+        len
+      }
+      127 => {
+        let len = u64::from_be_bytes(data.get(2..10)?.try_into().ok()?);
+        offset += 8;
+        usize::try_from(len).ok()?
+      }
+      len => len as usize,
+    };
+    let mask = if second & 0x80 != 0 {
+      let mask: [u8; 4] = data.get(offset..offset + 4)?.try_into().ok()?;
+      offset += 4;
+      Some(mask)
+    } else {
+      None
+    };
+    let payload = data.get(offset..offset.checked_add(len)?)?;
+    let payload = match mask {
+      Some(mask) => payload
+        .iter()
+        .enumerate()
+        .map(|(i, b)| b ^ mask[i % 4])
+        .collect(),
+      None => payload.to_vec(),
+    };
+    let frame = Self {
+      fin: first & 0x80 != 0,
+      opcode: Opcode::from(first & 0x0f),
+      payload,
+    };
+    Some((frame, offset + len))
+  }
+}
+
+/// WebSocket握手响应的状态码和响应头
+#[derive(Debug, Clone, Default)]
+pub struct WebSocketHandshake {
+  pub status: u16,
+  pub headers: Vec<(String, String)>,
+}
+
+impl WebSocketHandshake {
+  /// 解析握手响应的头部，返回握手响应和头部之后的字节，头部不完整时返回None
+  pub fn parse(data: &[u8]) -> Option<(Self, &[u8])> {
+    let end = data.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let head = String::from_utf8_lossy(&data[..end]);
+    let mut lines = head.split("\r\n");
+    let status = lines
+      .next()?
+      .split_whitespace()
+      .nth(1)
+      .and_then(|s| s.parse().ok())?;
+    let headers = lines
+      .filter_map(|line| line.split_once(':'))
+      .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+      .collect();
+    Some((Self { status, headers }, &data[end + 4..]))
+  }
+  /// 服务端同意升级协议，并且Sec-WebSocket-Accept和`key`对应
+  pub fn is_upgraded(&self, key: &str) -> bool {
+    self.status == 101
+      && self.headers.iter().any(|(name, value)| {
+        name.eq_ignore_ascii_case("sec-websocket-accept") && *value == websocket_accept(key)
+      })
+  }
+  /// 转换成可以匹配的响应，每个数据帧一行，二进制帧转义成可以打印的字符
+  pub fn into_response(
+    self,
+    request: Request,
+    frames: &[WebSocketFrame],
+    connection: ConnectionInfo,
+  ) -> Response {
+    let mut builder = Response::builder().status(self.status);
+    for (name, value) in self.headers.iter() {
+      builder = builder.header(name.as_str(), value.as_str());
+    }
+    let body: Vec<String> = frames
+      .iter()
+      .map(|frame| match frame.opcode {
+        Opcode::Text => String::from_utf8_lossy(&frame.payload).to_string(),
+        _ => frame.payload.escape_ascii().to_string(),
+      })
+      .collect();
+    let mut response: Response = builder
+      .body(body.join("\n").into_bytes())
+      .unwrap_or_default()
+      .into();
+    response.extensions_mut().insert(connection);
+    response.extensions_mut().insert(request);
+    response
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::operators::OperatorResult;
+  use crate::request::Requests;
+
+  #[test]
+  fn websocket_frames() {
+    // RFC6455 1.3的例子
+    assert_eq!(
+      websocket_accept("dGhlIHNhbXBsZSBub25jZQ=="),
+      "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    );
+    // RFC6455 5.7的例子：没有掩码和有掩码的Hello
+    let (frame, size) = WebSocketFrame::decode(b"\x81\x05\x48\x65\x6c\x6c\x6f").unwrap();
+    assert_eq!(size, 7);
+    assert_eq!(frame, WebSocketFrame::new(Opcode::Text, b"Hello".to_vec()));
+    let masked = b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58";
+    assert_eq!(WebSocketFrame::decode(masked).unwrap().0, frame);
+    // 不完整的帧
+    assert!(WebSocketFrame::decode(&masked[..8]).is_none());
+    for len in [0, 125, 126, 65535, 65536] {
+      let frame = WebSocketFrame::new(Opcode::Binary, vec![7; len]);
+      let data = frame.encode();
+      assert_eq!(WebSocketFrame::decode(&data), Some((frame, data.len())));
+    }
+  }
+
+  #[test]
+  fn match_websocket_handshake_and_frames() {
+    let requests: Requests = serde_json::from_value(serde_json::json!({
+      "websocket": [{
+        "address": "{{BaseURL}}/api/websocket",
+        "headers": {"Origin": "{{RootURL}}"},
+        "subprotocols": ["graphql-ws"],
+        "inputs": [{"data": "{\"type\":\"ping\"}"}],
+        "read-frames": 2,
+        "matchers-condition": "and",
+        "matchers": [
+          {"type": "status", "status": [101]},
+          {"type": "word", "part": "sec-websocket-protocol", "words": ["graphql-ws"]},
+          {"type": "regex", "regex": ["\"ha_version\":\"[0-9.]+\"}\n\\\\x00\\\\x01$"]}
+        ]
+      }]
+    }))
+    .unwrap();
+    let websocket = requests.is_websocket().unwrap();
+    assert_eq!(websocket.read_frames(), 2);
+    let target: Uri = "https://ha.example.com:8123".parse().unwrap();
+    let marker = Marker::with_target(&target);
+    let uri = websocket.address(&marker).unwrap();
+    assert_eq!(uri.to_string(), "https://ha.example.com:8123/api/websocket");
+    let handshake = String::from_utf8(websocket.handshake(&uri, &marker, "a2V5")).unwrap();
+    assert!(handshake.starts_with("GET /api/websocket HTTP/1.1\r\nHost: ha.example.com:8123\r\n"));
+    assert!(handshake.contains("Origin: https://ha.example.com:8123\r\n"));
+    assert!(handshake.contains("Sec-WebSocket-Protocol: graphql-ws\r\n"));
+    assert!(handshake.ends_with("\r\n\r\n"));
+    let ws: Uri = WebSocketRequest {
+      address: Some("ws://{{Hostname}}/socket".to_string()),
+      ..(**websocket).clone()
+    }
+    .address(&marker)
+    .unwrap();
+    assert_eq!(ws.to_string(), "http://ha.example.com:8123/socket");
+
+    let data = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\nSec-WebSocket-Protocol: graphql-ws\r\n\r\n\x81\x02hi";
+    let (handshake, rest) = WebSocketHandshake::parse(data).unwrap();
+    assert!(handshake.is_upgraded("dGhlIHNhbXBsZSBub25jZQ=="));
+    assert!(!handshake.is_upgraded("a2V5"));
+    assert_eq!(rest, b"\x81\x02hi");
+    let frames = vec![
+      WebSocketFrame::new(
+        Opcode::Text,
+        br#"{"type":"auth_required","ha_version":"2024.1.0"}"#.to_vec(),
+      ),
+      WebSocketFrame::new(Opcode::Binary, vec![0, 1]),
+    ];
+    let request = Request::raw(uri, vec![], false);
+    let response = handshake.into_response(request, &frames, ConnectionInfo::default());
+    let mut operators = (*websocket.operators).clone();
+    operators.compile().unwrap();
+    let mut result = OperatorResult::default();
+    operators.matcher(&response, &mut result).unwrap();
+    assert!(result.is_matched());
+  }
+}
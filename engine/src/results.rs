@@ -1,12 +1,15 @@
-use crate::info::Info;
+use crate::common::Payload;
+use crate::common::nvd::{NvdDatabase, Vulnerability};
+use crate::info::{Cpe, Info};
 use crate::operators::OperatorResult;
+use crate::operators::xpath::DocumentCache;
 use crate::serde_format::Value;
 use serde::{Deserialize, Serialize};
 use slinger::Response;
 use slinger::http::uri::Uri;
 use slinger::http_serde;
 use slinger::record::HTTPRecord;
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
@@ -24,9 +27,9 @@ pub struct MatchEvent {
       title = "matcher results",
       description = "Detailed results of all matched fingerprinting rules",
       example = r#"[{
-            "name": "nginx-version",
-            "matched": "server header",
-            "confidence": 90
+            "template": "nginx",
+            "matcher-name": ["server-header"],
+            "extractor": {"version": ["1.18.0"]}
         }]"#
     )
   )]
@@ -54,6 +57,12 @@ pub struct MatchEvent {
     )
   )]
   nuclei: HashSet<NameNuclei>,
+  // 生成当前请求的payload
+  #[serde(skip)]
+  payload: Payload,
+  // 内部提取器提取到的变量，不输出
+  #[serde(skip)]
+  variables: BTreeMap<String, String>,
 }
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,6 +73,31 @@ pub struct MatcherResult {
   pub info: Arc<Info>,
   pub matcher_name: Vec<String>,
   pub extractor: BTreeMap<String, HashSet<String>>,
+  /// 指纹的分类
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "category",
+      description = "Category of the detected technology",
+      example = r#"["waf", "cdn"]"#
+    )
+  )]
+  pub category: Vec<String>,
+  /// 命中时请求使用的payload
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub payload: BTreeMap<String, String>,
+  /// 通过implies推断出来的指纹，值为推断来源的模板
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "inferred from",
+      description = "Template id that implies this technology, present only for inferred fingerprints",
+      example = &"wordpress"
+    )
+  )]
+  pub inferred_from: Option<String>,
 }
 impl MatchEvent {
   pub fn nuclei_result(&self) -> &HashSet<NameNuclei> {
@@ -78,23 +112,57 @@ impl MatchEvent {
       info: info.clone(),
       matcher_name: ops.matcher_word(),
       extractor: ops.extract_result(),
+      category: info.get_category(),
+      payload: self.payload.values().clone(),
+      inferred_from: None,
+    });
+  }
+  /// 添加implies推断出来的指纹，没有命中的匹配器和提取器
+  pub fn push_inferred(&mut self, template: &str, info: &Arc<Info>, source: &str) {
+    self.matcher_results.push(MatcherResult {
+      template: template.to_string(),
+      info: info.clone(),
+      matcher_name: vec![],
+      extractor: BTreeMap::new(),
+      category: info.get_category(),
+      payload: BTreeMap::new(),
+      inferred_from: Some(source.to_string()),
     });
   }
   pub fn new(response: &Response) -> Self {
+    let mut response = response.clone();
+    // 所有模板匹配同一个响应，XPath解析的文档在模板之间复用
+    if response.extensions().get::<DocumentCache>().is_none() {
+      response.extensions_mut().insert(DocumentCache::default());
+    }
     let request = response.request().cloned().unwrap_or_default();
     let uri = request.uri().clone();
+    let payload = response
+      .extensions()
+      .get::<Payload>()
+      .cloned()
+      .unwrap_or_default();
     Self {
       matcher_results: vec![],
       matched_at: uri,
       record: Some(Arc::new(HTTPRecord {
         request,
         raw_request: Default::default(),
-        response: response.clone(),
+        response,
         raw_response: Default::default(),
       })),
       nuclei: Default::default(),
+      payload,
+      variables: BTreeMap::new(),
     }
   }
+  /// 内部提取器提取到的变量，给后续的请求使用
+  pub fn variables(&self) -> &BTreeMap<String, String> {
+    &self.variables
+  }
+  pub fn insert_variables(&mut self, variables: BTreeMap<String, String>) {
+    self.variables.extend(variables);
+  }
   pub fn matched_at(&self) -> &Uri {
     &self.matched_at
   }
@@ -136,6 +204,334 @@ impl MatchEvent {
     em
   }
 }
+/// 匹配器的默认权重，模板可以在 `info.metadata` 的 `confidence` 中给整个模板或者按匹配器名称指定
+pub const DEFAULT_WEIGHT: u8 = 80;
+
+/// 单个技术合并之后的版本和可信度
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Technology {
+  /// Name of the technology
+  pub name: String,
+  /// Best version candidate
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "version",
+      description = "Version supported by the most independent evidence",
+      example = &"1.18.0"
+    )
+  )]
+  pub version: Option<String>,
+  /// Other version candidates
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub alternatives: Vec<String>,
+  /// CPE 2.3 of the technology with the best version
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "cpe",
+      description = "CPE 2.3 formatted string of the technology with the best version",
+      example = &"cpe:2.3:a:f5:nginx:1.18.0:*:*:*:*:*:*:*"
+    )
+  )]
+  pub cpe: Option<String>,
+  /// Confidence score from 0 to 100
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "confidence",
+      description = "Confidence score from 0 to 100, combined from the weight of every independent evidence",
+      example = 96
+    )
+  )]
+  pub confidence: u8,
+  /// Evidence that detected the technology
+  pub evidence: Vec<Evidence>,
+  /// Vulnerabilities of the version from the local NVD feed
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "vulnerabilities",
+      description = "CVE of the detected version correlated offline from the local NVD feed, sorted by CVSS score"
+    )
+  )]
+  pub vulnerabilities: Vec<Vulnerability>,
+  // 模板的CPE，版本在合并之后替换
+  #[serde(skip)]
+  base_cpe: Option<Cpe>,
+}
+
+/// 技术命中的证据，同一个地址的证据会合并
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Evidence {
+  /// URL where the evidence was found
+  pub matched_at: String,
+  /// Matcher names, or nuclei template ids for nuclei evidence
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub matcher: Vec<String>,
+  /// Version extracted by the evidence, keyed by extractor name
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub version: BTreeMap<String, String>,
+  /// Template id of the technology that implies this one
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub inferred_from: Option<String>,
+  /// Weight of the evidence from 0 to 100
+  pub weight: u8,
+}
+
+/// 规范化版本号，去掉`v`前缀和后面的说明，不是数字开头的不当作版本
+pub fn normalize_version(version: &str) -> Option<String> {
+  let version = version
+    .split_whitespace()
+    .next()?
+    .trim_start_matches(['v', 'V'])
+    .trim_end_matches(|c: char| !c.is_ascii_alphanumeric());
+  if version.starts_with(|c: char| c.is_ascii_digit()) {
+    Some(version.to_string())
+  } else {
+    None
+  }
+}
+
+impl Evidence {
+  /// 从匹配结果生成证据，名称包含`version`的提取器作为版本候选
+  pub fn new(mr: &MatcherResult, matched_at: &str) -> Self {
+    let version = mr
+      .extractor
+      .iter()
+      .filter(|(k, _)| k.to_lowercase().contains("version"))
+      .filter_map(|(k, v)| {
+        v.iter()
+          .filter_map(|x| normalize_version(x))
+          .min()
+          .map(|x| (k.clone(), x))
+      })
+      .collect();
+    Self {
+      matched_at: matched_at.to_string(),
+      matcher: mr.matcher_name.clone(),
+      version,
+      inferred_from: mr.inferred_from.clone(),
+      weight: weight(&mr.info, &mr.matcher_name),
+    }
+  }
+  /// 从nuclei的结果生成证据，提取到的结果中是版本号的作为版本候选
+  pub fn from_nuclei(nuclei: &NucleiResult) -> Option<Self> {
+    let version = nuclei
+      .extracted_results
+      .iter()
+      .flatten()
+      .filter_map(|x| normalize_version(x))
+      .min()?;
+    Some(Self {
+      matched_at: nuclei.matched_at.clone(),
+      matcher: vec![nuclei.template_id.clone()],
+      version: BTreeMap::from([(nuclei.template_id.clone(), version)]),
+      inferred_from: None,
+      weight: DEFAULT_WEIGHT,
+    })
+  }
+}
+
+// 证据的权重，多个匹配器命中时取最大的权重
+fn weight(info: &Info, matcher: &[String]) -> u8 {
+  let weight = match info.metadata.get("confidence") {
+    Some(Value::Num(n)) => *n,
+    Some(Value::Map(m)) => matcher
+      .iter()
+      .filter_map(|name| match m.get(name) {
+        Some(Value::Num(n)) => Some(*n),
+        _ => None,
+      })
+      .max()
+      .unwrap_or(DEFAULT_WEIGHT as u32),
+    _ => DEFAULT_WEIGHT as u32,
+  };
+  weight.min(100) as u8
+}
+
+impl Technology {
+  pub fn new(info: &Info) -> Self {
+    let mut technology = Self {
+      name: info.name.clone(),
+      base_cpe: info.get_cpe(),
+      ..Default::default()
+    };
+    technology.consolidate();
+    technology
+  }
+  /// 带上版本的CPE
+  pub fn get_cpe(&self) -> Option<Cpe> {
+    self
+      .base_cpe
+      .as_ref()
+      .map(|cpe| cpe.with_version(self.version.as_deref()))
+  }
+  /// 使用本地的NVD数据关联当前版本的漏洞
+  pub fn correlate(&mut self, nvd: &NvdDatabase) {
+    self.vulnerabilities = self
+      .get_cpe()
+      .map(|cpe| nvd.lookup(&cpe))
+      .unwrap_or_default();
+  }
+  /// 添加证据并重新计算版本和可信度
+  pub fn push(&mut self, evidence: Evidence) {
+    if let Some(e) = self
+      .evidence
+      .iter_mut()
+      .find(|e| e.matched_at == evidence.matched_at && e.inferred_from == evidence.inferred_from)
+    {
+      for m in evidence.matcher {
+        if !e.matcher.contains(&m) {
+          e.matcher.push(m);
+        }
+      }
+      for (k, v) in evidence.version {
+        e.version.entry(k).or_insert(v);
+      }
+      e.weight = e.weight.max(evidence.weight);
+    } else {
+      self.evidence.push(evidence);
+    }
+    self.consolidate();
+  }
+  // 版本按支持它的证据数量排序，数量相同时取更具体的版本；每个独立的证据都会提高可信度
+  fn consolidate(&mut self) {
+    let mut count: BTreeMap<&String, usize> = BTreeMap::new();
+    for e in self.evidence.iter() {
+      for v in e.version.values().collect::<BTreeSet<_>>() {
+        *count.entry(v).or_default() += 1;
+      }
+    }
+    let mut versions: Vec<(&String, usize)> = count.into_iter().collect();
+    versions.sort_by(|a, b| {
+      b.1
+        .cmp(&a.1)
+        .then_with(|| b.0.split('.').count().cmp(&a.0.split('.').count()))
+        .then_with(|| a.0.cmp(b.0))
+    });
+    let mut versions = versions.into_iter().map(|(v, _)| v.clone());
+    self.version = versions.next();
+    self.alternatives = versions.collect();
+    let miss = self
+      .evidence
+      .iter()
+      .fold(1.0, |miss, e| miss * (1.0 - f64::from(e.weight) / 100.0));
+    self.confidence = ((1.0 - miss) * 100.0).round() as u8;
+    self.cpe = self.get_cpe().map(|cpe| cpe.to_string());
+  }
+}
+
+/// 指纹之间implies、excludes和requires关系的处理结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Relations {
+  /// 推断出来的指纹和推断来源，来源总是排在被推断的指纹前面
+  pub implied: Vec<(String, String)>,
+  /// 依赖没有满足或者被互斥剔除的指纹
+  pub removed: BTreeSet<String>,
+}
+
+// 从已有的指纹按implies推断，返回推断出来的指纹和直接推断它的来源
+fn implies_closure(
+  present: &BTreeSet<String>,
+  infos: &BTreeMap<String, Arc<Info>>,
+  excluded: &BTreeSet<String>,
+) -> Vec<(String, String)> {
+  let mut seen = present.clone();
+  let mut queue: Vec<String> = present.iter().cloned().collect();
+  let mut implied = Vec::new();
+  let mut index = 0;
+  while index < queue.len() {
+    let source = queue[index].clone();
+    index += 1;
+    let Some(info) = infos.get(&source) else {
+      continue;
+    };
+    for template in info.get_implies() {
+      if excluded.contains(&template) || !seen.insert(template.clone()) {
+        continue;
+      }
+      implied.push((template.clone(), source.clone()));
+      queue.push(template);
+    }
+  }
+  implied
+}
+
+/// 根据模板 `info.metadata` 中的 `implies`、`excludes` 和 `requires` 处理命中的指纹：
+/// 先剔除依赖没有满足的指纹，再按模板id的顺序处理互斥，最后补充推断出来的指纹
+pub fn resolve_relations(
+  matched: &HashSet<String>,
+  infos: &BTreeMap<String, Arc<Info>>,
+) -> Relations {
+  let mut kept: BTreeSet<String> = matched.iter().cloned().collect();
+  let no_excluded = BTreeSet::new();
+  // 剔除一个指纹后可能导致其他指纹的依赖不满足，直到没有变化
+  loop {
+    let mut present = kept.clone();
+    present.extend(
+      implies_closure(&kept, infos, &no_excluded)
+        .into_iter()
+        .map(|(template, _)| template),
+    );
+    let unmet: Vec<String> = kept
+      .iter()
+      .filter(|template| {
+        infos.get(*template).is_some_and(|info| {
+          info
+            .get_requires()
+            .iter()
+            .any(|require| !present.contains(require))
+        })
+      })
+      .cloned()
+      .collect();
+    if unmet.is_empty() {
+      break;
+    }
+    for template in unmet {
+      kept.remove(&template);
+    }
+  }
+  // 已经被剔除的指纹不再剔除其他指纹
+  let mut present = kept.clone();
+  present.extend(
+    implies_closure(&kept, infos, &no_excluded)
+      .into_iter()
+      .map(|(template, _)| template),
+  );
+  let mut excluded = BTreeSet::new();
+  for template in present.iter() {
+    if excluded.contains(template) {
+      continue;
+    }
+    if let Some(info) = infos.get(template) {
+      excluded.extend(
+        info
+          .get_excludes()
+          .into_iter()
+          .filter(|exclude| exclude != template),
+      );
+    }
+  }
+  kept.retain(|template| !excluded.contains(template));
+  Relations {
+    implied: implies_closure(&kept, infos, &excluded),
+    removed: matched
+      .iter()
+      .filter(|template| !kept.contains(*template))
+      .cloned()
+      .collect(),
+  }
+}
+
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
 #[serde(rename_all = "kebab-case")]
@@ -244,3 +640,142 @@ impl Hash for NucleiResult {
     self.matched_at.hash(state);
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn info(relations: &[(&str, &[&str])]) -> Arc<Info> {
+    let mut info = Info::default();
+    for (key, templates) in relations {
+      info.metadata.insert(
+        key.to_string(),
+        Value::List(
+          templates
+            .iter()
+            .map(|x| Value::String(x.to_string()))
+            .collect(),
+        ),
+      );
+    }
+    Arc::new(info)
+  }
+
+  #[test]
+  fn consolidate_technology_version() {
+    assert_eq!(normalize_version("v1.18.0"), Some("1.18.0".to_string()));
+    assert_eq!(
+      normalize_version("8.2p1 Ubuntu-4ubuntu0.5"),
+      Some("8.2p1".to_string())
+    );
+    assert_eq!(normalize_version("$1"), None);
+    assert_eq!(normalize_version(""), None);
+    let mut nginx = Info {
+      name: "nginx".to_string(),
+      ..Default::default()
+    };
+    nginx.metadata.insert(
+      "confidence".to_string(),
+      Value::Map(BTreeMap::from([(
+        "header-server".to_string(),
+        Value::Num(50),
+      )])),
+    );
+    let template = Arc::new(nginx.clone());
+    let mr = |matcher: &str, version: &str| MatcherResult {
+      template: "nginx".to_string(),
+      info: template.clone(),
+      matcher_name: vec![matcher.to_string()],
+      extractor: BTreeMap::from([("version".to_string(), HashSet::from([version.to_string()]))]),
+      category: vec![],
+      payload: BTreeMap::new(),
+      inferred_from: None,
+    };
+    let mut technology = Technology::new(&nginx);
+    technology.push(Evidence::new(
+      &mr("header-server", "1.18"),
+      "https://example.com/",
+    ));
+    assert_eq!(technology.version, Some("1.18".to_string()));
+    assert_eq!(technology.confidence, 50);
+    // 同一个地址的证据合并，不算独立的证据
+    technology.push(Evidence::new(
+      &mr("body", "v1.18.0"),
+      "https://example.com/",
+    ));
+    assert_eq!(technology.evidence.len(), 1);
+    assert_eq!(technology.confidence, 80);
+    // 更多独立的证据支持的版本优先
+    technology.push(Evidence::new(
+      &mr("body", "1.18.0"),
+      "https://example.com/404",
+    ));
+    assert_eq!(technology.version, Some("1.18.0".to_string()));
+    assert_eq!(technology.alternatives, vec!["1.18"]);
+    assert_eq!(technology.confidence, 96);
+    // 没有vendor的模板不生成CPE
+    assert!(technology.cpe.is_none());
+    nginx.metadata.insert(
+      "cpe".to_string(),
+      Value::String("cpe:2.3:a:f5:nginx:*:*:*:*:*:*:*:*".to_string()),
+    );
+    let mut technology = Technology::new(&nginx);
+    assert_eq!(
+      technology.cpe.as_deref(),
+      Some("cpe:2.3:a:f5:nginx:*:*:*:*:*:*:*:*")
+    );
+    technology.push(Evidence::new(&mr("body", "1.18.0"), "https://example.com/"));
+    assert_eq!(
+      technology.cpe.as_deref(),
+      Some("cpe:2.3:a:f5:nginx:1.18.0:*:*:*:*:*:*:*")
+    );
+  }
+
+  #[test]
+  fn resolve_technology_relations() {
+    let infos = BTreeMap::from([
+      (
+        "wordpress".to_string(),
+        info(&[("implies", &["php", "mysql"])]),
+      ),
+      ("php".to_string(), info(&[("implies", &["php-fpm"])])),
+      (
+        "next-js".to_string(),
+        info(&[("implies", &["react", "node-js"])]),
+      ),
+      ("react".to_string(), info(&[("excludes", &["preact"])])),
+      ("preact".to_string(), info(&[])),
+      (
+        "woocommerce".to_string(),
+        info(&[("requires", &["wordpress"])]),
+      ),
+      ("elementor".to_string(), info(&[("requires", &["php"])])),
+    ]);
+    let matched = |names: &[&str]| names.iter().map(|x| x.to_string()).collect::<HashSet<_>>();
+    // 推断可以传递，来源是直接推断它的指纹
+    let relations = resolve_relations(&matched(&["wordpress", "elementor"]), &infos);
+    assert_eq!(
+      relations.implied,
+      vec![
+        ("php".to_string(), "wordpress".to_string()),
+        ("mysql".to_string(), "wordpress".to_string()),
+        ("php-fpm".to_string(), "php".to_string()),
+      ]
+    );
+    assert!(relations.removed.is_empty());
+    // 推断出来的指纹可以互斥命中的指纹
+    let relations = resolve_relations(&matched(&["next-js", "preact"]), &infos);
+    assert_eq!(relations.removed, BTreeSet::from(["preact".to_string()]));
+    assert_eq!(relations.implied.len(), 2);
+    // 依赖没有命中，剔除之后也不会推断其他指纹
+    let relations = resolve_relations(&matched(&["woocommerce", "nginx"]), &infos);
+    assert_eq!(
+      relations.removed,
+      BTreeSet::from(["woocommerce".to_string()])
+    );
+    assert!(relations.implied.is_empty());
+    // 依赖可以由推断满足
+    let relations = resolve_relations(&matched(&["woocommerce", "wordpress"]), &infos);
+    assert!(relations.removed.is_empty());
+  }
+}
@@ -1,4 +1,5 @@
 use crate::execute::{ClusterExecute, ClusterType, ClusteredOperator};
+use crate::operators::matchers::MatcherType;
 use crate::request::Requests;
 use crate::template::Template;
 use log::debug;
@@ -14,6 +15,14 @@ pub fn cluster_templates(templates_list: &[Template], index_only: bool) -> Clust
     // 编译正则和一些预处理
     match template.compile() {
       Ok(_) => {
+        executes
+          .infos
+          .insert(template.id.clone(), template.info.clone());
+        executes.jarm |= template.requests.operators().iter().any(|op| {
+          op.matchers
+            .iter()
+            .any(|m| matches!(m.matcher_type, MatcherType::Jarm(..)))
+        });
         // 剔除图标指纹，如果还有其他规则就加进已经编译列表
         let favicon = template.find_favicon();
         if !template
@@ -39,11 +48,15 @@ pub fn cluster_templates(templates_list: &[Template], index_only: bool) -> Clust
     }
     let requests = clusters[0].requests.clone();
     let info = clusters[0].info.clone();
+    let flow = clusters[0].compiled_flow.clone();
+    let variables = clusters[0].variables.clone();
     let cops = clusters.into_iter().map(ClusteredOperator::new).collect();
     let cluster_execute = Arc::new(ClusterExecute {
       requests: requests.clone(),
       rarity: info.get_rarity().unwrap_or_default(),
       operators: cops,
+      flow,
+      variables,
     });
     if let Some(_web) = requests.is_web() {
       // 如果请求是首页请求就加进去首页分类，否则加入危险分类
@@ -65,6 +78,14 @@ pub fn cluster_templates(templates_list: &[Template], index_only: bool) -> Clust
           .port_range
           .insert(tcp.name.clone().unwrap_or_default(), tcp.port.clone());
       }
+    } else if requests.is_udp().is_some() {
+      executes.udp.push(cluster_execute);
+    } else if requests.is_ssl().is_some() {
+      executes.ssl.push(cluster_execute);
+    } else if requests.is_dns().is_some() {
+      executes.dns.push(cluster_execute);
+    } else if requests.is_websocket().is_some() {
+      executes.websocket.push(cluster_execute);
     } else {
       #[cfg(feature = "code")]
       if requests.is_code().is_some() {
@@ -78,6 +99,8 @@ pub fn cluster_templates(templates_list: &[Template], index_only: bool) -> Clust
       requests: Default::default(),
       rarity: 0,
       operators: favicon_cops,
+      flow: None,
+      variables: Default::default(),
     }));
   }
   // 如果只有图标hash，没有请求就补充一个Web首页请求
@@ -86,6 +109,8 @@ pub fn cluster_templates(templates_list: &[Template], index_only: bool) -> Clust
       requests: Arc::new(Requests::default_web_index()),
       rarity: 0,
       operators: vec![],
+      flow: None,
+      variables: Default::default(),
     }))
   }
   executes
@@ -102,10 +127,18 @@ fn cluster(list: &[Arc<Template>]) -> Vec<Vec<Arc<Template>>> {
     } else {
       continue;
     }
-    if t.requests.http.len() == 1 || t.requests.tcp.len() == 1 {
+    // 有flow或者变量的模板需要按自己的流程执行，不参与合并
+    if t.can_cluster()
+      && (t.requests.http.len() == 1
+        || t.requests.tcp.len() == 1
+        || t.requests.udp.len() == 1
+        || t.requests.ssl.len() == 1
+        || t.requests.dns.len() == 1
+        || t.requests.websocket.len() == 1)
+    {
       let mut cluster = Vec::new();
       for ot in list.iter() {
-        if skip.contains(&ot.id) {
+        if skip.contains(&ot.id) || !ot.can_cluster() {
           continue;
         }
         if t.requests.can_cluster(&ot.requests) {
@@ -0,0 +1,779 @@
+use crate::error::{Error, Result};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+
+/// Flow 是模板中 `flow` 字段的编译结果
+///
+/// 支持的是 nuclei flow 中常用的 JavaScript 子集：
+/// - 协议调用：`http(0)`、`tcp(1)`、`code("name")`，不带参数时执行该协议的全部请求
+/// - 布尔运算：`&&`、`||`、`!`、`==`、`!=`，并且会短路
+/// - 语句：`if (..) {..} else {..}`、`let x = ..`、`return ..`
+/// - 循环：`for x in vars {..}`、`for (let x of iterate(template["vars"])) {..}`
+/// - 内置函数：`set(name, value)`、`iterate(..)`、`log(..)`、`len(..)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flow {
+  statements: Vec<Statement>,
+}
+
+/// 协议调用的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FlowProtocol {
+  Http,
+  Tcp,
+  Udp,
+  Ssl,
+  Dns,
+  Websocket,
+  Code,
+}
+
+impl FlowProtocol {
+  fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "http" => Some(Self::Http),
+      "tcp" | "network" => Some(Self::Tcp),
+      "udp" => Some(Self::Udp),
+      "ssl" => Some(Self::Ssl),
+      "dns" => Some(Self::Dns),
+      "websocket" => Some(Self::Websocket),
+      "code" => Some(Self::Code),
+      _ => None,
+    }
+  }
+}
+
+impl Display for FlowProtocol {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let s = match self {
+      FlowProtocol::Http => "http",
+      FlowProtocol::Tcp => "tcp",
+      FlowProtocol::Udp => "udp",
+      FlowProtocol::Ssl => "ssl",
+      FlowProtocol::Dns => "dns",
+      FlowProtocol::Websocket => "websocket",
+      FlowProtocol::Code => "code",
+    };
+    f.write_str(s)
+  }
+}
+
+/// 协议调用选择哪一个请求
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FlowSelector {
+  /// 不带参数，执行全部请求
+  All,
+  /// 请求下标，从0开始
+  Index(usize),
+  /// 请求的id或者name
+  Name(String),
+}
+
+impl FlowSelector {
+  /// 判断第 `index` 个请求是否被选中
+  pub fn matches(&self, index: usize, id: Option<&str>, name: Option<&str>) -> bool {
+    match self {
+      FlowSelector::All => true,
+      FlowSelector::Index(i) => *i == index,
+      FlowSelector::Name(n) => id == Some(n.as_str()) || name == Some(n.as_str()),
+    }
+  }
+}
+
+/// flow 运行时的值
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum FlowValue {
+  #[default]
+  Null,
+  Bool(bool),
+  Number(i64),
+  String(String),
+  List(Vec<FlowValue>),
+}
+
+impl FlowValue {
+  pub fn is_truthy(&self) -> bool {
+    match self {
+      FlowValue::Null => false,
+      FlowValue::Bool(b) => *b,
+      FlowValue::Number(n) => *n != 0,
+      FlowValue::String(s) => !s.is_empty(),
+      FlowValue::List(l) => !l.is_empty(),
+    }
+  }
+  /// 循环时展开成列表，单个值当作只有一个元素
+  fn into_list(self) -> Vec<FlowValue> {
+    match self {
+      FlowValue::Null => Vec::new(),
+      FlowValue::List(l) => l,
+      v => vec![v],
+    }
+  }
+}
+
+impl Display for FlowValue {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      FlowValue::Null => Ok(()),
+      FlowValue::Bool(b) => write!(f, "{b}"),
+      FlowValue::Number(n) => write!(f, "{n}"),
+      FlowValue::String(s) => f.write_str(s),
+      FlowValue::List(l) => f.write_str(
+        &l.iter()
+          .map(|v| v.to_string())
+          .collect::<Vec<_>>()
+          .join(","),
+      ),
+    }
+  }
+}
+
+impl From<&str> for FlowValue {
+  fn from(value: &str) -> Self {
+    FlowValue::String(value.to_string())
+  }
+}
+
+impl From<String> for FlowValue {
+  fn from(value: String) -> Self {
+    FlowValue::String(value)
+  }
+}
+
+impl<T: Into<FlowValue>> FromIterator<T> for FlowValue {
+  fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+    FlowValue::List(iter.into_iter().map(Into::into).collect())
+  }
+}
+
+/// flow 的变量上下文，模板变量和提取器的结果都会放进来
+pub type FlowVariables = BTreeMap<String, FlowValue>;
+
+/// 执行协议请求的一方，由具体的运行器实现
+pub trait FlowExecutor: Send {
+  /// 执行指定协议的请求，返回是否命中了该请求的匹配器
+  ///
+  /// 执行过程中提取到的值可以写入 `variables`，供后续的调用和循环使用
+  fn call(
+    &mut self,
+    protocol: FlowProtocol,
+    selector: FlowSelector,
+    variables: &mut FlowVariables,
+  ) -> impl Future<Output = bool> + Send;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Statement {
+  Expr(Expr),
+  Let(String, Expr),
+  If(Expr, Vec<Statement>, Vec<Statement>),
+  For(String, Expr, Vec<Statement>),
+  Return(Option<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+  Literal(FlowValue),
+  Ident(String),
+  List(Vec<Expr>),
+  Call(String, Vec<Expr>),
+  Member(Box<Expr>, Box<Expr>),
+  Not(Box<Expr>),
+  And(Box<Expr>, Box<Expr>),
+  Or(Box<Expr>, Box<Expr>),
+  Eq(Box<Expr>, Box<Expr>),
+  Ne(Box<Expr>, Box<Expr>),
+}
+
+enum Control {
+  Next(Option<FlowValue>),
+  Return(FlowValue),
+}
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+struct FlowState<'e, E: FlowExecutor> {
+  executor: &'e mut E,
+  variables: FlowVariables,
+  matched: bool,
+}
+
+impl Flow {
+  pub fn parse(source: &str) -> Result<Self> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    let mut statements = Vec::new();
+    while !parser.is_end() {
+      statements.push(parser.statement()?);
+    }
+    Ok(Flow { statements })
+  }
+
+  /// 执行flow，返回最终结果
+  ///
+  /// 有 `return` 时以返回值为准，否则最后一条顶层语句是表达式时取它的值，
+  /// 都没有时只要任意一次协议调用命中就为真
+  pub async fn execute<E: FlowExecutor>(&self, executor: &mut E, variables: FlowVariables) -> bool {
+    let mut state = FlowState {
+      executor,
+      variables,
+      matched: false,
+    };
+    match exec_block(&self.statements, &mut state).await {
+      Control::Return(v) => v.is_truthy(),
+      Control::Next(Some(v)) => v.is_truthy(),
+      Control::Next(None) => state.matched,
+    }
+  }
+}
+
+fn exec_block<'a, 'e, E: FlowExecutor>(
+  statements: &'a [Statement],
+  state: &'a mut FlowState<'e, E>,
+) -> BoxFuture<'a, Control> {
+  Box::pin(async move {
+    let mut last = None;
+    for statement in statements {
+      // 只有最后一条语句是表达式时才作为结果
+      last = None;
+      match statement {
+        Statement::Expr(expr) => {
+          last = Some(eval(expr, state).await);
+        }
+        Statement::Let(name, expr) => {
+          let value = eval(expr, state).await;
+          state.variables.insert(name.clone(), value);
+        }
+        Statement::If(cond, then, otherwise) => {
+          let branch = if eval(cond, state).await.is_truthy() {
+            then
+          } else {
+            otherwise
+          };
+          if let Control::Return(v) = exec_block(branch, state).await {
+            return Control::Return(v);
+          }
+        }
+        Statement::For(name, iterable, body) => {
+          for item in eval(iterable, state).await.into_list() {
+            state.variables.insert(name.clone(), item);
+            if let Control::Return(v) = exec_block(body, state).await {
+              return Control::Return(v);
+            }
+          }
+        }
+        Statement::Return(expr) => {
+          let value = match expr {
+            Some(expr) => eval(expr, state).await,
+            None => FlowValue::Bool(state.matched),
+          };
+          return Control::Return(value);
+        }
+      }
+    }
+    Control::Next(last)
+  })
+}
+
+fn eval<'a, 'e, E: FlowExecutor>(
+  expr: &'a Expr,
+  state: &'a mut FlowState<'e, E>,
+) -> BoxFuture<'a, FlowValue> {
+  Box::pin(async move {
+    match expr {
+      Expr::Literal(v) => v.clone(),
+      Expr::Ident(name) => state.variables.get(name).cloned().unwrap_or_default(),
+      Expr::List(items) => {
+        let mut list = Vec::with_capacity(items.len());
+        for item in items {
+          list.push(eval(item, state).await);
+        }
+        FlowValue::List(list)
+      }
+      Expr::Member(base, key) => {
+        let key = eval(key, state).await;
+        // template["name"] 和 template.name 都是读取变量
+        if matches!(base.as_ref(), Expr::Ident(name) if name == "template") {
+          return state
+            .variables
+            .get(&key.to_string())
+            .cloned()
+            .unwrap_or_default();
+        }
+        match (eval(base, state).await, key) {
+          (FlowValue::List(list), FlowValue::Number(i)) => usize::try_from(i)
+            .ok()
+            .and_then(|i| list.get(i).cloned())
+            .unwrap_or_default(),
+          _ => FlowValue::Null,
+        }
+      }
+      Expr::Not(inner) => FlowValue::Bool(!eval(inner, state).await.is_truthy()),
+      Expr::And(left, right) => {
+        if !eval(left, state).await.is_truthy() {
+          return FlowValue::Bool(false);
+        }
+        FlowValue::Bool(eval(right, state).await.is_truthy())
+      }
+      Expr::Or(left, right) => {
+        if eval(left, state).await.is_truthy() {
+          return FlowValue::Bool(true);
+        }
+        FlowValue::Bool(eval(right, state).await.is_truthy())
+      }
+      Expr::Eq(left, right) => {
+        let l = eval(left, state).await;
+        let r = eval(right, state).await;
+        FlowValue::Bool(l == r || l.to_string() == r.to_string())
+      }
+      Expr::Ne(left, right) => {
+        let l = eval(left, state).await;
+        let r = eval(right, state).await;
+        FlowValue::Bool(l != r && l.to_string() != r.to_string())
+      }
+      Expr::Call(name, args) => {
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+          values.push(eval(arg, state).await);
+        }
+        call(name, values, state).await
+      }
+    }
+  })
+}
+
+async fn call<E: FlowExecutor>(
+  name: &str,
+  args: Vec<FlowValue>,
+  state: &mut FlowState<'_, E>,
+) -> FlowValue {
+  if let Some(protocol) = FlowProtocol::from_name(name) {
+    let selector = match args.into_iter().next() {
+      None | Some(FlowValue::Null) => FlowSelector::All,
+      Some(FlowValue::Number(n)) => FlowSelector::Index(n.max(0) as usize),
+      Some(v) => FlowSelector::Name(v.to_string()),
+    };
+    let matched = state
+      .executor
+      .call(protocol, selector, &mut state.variables)
+      .await;
+    state.matched |= matched;
+    return FlowValue::Bool(matched);
+  }
+  match name {
+    "set" => {
+      let mut args = args.into_iter();
+      if let (Some(key), Some(value)) = (args.next(), args.next()) {
+        state.variables.insert(key.to_string(), value);
+      }
+      FlowValue::Null
+    }
+    "iterate" => FlowValue::List(args.into_iter().flat_map(FlowValue::into_list).collect()),
+    "len" => FlowValue::Number(match args.first() {
+      Some(FlowValue::List(l)) => l.len() as i64,
+      Some(v) => v.to_string().len() as i64,
+      None => 0,
+    }),
+    "log" => {
+      log::debug!(
+        "flow: {}",
+        args
+          .iter()
+          .map(|v| v.to_string())
+          .collect::<Vec<_>>()
+          .join(" ")
+      );
+      FlowValue::Null
+    }
+    _ => {
+      log::debug!("flow: unknown function {name}");
+      FlowValue::Null
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Ident(String),
+  Number(i64),
+  Str(String),
+  Punct(&'static str),
+}
+
+const PUNCTUATIONS: [&str; 15] = [
+  "&&", "||", "==", "!=", "!", "(", ")", "{", "}", "[", "]", ",", ";", ".", "=",
+];
+
+fn flow_error(msg: String) -> Error {
+  Error::IO(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg))
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+  let mut tokens = Vec::new();
+  let chars: Vec<char> = source.chars().collect();
+  let mut i = 0;
+  while i < chars.len() {
+    let c = chars[i];
+    if c == '/' && chars.get(i + 1) == Some(&'/') {
+      while i < chars.len() && chars[i] != '\n' {
+        i += 1;
+      }
+      continue;
+    }
+    if c.is_whitespace() {
+      i += 1;
+      continue;
+    }
+    if c.is_ascii_digit() {
+      let start = i;
+      while i < chars.len() && chars[i].is_ascii_digit() {
+        i += 1;
+      }
+      let n: String = chars[start..i].iter().collect();
+      tokens.push(Token::Number(n.parse()?));
+      continue;
+    }
+    if c.is_alphabetic() || c == '_' || c == '$' {
+      let start = i;
+      while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+        i += 1;
+      }
+      tokens.push(Token::Ident(chars[start..i].iter().collect()));
+      continue;
+    }
+    if c == '"' || c == '\'' || c == '`' {
+      let mut s = String::new();
+      i += 1;
+      loop {
+        match chars.get(i) {
+          None => return Err(flow_error("flow: unterminated string".to_string())),
+          Some('\\') => {
+            match chars.get(i + 1) {
+              Some('n') => s.push('\n'),
+              Some('t') => s.push('\t'),
+              Some(other) => s.push(*other),
+              None => {}
+            }
+            i += 2;
+          }
+          Some(q) if *q == c => {
+            i += 1;
+            break;
+          }
+          Some(other) => {
+            s.push(*other);
+            i += 1;
+          }
+        }
+      }
+      tokens.push(Token::Str(s));
+      continue;
+    }
+    let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+    match PUNCTUATIONS.iter().find(|p| rest.starts_with(**p)) {
+      Some(p) => {
+        tokens.push(Token::Punct(p));
+        i += p.chars().count();
+      }
+      None => return Err(flow_error(format!("flow: unexpected character '{c}'"))),
+    }
+  }
+  Ok(tokens)
+}
+
+struct Parser {
+  tokens: Vec<Token>,
+  pos: usize,
+}
+
+impl Parser {
+  fn is_end(&self) -> bool {
+    self.pos >= self.tokens.len()
+  }
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.pos)
+  }
+  fn next(&mut self) -> Option<Token> {
+    let token = self.tokens.get(self.pos).cloned();
+    self.pos += 1;
+    token
+  }
+  fn eat_punct(&mut self, p: &str) -> bool {
+    if matches!(self.peek(), Some(Token::Punct(x)) if *x == p) {
+      self.pos += 1;
+      return true;
+    }
+    false
+  }
+  fn eat_keyword(&mut self, k: &str) -> bool {
+    if matches!(self.peek(), Some(Token::Ident(x)) if x == k) {
+      self.pos += 1;
+      return true;
+    }
+    false
+  }
+  fn expect_punct(&mut self, p: &str) -> Result<()> {
+    if self.eat_punct(p) {
+      Ok(())
+    } else {
+      Err(flow_error(format!(
+        "flow: expected '{p}' found {:?}",
+        self.peek()
+      )))
+    }
+  }
+  fn ident(&mut self) -> Result<String> {
+    match self.next() {
+      Some(Token::Ident(name)) => Ok(name),
+      other => Err(flow_error(format!(
+        "flow: expected identifier found {other:?}"
+      ))),
+    }
+  }
+  fn statement(&mut self) -> Result<Statement> {
+    let statement = if self.eat_keyword("if") {
+      self.expect_punct("(")?;
+      let cond = self.expr()?;
+      self.expect_punct(")")?;
+      let then = self.block()?;
+      let otherwise = if self.eat_keyword("else") {
+        self.block()?
+      } else {
+        Vec::new()
+      };
+      Statement::If(cond, then, otherwise)
+    } else if self.eat_keyword("for") {
+      let paren = self.eat_punct("(");
+      if !(self.eat_keyword("let") || self.eat_keyword("const")) {
+        self.eat_keyword("var");
+      }
+      let name = self.ident()?;
+      if !(self.eat_keyword("of") || self.eat_keyword("in")) {
+        return Err(flow_error("flow: expected 'of' or 'in'".to_string()));
+      }
+      let iterable = self.expr()?;
+      if paren {
+        self.expect_punct(")")?;
+      }
+      Statement::For(name, iterable, self.block()?)
+    } else if self.eat_keyword("let") || self.eat_keyword("const") || self.eat_keyword("var") {
+      let name = self.ident()?;
+      self.expect_punct("=")?;
+      Statement::Let(name, self.expr()?)
+    } else if self.eat_keyword("return") {
+      if self.is_end() || matches!(self.peek(), Some(Token::Punct(";" | "}"))) {
+        Statement::Return(None)
+      } else {
+        Statement::Return(Some(self.expr()?))
+      }
+    } else {
+      Statement::Expr(self.expr()?)
+    };
+    self.eat_punct(";");
+    Ok(statement)
+  }
+  fn block(&mut self) -> Result<Vec<Statement>> {
+    if !self.eat_punct("{") {
+      return Ok(vec![self.statement()?]);
+    }
+    let mut statements = Vec::new();
+    while !self.eat_punct("}") {
+      if self.is_end() {
+        return Err(flow_error("flow: expected '}'".to_string()));
+      }
+      statements.push(self.statement()?);
+    }
+    Ok(statements)
+  }
+  fn expr(&mut self) -> Result<Expr> {
+    let mut left = self.and()?;
+    while self.eat_punct("||") {
+      left = Expr::Or(Box::new(left), Box::new(self.and()?));
+    }
+    Ok(left)
+  }
+  fn and(&mut self) -> Result<Expr> {
+    let mut left = self.equality()?;
+    while self.eat_punct("&&") {
+      left = Expr::And(Box::new(left), Box::new(self.equality()?));
+    }
+    Ok(left)
+  }
+  fn equality(&mut self) -> Result<Expr> {
+    let mut left = self.unary()?;
+    loop {
+      if self.eat_punct("==") {
+        left = Expr::Eq(Box::new(left), Box::new(self.unary()?));
+      } else if self.eat_punct("!=") {
+        left = Expr::Ne(Box::new(left), Box::new(self.unary()?));
+      } else {
+        return Ok(left);
+      }
+    }
+  }
+  fn unary(&mut self) -> Result<Expr> {
+    if self.eat_punct("!") {
+      return Ok(Expr::Not(Box::new(self.unary()?)));
+    }
+    self.postfix()
+  }
+  fn postfix(&mut self) -> Result<Expr> {
+    let mut expr = self.primary()?;
+    loop {
+      if self.eat_punct("[") {
+        let key = self.expr()?;
+        self.expect_punct("]")?;
+        expr = Expr::Member(Box::new(expr), Box::new(key));
+      } else if self.eat_punct(".") {
+        let key = self.ident()?;
+        expr = Expr::Member(Box::new(expr), Box::new(Expr::Literal(key.into())));
+      } else {
+        return Ok(expr);
+      }
+    }
+  }
+  fn primary(&mut self) -> Result<Expr> {
+    match self.next() {
+      Some(Token::Number(n)) => Ok(Expr::Literal(FlowValue::Number(n))),
+      Some(Token::Str(s)) => Ok(Expr::Literal(FlowValue::String(s))),
+      Some(Token::Ident(name)) => match name.as_str() {
+        "true" => Ok(Expr::Literal(FlowValue::Bool(true))),
+        "false" => Ok(Expr::Literal(FlowValue::Bool(false))),
+        "null" | "undefined" => Ok(Expr::Literal(FlowValue::Null)),
+        _ => {
+          if self.eat_punct("(") {
+            let args = self.list(")")?;
+            Ok(Expr::Call(name, args))
+          } else {
+            Ok(Expr::Ident(name))
+          }
+        }
+      },
+      Some(Token::Punct("(")) => {
+        let expr = self.expr()?;
+        self.expect_punct(")")?;
+        Ok(expr)
+      }
+      Some(Token::Punct("[")) => Ok(Expr::List(self.list("]")?)),
+      other => Err(flow_error(format!("flow: unexpected token {other:?}"))),
+    }
+  }
+  fn list(&mut self, close: &str) -> Result<Vec<Expr>> {
+    let mut items = Vec::new();
+    while !self.eat_punct(close) {
+      if !items.is_empty() {
+        self.expect_punct(",")?;
+      }
+      items.push(self.expr()?);
+    }
+    Ok(items)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Default)]
+  struct Recorder {
+    matched: Vec<(FlowProtocol, usize)>,
+    calls: Vec<String>,
+  }
+
+  impl FlowExecutor for Recorder {
+    async fn call(
+      &mut self,
+      protocol: FlowProtocol,
+      selector: FlowSelector,
+      variables: &mut FlowVariables,
+    ) -> bool {
+      let index = match &selector {
+        FlowSelector::Index(i) => *i,
+        _ => 0,
+      };
+      self.calls.push(format!("{protocol}({index})"));
+      if index == 0 {
+        variables.insert(
+          "paths".to_string(),
+          ["/admin", "/login"].into_iter().collect(),
+        );
+      }
+      self.matched.contains(&(protocol, index))
+    }
+  }
+
+  fn run(source: &str, matched: Vec<(FlowProtocol, usize)>) -> (bool, Vec<String>) {
+    let flow = Flow::parse(source).unwrap();
+    let mut recorder = Recorder {
+      matched,
+      calls: Vec::new(),
+    };
+    let result = block_on(flow.execute(&mut recorder, FlowVariables::new()));
+    (result, recorder.calls)
+  }
+
+  // 测试里不引入异步运行时，flow本身不会挂起，轮询一次就能完成
+  fn block_on<F: Future>(future: F) -> F::Output {
+    use std::task::{Context, Poll, Waker};
+    let mut future = std::pin::pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+      if let Poll::Ready(v) = future.as_mut().poll(&mut cx) {
+        return v;
+      }
+    }
+  }
+
+  #[test]
+  fn short_circuit() {
+    let (result, calls) = run("http(0) && http(1)", vec![]);
+    assert!(!result);
+    assert_eq!(calls, vec!["http(0)"]);
+    let (result, calls) = run("http(0) && http(1)", vec![(FlowProtocol::Http, 0)]);
+    assert!(!result);
+    assert_eq!(calls, vec!["http(0)", "http(1)"]);
+    let (result, _) = run(
+      "http(0) || tcp(1)",
+      vec![(FlowProtocol::Http, 0), (FlowProtocol::Tcp, 1)],
+    );
+    assert!(result);
+  }
+
+  #[test]
+  fn loop_over_extracted_variables() {
+    let source = r#"
+      http(0)
+      for (let path of iterate(template["paths"])) {
+        set("path", path)
+        http(1)
+      }
+    "#;
+    let (result, calls) = run(source, vec![(FlowProtocol::Http, 1)]);
+    assert!(result);
+    assert_eq!(calls, vec!["http(0)", "http(1)", "http(1)"]);
+    let (_, calls) = run("http(0)\nfor p in paths { tcp(2) }", vec![]);
+    assert_eq!(calls, vec!["http(0)", "tcp(2)", "tcp(2)"]);
+  }
+
+  #[test]
+  fn if_else_and_return() {
+    let source = "if (http(0)) { return http(1) } else { code(0) }";
+    let (result, calls) = run(source, vec![]);
+    assert!(!result);
+    assert_eq!(calls, vec!["http(0)", "code(0)"]);
+    let (result, calls) = run(
+      source,
+      vec![(FlowProtocol::Http, 0), (FlowProtocol::Http, 1)],
+    );
+    assert!(result);
+    assert_eq!(calls, vec!["http(0)", "http(1)"]);
+  }
+
+  #[test]
+  fn parse_error() {
+    assert!(Flow::parse("http(0) &&").is_err());
+    assert!(Flow::parse("if (http(0) { }").is_err());
+    assert!(Flow::parse("http(0) # 1").is_err());
+  }
+}
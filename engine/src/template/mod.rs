@@ -3,11 +3,15 @@ use crate::info::Info;
 use crate::operators::matchers::MatcherType;
 use crate::request::{HttpRaw, Requests};
 use crate::serde_format::is_default;
+use crate::template::flow::Flow;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::sync::Arc;
 
 pub mod cluster;
+pub mod flow;
+pub mod nmap;
+pub mod wappalyzer;
 /// Template is a YAML input file which defines all the requests and
 /// other metadata for a template.
 #[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
@@ -71,6 +75,9 @@ pub struct Template {
     )
   )]
   pub flow: Option<String>,
+  /// 编译后的flow，在 `compile` 时生成
+  #[serde(skip)]
+  pub compiled_flow: Option<Arc<Flow>>,
   #[serde(flatten)]
   pub requests: Arc<Requests>,
   /// description: |
@@ -109,6 +116,9 @@ pub struct Template {
 
 impl Template {
   pub fn compile(&mut self) -> Result<()> {
+    if let Some(flow) = &self.flow {
+      self.compiled_flow = Some(Arc::new(Flow::parse(flow)?));
+    }
     let requests = Arc::make_mut(&mut self.requests);
     for http in requests.http.iter_mut() {
       let mutable_http = Arc::make_mut(http);
@@ -120,6 +130,26 @@ impl Template {
       let mutable_operators = Arc::make_mut(&mut mutable_tcp.operators);
       mutable_operators.compile().map_err(new_regex_error)?;
     }
+    for udp in requests.udp.iter_mut() {
+      let mutable_udp = Arc::make_mut(udp);
+      let mutable_operators = Arc::make_mut(&mut mutable_udp.operators);
+      mutable_operators.compile().map_err(new_regex_error)?;
+    }
+    for ssl in requests.ssl.iter_mut() {
+      let mutable_ssl = Arc::make_mut(ssl);
+      let mutable_operators = Arc::make_mut(&mut mutable_ssl.operators);
+      mutable_operators.compile().map_err(new_regex_error)?;
+    }
+    for dns in requests.dns.iter_mut() {
+      let mutable_dns = Arc::make_mut(dns);
+      let mutable_operators = Arc::make_mut(&mut mutable_dns.operators);
+      mutable_operators.compile().map_err(new_regex_error)?;
+    }
+    for websocket in requests.websocket.iter_mut() {
+      let mutable_websocket = Arc::make_mut(websocket);
+      let mutable_operators = Arc::make_mut(&mut mutable_websocket.operators);
+      mutable_operators.compile().map_err(new_regex_error)?;
+    }
     #[cfg(feature = "code")]
     {
       for code in requests.code.iter_mut() {
@@ -136,6 +166,16 @@ impl Template {
     }
     Ok(())
   }
+  // 有flow、变量或者关联匹配的模板请求和其他模板不一样，不能合并
+  pub fn can_cluster(&self) -> bool {
+    self.compiled_flow.is_none()
+      && self.variables.is_empty()
+      && !self
+        .requests
+        .http
+        .iter()
+        .any(|http| http.http_option.req_condition)
+  }
   pub fn find_favicon(&mut self) -> Option<Template> {
     let mut new_template = self.clone();
     let new_requests = Arc::make_mut(&mut new_template.requests);
@@ -0,0 +1,383 @@
+use crate::error::{Error, Result};
+use crate::request::PortRange;
+use crate::template::Template;
+use log::debug;
+use serde_json::{Map, Value, json};
+use std::str::FromStr;
+
+// nmap-service-probes 中的一个探针
+#[derive(Debug, Default)]
+struct Probe {
+  protocol: String,
+  name: String,
+  data: String,
+  ports: Option<String>,
+  ssl_ports: Option<String>,
+  rarity: Option<u32>,
+  wait: Option<u64>,
+  fallback: Vec<String>,
+  matches: Vec<ServiceMatch>,
+}
+
+// match 或者 softmatch 指令
+#[derive(Debug, Default)]
+struct ServiceMatch {
+  service: String,
+  pattern: String,
+  soft: bool,
+  fields: Vec<(&'static str, String)>,
+  cpe: Vec<String>,
+}
+
+/// 将 nmap-service-probes 文件内容转换为服务指纹模板
+///
+/// 每一条 `match`/`softmatch` 生成一个模板，请求名称是小写的探针名称，
+/// 同一个探针的模板在聚类时会合并成一个请求。`p/ v/ i/ h/ o/ d/ cpe:/` 保存到
+/// `info.metadata`，匹配后通过 [`crate::info::Version`] 替换 `$1` 这样的分组。
+/// 无法解析或者正则无法编译的指令会被跳过
+pub fn parse_service_probes(content: &str) -> Result<Vec<Template>> {
+  let mut probes: Vec<Probe> = Vec::new();
+  for line in content.lines() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let (directive, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let rest = rest.trim();
+    if directive == "Probe" {
+      if let Some(probe) = parse_probe(rest) {
+        probes.push(probe);
+      } else {
+        debug!("invalid nmap probe: {line}");
+      }
+      continue;
+    }
+    // Exclude 这类全局指令和不认识的指令直接忽略
+    let Some(probe) = probes.last_mut() else {
+      continue;
+    };
+    match directive {
+      "match" | "softmatch" => match parse_match(rest, directive == "softmatch") {
+        Some(m) => probe.matches.push(m),
+        None => debug!("invalid nmap match: {line}"),
+      },
+      "ports" => probe.ports = Some(rest.to_string()),
+      "sslports" => probe.ssl_ports = Some(rest.to_string()),
+      "rarity" => probe.rarity = rest.parse().ok(),
+      "totalwaitms" => probe.wait = rest.parse().ok(),
+      "fallback" => {
+        probe.fallback = rest
+          .split(',')
+          .map(|name| name.trim().to_lowercase())
+          .filter(|name| !name.is_empty())
+          .collect()
+      }
+      _ => {}
+    }
+  }
+  let mut templates = Vec::new();
+  for probe in probes.iter() {
+    for (index, m) in probe.matches.iter().enumerate() {
+      templates.push(probe_template(probe, index, m)?);
+    }
+  }
+  Ok(templates)
+}
+
+// Probe <protocol> <probename> <probestring> [no-payload]
+fn parse_probe(rest: &str) -> Option<Probe> {
+  let (protocol, rest) = rest.split_once(' ')?;
+  let (name, rest) = rest.trim_start().split_once(' ')?;
+  let rest = rest.trim_start().strip_prefix('q')?;
+  let (data, _) = take_delimited(rest)?;
+  let protocol = protocol.to_lowercase();
+  if protocol != "tcp" && protocol != "udp" {
+    return None;
+  }
+  Some(Probe {
+    protocol,
+    name: name.to_lowercase(),
+    data: probe_data(data),
+    ..Default::default()
+  })
+}
+
+// match <service> <pattern> [<versioninfo>]
+fn parse_match(rest: &str, soft: bool) -> Option<ServiceMatch> {
+  let (service, rest) = rest.split_once(' ')?;
+  let rest = rest.trim_start().strip_prefix('m')?;
+  let (pattern, rest) = take_delimited(rest)?;
+  let (flags, mut rest) = rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len()));
+  let mut m = ServiceMatch {
+    service: service.to_string(),
+    pattern: match_regex(pattern, flags),
+    soft,
+    ..Default::default()
+  };
+  // 校验一下正则，避免加载模板的时候报错
+  if let Err(err) = fancy_regex::Regex::new(&m.pattern) {
+    debug!("nmap match {service} regex error: {err}");
+    return None;
+  }
+  loop {
+    rest = rest.trim_start();
+    if rest.is_empty() {
+      break;
+    }
+    let (key, value_rest) = if let Some(r) = rest.strip_prefix("cpe:") {
+      ("cpe", r)
+    } else {
+      let mut chars = rest.chars();
+      let key = match chars.next()? {
+        'p' => "product_name",
+        'v' => "version",
+        'i' => "info",
+        'h' => "hostname",
+        'o' => "operating_system",
+        'd' => "device_type",
+        _ => "",
+      };
+      (key, chars.as_str())
+    };
+    let (value, r) = take_delimited(value_rest)?;
+    // 跳过字段后面的标记，例如 cpe 的 `a`
+    rest = &r[r.find(char::is_whitespace).unwrap_or(r.len())..];
+    let value = version_value(value);
+    match key {
+      "cpe" => m.cpe.push(format!("cpe:/{value}")),
+      "" => {}
+      key => m.fields.push((key, value)),
+    }
+  }
+  Some(m)
+}
+
+// 第一个字符是分隔符，返回分隔符之间的内容和剩余部分
+fn take_delimited(s: &str) -> Option<(&str, &str)> {
+  let delimiter = s.chars().next()?;
+  let s = &s[delimiter.len_utf8()..];
+  let end = s.find(delimiter)?;
+  Some((&s[..end], &s[end + delimiter.len_utf8()..]))
+}
+
+// 探针的转义和C语言一样，转换成Input支持的转义格式
+fn probe_data(data: &str) -> String {
+  let mut out = String::with_capacity(data.len());
+  let mut chars = data.chars();
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      out.push(c);
+      continue;
+    }
+    match chars.next() {
+      Some('a') => out.push_str("\\x07"),
+      Some('b') => out.push_str("\\x08"),
+      Some('f') => out.push_str("\\x0c"),
+      Some('v') => out.push_str("\\x0b"),
+      Some(c) => {
+        out.push('\\');
+        out.push(c);
+      }
+      None => out.push_str("\\\\"),
+    }
+  }
+  out
+}
+
+// nmap使用的是PCRE，`\0`需要转换成`\x00`，`s`和`i`标记转换为内联标记
+fn match_regex(pattern: &str, flags: &str) -> String {
+  let mut out = String::with_capacity(pattern.len() + 6);
+  let flags: String = flags.chars().filter(|c| matches!(c, 'i' | 's')).collect();
+  if !flags.is_empty() {
+    out.push_str(&format!("(?{flags})"));
+  }
+  let mut chars = pattern.chars().peekable();
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      out.push(c);
+      continue;
+    }
+    match chars.next() {
+      Some('0') if !chars.peek().is_some_and(|c| c.is_ascii_digit()) => out.push_str("\\x00"),
+      Some(c) => {
+        out.push('\\');
+        out.push(c);
+      }
+      None => out.push_str("\\\\"),
+    }
+  }
+  out
+}
+
+// `$P(1)`、`$I(1,">")`、`$SUBST(1,"_",".")` 这些辅助函数只保留分组 `$1`
+fn version_value(value: &str) -> String {
+  let mut out = String::with_capacity(value.len());
+  let mut rest = value;
+  while let Some(start) = rest.find('$') {
+    out.push_str(&rest[..start]);
+    let tail = &rest[start + 1..];
+    let name_len = tail
+      .find(|c: char| !c.is_ascii_uppercase())
+      .unwrap_or(tail.len());
+    if name_len > 0 && tail[name_len..].starts_with('(') {
+      let args = &tail[name_len + 1..];
+      if let Some(end) = args.find(')') {
+        let group: String = args.chars().take_while(|c| c.is_ascii_digit()).collect();
+        out.push('$');
+        out.push_str(&group);
+        rest = &args[end + 1..];
+        continue;
+      }
+    }
+    out.push('$');
+    rest = tail;
+  }
+  out.push_str(rest);
+  out
+}
+
+fn probe_template(probe: &Probe, index: usize, m: &ServiceMatch) -> Result<Template> {
+  let mut metadata = Map::new();
+  for (key, value) in m.fields.iter() {
+    metadata.insert(key.to_string(), json!(value));
+  }
+  if !m.cpe.is_empty() {
+    metadata.insert("cpe".to_string(), json!(m.cpe));
+  }
+  if let Some(rarity) = probe.rarity {
+    metadata.insert("rarity".to_string(), json!(rarity));
+  }
+  if m.soft {
+    metadata.insert("softmatch".to_string(), json!(true));
+  }
+  let mut request = json!({
+    "name": probe.name,
+    "inputs": [{"data": probe.data}],
+    "matchers": [{"type": "regex", "regex": [m.pattern]}],
+    // 提取器只用于替换版本信息中的分组
+    "extractors": [{"type": "regex", "regex": [m.pattern], "internal": true}],
+  });
+  for (key, ports) in [("port", &probe.ports), ("ssl-port", &probe.ssl_ports)] {
+    // 端口格式不对的时候不限制端口
+    if let Some(ports) = ports.as_deref().filter(|p| PortRange::from_str(p).is_ok()) {
+      request[key] = Value::String(ports.to_string());
+    }
+  }
+  if let Some(wait) = probe.wait {
+    request["wait"] = json!(wait);
+  }
+  if probe.protocol == "tcp" {
+    if !probe.fallback.is_empty() {
+      request["fallback"] = json!(probe.fallback);
+    }
+  } else if let Some(request) = request.as_object_mut() {
+    request.remove("ssl-port");
+  }
+  let mut template = json!({
+    "id": format!("nmap-{}-{}-{}", probe.protocol, probe.name, index),
+    "info": {
+      "name": m.service,
+      "author": "nmap",
+      "tags": format!("nmap,{}", m.service),
+      "severity": "info",
+      "metadata": metadata,
+    },
+  });
+  template[probe.protocol.as_str()] = json!([request]);
+  serde_json::from_value(template).map_err(|err| Error::IO(err.into()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use slinger::Response;
+  use std::time::Duration;
+
+  const PROBES: &str = r#"
+# Nmap service detection probe list
+Exclude T:9100-9107
+Probe TCP NULL q||
+totalwaitms 6000
+match ftp m|^220 ProFTPD (\d\S+) Server| p/ProFTPD/ v/$1/ cpe:/a:proftpd:proftpd:$1/a
+match ssh m|^SSH-([\d.]+)-OpenSSH_([\w._-]+)\r?\n|i p/OpenSSH/ v/$P(2)/ i/protocol $1/ cpe:/a:openbsd:openssh:$2/
+softmatch ftp m|^220[- ]|
+match broken m|^(unclosed|
+Probe TCP GetRequest q|GET / HTTP/1.0\r\n\r\n|
+rarity 1
+fallback NULL
+ports 80,8000-8010
+sslports 443
+match http m|^HTTP/1\.[01] \d\d\d.*\r\nServer: nginx/([\d.]+)|s p/nginx/ v/$1/ cpe:/a:igor_sysoev:nginx:$1/
+Probe UDP DNSStatusRequest q|\0\0\x10\0\0\0\0\0\0\0\0\0|
+rarity 2
+totalwaitms 3000
+ports 53
+match dns m|^\0\0\x90\x04\0\0\0\0\0\0\0\0| p/generic dns response: NOTIMP/
+"#;
+
+  #[test]
+  fn nmap_service_probes() {
+    let templates = parse_service_probes(PROBES).unwrap();
+    assert_eq!(templates.len(), 5);
+    let ssh = &templates[1];
+    assert_eq!(ssh.id, "nmap-tcp-null-1");
+    assert!(ssh.requests.is_tcp_default());
+    let version = ssh.info.get_version().unwrap();
+    assert_eq!(version.version.as_deref(), Some("$2"));
+    assert_eq!(version.cpe, vec!["cpe:/a:openbsd:openssh:$2"]);
+    assert!(!ssh.info.is_softmatch());
+    assert!(templates[2].info.is_softmatch());
+    assert_eq!(
+      ssh.requests.is_tcp().unwrap().wait(),
+      Some(Duration::from_secs(6))
+    );
+    let http = templates[3].requests.is_tcp().unwrap();
+    assert_eq!(http.name.as_deref(), Some("getrequest"));
+    assert!(http.port.as_ref().is_some_and(|p| p.contains(8005)));
+    assert!(http.ssl_port.as_ref().is_some_and(|p| p.contains(443)));
+    assert_eq!(http.fallback, vec!["null"]);
+    assert_eq!(http.wait(), None);
+    assert_eq!(
+      http.inputs[0].data(&Default::default()),
+      b"GET / HTTP/1.0\r\n\r\n"
+    );
+    assert_eq!(templates[3].info.get_rarity(), Some(1));
+    let dns = templates[4].requests.is_udp().unwrap();
+    assert_eq!(dns.wait(), Duration::from_secs(3));
+    assert_eq!(
+      dns.inputs[0].data(&Default::default()),
+      b"\0\0\x10\0\0\0\0\0\0\0\0\0"
+    );
+  }
+
+  #[test]
+  fn match_nmap_version() {
+    let mut templates = parse_service_probes(PROBES).unwrap();
+    let banner = "SSH-2.0-OpenSSH_8.9p1\r\n";
+    let ssh = &mut templates[1];
+    ssh.compile().unwrap();
+    let operators = ssh.requests.operators();
+    let response: Response = slinger::http::Response::builder()
+      .body(banner.as_bytes().to_vec())
+      .unwrap()
+      .into();
+    let mut result = Default::default();
+    operators[0].matcher(&response, &mut result).unwrap();
+    operators[0].extractor(ssh.info.get_version(), &response, &mut result);
+    assert!(result.is_matched());
+    let extract = result.extract_result();
+    assert!(extract["version"].contains("8.9p1"));
+    assert!(extract["info"].contains("protocol 2.0"));
+    assert!(extract["cpe"].contains("cpe:/a:openbsd:openssh:8.9p1"));
+  }
+
+  #[test]
+  fn nmap_helpers() {
+    assert_eq!(
+      version_value(r#"$SUBST(1,"_",".") on $I(2,">")"#),
+      "$1 on $2"
+    );
+    assert_eq!(match_regex(r"^\0\x01\\0", "si"), r"(?si)^\x00\x01\\0");
+    assert_eq!(probe_data(r"\a\0\r\n"), r"\x07\0\r\n");
+  }
+}
@@ -0,0 +1,569 @@
+use crate::error::{Error, Result};
+use crate::template::Template;
+use log::debug;
+use serde_json::{Map, Value, json};
+
+// 属性值中规则前面的任意字符
+const ATTRIBUTE_ANY: &str = r#"[^"'>]*?"#;
+
+// 技术的一个匹配规则，例如 `nginx(?:/([\d.]+))?\;version:\1`
+#[derive(Debug, Default, PartialEq)]
+struct Pattern {
+  regex: String,
+  version: Option<String>,
+  confidence: Option<u32>,
+}
+
+/// 将 Wappalyzer/webappanalyzer 的 `technologies/*.json` 文件内容转换为web指纹模板
+///
+/// 每个技术生成一个请求首页的模板，模板ID是小写的技术名称。`headers`、`cookies`、`meta`、
+/// `html`、`scriptSrc`、`scripts`、`url` 转换为正则匹配器，需要执行js的
+/// `js`、`dom` 不支持。`\;version:\1` 通过内部的正则提取器替换版本信息，`\;confidence:50`
+/// 按匹配器名称保存到 `info.metadata` 的 `confidence`，`cats`、`implies`、`excludes`、
+/// `requires` 也保存到 `info.metadata`。没有可用规则的技术和无法编译的正则会被跳过
+pub fn parse_technologies(content: &str) -> Result<Vec<Template>> {
+  let technologies: Map<String, Value> =
+    serde_json::from_str(content).map_err(|err| Error::IO(err.into()))?;
+  let mut templates = Vec::new();
+  for (name, technology) in technologies.iter() {
+    let Some(technology) = technology.as_object() else {
+      continue;
+    };
+    if let Some(template) = technology_template(name, technology)? {
+      templates.push(template);
+    }
+  }
+  Ok(templates)
+}
+
+/// 技术名称转换为模板ID，例如 `Next.js` 转换为 `next-js`
+pub fn technology_id(name: &str) -> String {
+  let mut id = String::with_capacity(name.len());
+  for c in name.chars() {
+    if c.is_ascii_alphanumeric() {
+      id.push(c.to_ascii_lowercase());
+    } else if !id.is_empty() && !id.ends_with('-') {
+      id.push('-');
+    }
+  }
+  id.trim_end_matches('-').to_string()
+}
+
+// 模式后面用 `\;` 分隔的修饰
+fn parse_pattern(pattern: &str) -> Pattern {
+  let mut parts = pattern.split("\\;");
+  let mut p = Pattern {
+    regex: parts.next().unwrap_or_default().to_string(),
+    ..Default::default()
+  };
+  for part in parts {
+    match part.split_once(':') {
+      Some(("version", version)) => p.version = version_value(version),
+      Some(("confidence", confidence)) => p.confidence = confidence.trim().parse().ok(),
+      _ => {}
+    }
+  }
+  p
+}
+
+// `\1` 转换为 `$1`，三元表达式 `\1?a:b` 不支持
+fn version_value(version: &str) -> Option<String> {
+  if version.is_empty() || version.contains('?') {
+    return None;
+  }
+  let mut out = String::with_capacity(version.len());
+  let mut chars = version.chars().peekable();
+  while let Some(c) = chars.next() {
+    if c == '\\' && chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+      out.push('$');
+    } else {
+      out.push(c);
+    }
+  }
+  Some(out)
+}
+
+// 只引用一个分组的版本，例如 `$2`
+fn version_group(version: &str) -> Option<usize> {
+  version.strip_prefix('$')?.parse().ok().filter(|g| *g > 0)
+}
+
+// 把第 `from` 个捕获分组调整为第 `to` 个，前面的捕获分组改为非捕获分组，再补上空的分组
+fn renumber_group(regex: &str, from: usize, to: usize) -> String {
+  let mut out = "()".repeat(to - 1);
+  let mut group = 0;
+  let mut in_class = false;
+  let mut chars = regex.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => {
+        out.push(c);
+        if let Some(next) = chars.next() {
+          out.push(next);
+        }
+        continue;
+      }
+      '[' if !in_class => in_class = true,
+      ']' if in_class => in_class = false,
+      '(' if !in_class => {
+        let rest: String = chars.clone().take(3).collect();
+        // 命名分组也是捕获分组，后顾不是
+        let named = rest.starts_with("?P<")
+          || (rest.starts_with("?<") && !rest.starts_with("?<=") && !rest.starts_with("?<!"));
+        if !rest.starts_with('?') || named {
+          group += 1;
+          if group < from {
+            out.push_str("(?:");
+            if named {
+              for c in chars.by_ref() {
+                if c == '>' {
+                  break;
+                }
+              }
+            }
+            continue;
+          }
+        }
+      }
+      _ => {}
+    }
+    out.push(c);
+  }
+  out
+}
+
+// 字符串或者字符串数组
+fn patterns(value: &Value) -> Vec<Pattern> {
+  match value {
+    Value::String(s) => vec![parse_pattern(s)],
+    Value::Array(values) => values
+      .iter()
+      .filter_map(|v| v.as_str())
+      .map(parse_pattern)
+      .collect(),
+    _ => Vec::new(),
+  }
+}
+
+// 依赖的技术名称去掉修饰后转换为模板ID
+fn technology_ids(value: Option<&Value>) -> Vec<String> {
+  value
+    .map(patterns)
+    .unwrap_or_default()
+    .iter()
+    .map(|p| technology_id(&p.regex))
+    .filter(|id| !id.is_empty())
+    .collect()
+}
+
+// 属性值的规则，`^` 开头的规则从属性值的开头匹配，否则跳过属性值前面的任意字符
+fn attribute_regex(prefix: &str, any: &str, regex: &str) -> String {
+  match regex.strip_prefix('^') {
+    Some(regex) => format!("(?i){prefix}(?:{regex})"),
+    None => format!("(?i){prefix}{any}(?:{regex})"),
+  }
+}
+
+// 匹配器和对应的版本提取器
+#[derive(Default)]
+struct Rules {
+  matchers: Vec<Value>,
+  extractors: Vec<Value>,
+  confidence: Map<String, Value>,
+  version: Option<String>,
+}
+
+impl Rules {
+  fn push(&mut self, name: String, part: &str, regex: String, pattern: &Pattern) {
+    // 校验一下正则，避免加载模板的时候报错
+    if let Err(err) = fancy_regex::Regex::new(&regex) {
+      debug!("wappalyzer {name} regex error: {err}");
+      return;
+    }
+    if let Some(version) = &pattern.version {
+      // 模板只有一个版本信息，只引用一个分组的版本调整分组的序号后和模板的版本一致
+      let template_version = self.version.get_or_insert_with(|| version.clone());
+      let regex = if template_version == version {
+        Some(regex.clone())
+      } else {
+        match (version_group(version), version_group(template_version)) {
+          (Some(from), Some(to)) => Some(renumber_group(&regex, from, to)),
+          _ => None,
+        }
+      };
+      match regex {
+        Some(regex) => self.extractors.push(json!({
+          "type": "regex",
+          "part": part,
+          "regex": [regex],
+          "internal": true,
+        })),
+        None => {
+          debug!("wappalyzer {name} version {version} differs from {template_version}, skipped")
+        }
+      }
+    }
+    if let Some(confidence) = pattern.confidence.filter(|c| *c < 100) {
+      self.confidence.insert(name.clone(), json!(confidence));
+    }
+    self.matchers.push(json!({
+      "type": "regex",
+      "name": name,
+      "part": part,
+      "regex": [regex],
+    }));
+  }
+}
+
+fn technology_template(name: &str, technology: &Map<String, Value>) -> Result<Option<Template>> {
+  let id = technology_id(name);
+  if id.is_empty() {
+    return Ok(None);
+  }
+  let mut rules = Rules::default();
+  if let Some(headers) = technology.get("headers").and_then(|v| v.as_object()) {
+    for (header, value) in headers.iter() {
+      let header = header.to_lowercase();
+      for pattern in patterns(value) {
+        let name = format!("header-{}", technology_id(&header));
+        // 没有规则的只需要存在这个响应头，缺少的响应头会按空字符串匹配
+        if pattern.regex.is_empty() {
+          let regex = format!("(?im)^{}:", regex::escape(&header));
+          rules.push(name, "header", regex, &pattern);
+        } else {
+          rules.push(name, &header, format!("(?i){}", pattern.regex), &pattern);
+        }
+      }
+    }
+  }
+  if let Some(cookies) = technology.get("cookies").and_then(|v| v.as_object()) {
+    for (cookie, value) in cookies.iter() {
+      for pattern in patterns(value) {
+        let name = format!("cookie-{}", technology_id(cookie));
+        // 多个set-cookie响应头使用逗号连接
+        let prefix = format!("(?:^|, ){}=", regex::escape(cookie));
+        let regex = if pattern.regex.is_empty() {
+          format!("(?i){prefix}")
+        } else {
+          attribute_regex(&prefix, "[^;]*?", &pattern.regex)
+        };
+        rules.push(name, "set-cookie", regex, &pattern);
+      }
+    }
+  }
+  if let Some(meta) = technology.get("meta").and_then(|v| v.as_object()) {
+    for (meta_name, value) in meta.iter() {
+      for pattern in patterns(value) {
+        let name = format!("meta-{}", technology_id(meta_name));
+        // name和content的顺序不固定，使用前瞻判断name
+        let prefix = format!(
+          r#"<meta(?=[^>]*\b(?:name|property)=["']?{}["'\s/>])[^>]*?\bcontent=["']?"#,
+          regex::escape(meta_name)
+        );
+        let regex = attribute_regex(&prefix, ATTRIBUTE_ANY, &pattern.regex);
+        rules.push(name, "body", regex, &pattern);
+      }
+    }
+  }
+  for (key, prefix) in [
+    ("html", None),
+    ("scripts", None),
+    ("scriptSrc", Some(r#"<script[^>]+\bsrc=["']?"#)),
+  ] {
+    for (index, pattern) in technology
+      .get(key)
+      .map(patterns)
+      .unwrap_or_default()
+      .iter()
+      .enumerate()
+    {
+      // 空的规则会匹配所有页面
+      if pattern.regex.is_empty() {
+        continue;
+      }
+      let name = format!("{}-{index}", technology_id(key));
+      let regex = match prefix {
+        Some(prefix) => attribute_regex(prefix, ATTRIBUTE_ANY, &pattern.regex),
+        None => format!("(?i){}", pattern.regex),
+      };
+      rules.push(name, "body", regex, pattern);
+    }
+  }
+  for (index, pattern) in technology
+    .get("url")
+    .map(patterns)
+    .unwrap_or_default()
+    .iter()
+    .enumerate()
+  {
+    if !pattern.regex.is_empty() {
+      let regex = format!("(?i){}", pattern.regex);
+      rules.push(format!("url-{index}"), "url", regex, pattern);
+    }
+  }
+  if rules.matchers.is_empty() {
+    debug!("wappalyzer {name} has no supported patterns");
+    return Ok(None);
+  }
+  let mut metadata = Map::new();
+  if let Some(version) = &rules.version {
+    metadata.insert("product_name".to_string(), json!(name));
+    metadata.insert("version".to_string(), json!(version));
+  }
+  if let Some(cpe) = technology.get("cpe").and_then(|v| v.as_str()) {
+    let mut parts: Vec<&str> = cpe.split(':').collect();
+    if let (Some(vendor), Some(product)) = (parts.get(3), parts.get(4)) {
+      metadata.insert("vendor".to_string(), json!(vendor));
+      metadata.insert("product".to_string(), json!(product));
+    }
+    // cpe 2.3 的第6段是版本
+    if let Some(version) = &rules.version
+      && parts.len() > 5
+    {
+      parts[5] = version;
+    }
+    metadata.insert("cpe".to_string(), json!([parts.join(":")]));
+  }
+  if let Some(cats) = technology.get("cats").filter(|v| v.is_array()) {
+    metadata.insert("cats".to_string(), cats.clone());
+  }
+  for key in ["implies", "excludes", "requires"] {
+    let ids = technology_ids(technology.get(key));
+    if !ids.is_empty() {
+      metadata.insert(key.to_string(), json!(ids));
+    }
+  }
+  if !rules.confidence.is_empty() {
+    metadata.insert("confidence".to_string(), Value::Object(rules.confidence));
+  }
+  let mut info = json!({
+    "name": name,
+    "author": "wappalyzer",
+    "tags": "wappalyzer",
+    "severity": "info",
+    "metadata": metadata,
+  });
+  if let Some(description) = technology.get("description").and_then(|v| v.as_str()) {
+    info["description"] = json!(description);
+  }
+  if let Some(website) = technology.get("website").and_then(|v| v.as_str()) {
+    info["reference"] = json!([website]);
+  }
+  let mut request = json!({
+    "method": "GET",
+    "path": ["{{BaseURL}}/"],
+    "matchers": rules.matchers,
+  });
+  if !rules.extractors.is_empty() {
+    request["extractors"] = json!(rules.extractors);
+  }
+  let template = json!({
+    "id": id,
+    "info": info,
+    "http": [request],
+  });
+  serde_json::from_value(template)
+    .map(Some)
+    .map_err(|err| Error::IO(err.into()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::serde_format::Value as MetaValue;
+  use slinger::Response;
+
+  const TECHNOLOGIES: &str = r#"{
+  "Nginx": {
+    "cats": [22],
+    "headers": {"Server": "nginx(?:/([\\d.]+))?\\;version:\\1"},
+    "cpe": "cpe:2.3:a:f5:nginx:*:*:*:*:*:*:*:*",
+    "website": "http://nginx.org/en"
+  },
+  "WordPress": {
+    "cats": [1, 11],
+    "cookies": {"wordpress_test_cookie": ""},
+    "meta": {"generator": "^WordPress ?([\\d.]+)?\\;version:\\1"},
+    "html": "<link rel=[\"']stylesheet[\"'] [^>]+/wp-(?:content|includes)/\\;confidence:50",
+    "scriptSrc": "/wp-(?:content|includes)/",
+    "implies": ["PHP", "MySQL\\;confidence:50"]
+  },
+  "Next.js": {
+    "headers": {"X-Powered-By": "^Next\\.js ?([0-9.]+)?\\;version:\\1"},
+    "url": ["^https?://[^/]+\\.vercel\\.app", "^https?://(?!www\\.)[^/]+\\.now\\.sh/(?:'|\"|\\n)?"],
+    "implies": "React"
+  },
+  "jQuery": {
+    "js": {"jQuery.fn.jquery": "([\\d.]+)\\;version:\\1"}
+  },
+  "Ghost": {
+    "meta": {"generator": "^Ghost ([\\d.]+)\\;version:\\1"},
+    "html": "<link[^>]+ghost-(portal|search)@([\\d.]+)\\;version:\\2"
+  }
+}"#;
+
+  fn response(headers: &[(&str, &str)], body: &str) -> Response {
+    let mut builder = slinger::http::Response::builder();
+    for (name, value) in headers {
+      builder = builder.header(*name, *value);
+    }
+    builder.body(body.as_bytes().to_vec()).unwrap().into()
+  }
+
+  fn find(templates: &[Template], id: &str) -> Template {
+    templates.iter().find(|t| t.id == id).unwrap().clone()
+  }
+
+  #[test]
+  fn wappalyzer_technologies() {
+    let templates = parse_technologies(TECHNOLOGIES).unwrap();
+    // 只有js规则的jQuery不支持
+    assert_eq!(templates.len(), 4);
+    let wordpress = find(&templates, "wordpress");
+    assert_eq!(wordpress.info.name, "WordPress");
+    assert!(wordpress.requests.is_web_default());
+    let metadata = &wordpress.info.metadata;
+    assert_eq!(metadata["implies"].to_vec(), vec!["php", "mysql"]);
+    assert_eq!(metadata["cats"].to_vec(), vec!["1", "11"]);
+    let MetaValue::Map(confidence) = &metadata["confidence"] else {
+      panic!("confidence should be a map");
+    };
+    assert_eq!(confidence["html-0"], MetaValue::Num(50));
+    let nginx = find(&templates, "nginx");
+    let version = nginx.info.get_version().unwrap();
+    assert_eq!(version.version.as_deref(), Some("$1"));
+    assert_eq!(version.cpe, vec!["cpe:2.3:a:f5:nginx:$1:*:*:*:*:*:*:*"]);
+    let vpf = nginx.info.get_vpf().unwrap();
+    assert_eq!((vpf.vendor.as_str(), vpf.product.as_str()), ("f5", "nginx"));
+    assert_eq!(nginx.info.reference, vec!["http://nginx.org/en"]);
+    let next = find(&templates, "next-js");
+    assert_eq!(next.info.metadata["implies"].to_vec(), vec!["react"]);
+  }
+
+  #[test]
+  fn match_wappalyzer_version() {
+    let templates = parse_technologies(TECHNOLOGIES).unwrap();
+    let cases = [
+      (
+        "wordpress",
+        response(
+          &[],
+          r#"<meta content="WordPress 6.4.2" name="generator" />"#,
+        ),
+        Some("6.4.2"),
+      ),
+      (
+        "wordpress",
+        response(
+          &[
+            ("Set-Cookie", "a=1"),
+            ("Set-Cookie", "wordpress_test_cookie=WP+Cookie+check"),
+          ],
+          "",
+        ),
+        None,
+      ),
+      (
+        "wordpress",
+        response(
+          &[],
+          r#"<script src="https://example.com/wp-includes/js/jquery.js">"#,
+        ),
+        None,
+      ),
+      (
+        "nginx",
+        response(&[("Server", "nginx/1.25.3")], ""),
+        Some("1.25.3"),
+      ),
+      (
+        "next-js",
+        response(&[("X-Powered-By", "Next.js 14.1.0")], ""),
+        Some("14.1.0"),
+      ),
+      // 分组不一样的版本使用命名的提取器
+      (
+        "ghost",
+        response(&[], r#"<link href="/npm/ghost-portal@2.37.0/">"#),
+        Some("2.37.0"),
+      ),
+      (
+        "ghost",
+        response(&[], r#"<meta name="generator" content="Ghost 5.75">"#),
+        Some("5.75"),
+      ),
+    ];
+    for (id, response, version) in cases {
+      let mut template = find(&templates, id);
+      template.compile().unwrap();
+      let operators = template.requests.operators();
+      let mut result = Default::default();
+      operators[0].matcher(&response, &mut result).unwrap();
+      operators[0].extractor(template.info.get_version(), &response, &mut result);
+      assert!(result.is_matched(), "{id}");
+      let extract = result.extract_result();
+      assert_eq!(
+        extract
+          .get("version")
+          .and_then(|v| v.iter().next().cloned()),
+        version.map(|v| v.to_string()),
+        "{id}"
+      );
+    }
+    // 只匹配规则指定的响应头
+    let mut nginx = find(&templates, "nginx");
+    nginx.compile().unwrap();
+    let mut result = Default::default();
+    nginx.requests.operators()[0]
+      .matcher(&response(&[("Via", "1.1 nginx/1.25.3")], ""), &mut result)
+      .unwrap();
+    assert!(!result.is_matched());
+    // url规则使用请求的地址匹配
+    let mut next = find(&templates, "next-js");
+    next.compile().unwrap();
+    // 前瞻、引号和换行转义的规则也可以使用
+    assert_eq!(next.requests.operators()[0].matchers.len(), 3);
+    for (uri, matched) in [
+      ("https://demo.vercel.app/", true),
+      ("https://demo.now.sh/", true),
+      ("https://www.now.sh/", false),
+    ] {
+      let request: slinger::Request = slinger::http::Request::builder()
+        .uri(uri)
+        .body(Vec::new())
+        .unwrap()
+        .into();
+      let mut response = response(&[], "");
+      response.extensions_mut().insert(request);
+      let mut result = Default::default();
+      next.requests.operators()[0]
+        .matcher(&response, &mut result)
+        .unwrap();
+      assert_eq!(result.is_matched(), matched, "{uri}");
+    }
+  }
+
+  #[test]
+  fn wappalyzer_helpers() {
+    assert_eq!(technology_id("Next.js"), "next-js");
+    assert_eq!(technology_id("Microsoft ASP.NET"), "microsoft-asp-net");
+    assert_eq!(
+      parse_pattern(r"nginx/([\d.]+)\;version:\1\;confidence:75"),
+      Pattern {
+        regex: r"nginx/([\d.]+)".to_string(),
+        version: Some("$1".to_string()),
+        confidence: Some(75),
+      }
+    );
+    assert_eq!(version_value(r"\1?next:"), None);
+    assert_eq!(
+      renumber_group(r"(?i)(a|b)[(]\(x(?<n>y)(z)(?<=w)", 3, 1),
+      r"(?i)(?:a|b)[(]\(x(?:y)(z)(?<=w)"
+    );
+    assert_eq!(renumber_group(r"v(\d+)", 1, 2), r"()v(\d+)");
+    assert_eq!(
+      attribute_regex("src=", ATTRIBUTE_ANY, "^/static/"),
+      "(?i)src=(?:/static/)"
+    );
+  }
+}
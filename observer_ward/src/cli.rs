@@ -3,6 +3,7 @@ use crate::parse_yaml;
 use crate::tls::{FallbackHttpClient, TlsBackend, fallback_tls_connector};
 use argh::FromArgs;
 use console::Emoji;
+use engine::common::nvd::NvdDatabase;
 use engine::find_yaml_file;
 use engine::slinger::http::Uri;
 use engine::slinger::http::header::HeaderValue;
@@ -10,9 +11,11 @@ use engine::slinger::http_serde;
 use engine::slinger::redirect::Policy;
 use engine::slinger::{ClientBuilder, ConnectorBuilder, Proxy};
 use engine::template::Template;
+use engine::template::nmap::parse_service_probes;
+use engine::template::wappalyzer::parse_technologies;
 use log::{error, warn};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env::current_dir;
 use std::fmt::{Display, Formatter};
 use std::fs::File;
@@ -20,7 +23,7 @@ use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
 use std::str::FromStr;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, OnceLock, RwLock};
 use std::time::Duration;
 
 #[derive(Debug, Clone, Default)]
@@ -54,6 +57,7 @@ pub enum Mode {
   #[default]
   HTTP,
   TCP,
+  UDP,
   ALL,
 }
 
@@ -64,6 +68,7 @@ impl FromStr for Mode {
       "all" => Mode::ALL,
       "http" => Mode::HTTP,
       "tcp" => Mode::TCP,
+      "udp" => Mode::UDP,
       _ => {
         return Err(std::io::Error::new(
           std::io::ErrorKind::InvalidInput,
@@ -177,14 +182,30 @@ pub struct ObserverWardConfig {
   #[argh(option)]
   #[serde(skip)]
   pub probe_dir: Vec<PathBuf>,
+  /// nmap-service-probes file to load as service fingerprint
+  #[argh(option)]
+  #[serde(skip)]
+  pub nmap_probes: Option<PathBuf>,
+  /// wappalyzer technologies json file dir to load as web fingerprint
+  #[argh(option)]
+  #[serde(skip)]
+  pub wappalyzer_dir: Option<PathBuf>,
+  /// local NVD JSON 2.0 feed file or dir to correlate CVE offline
+  #[argh(option)]
+  #[serde(skip)]
+  pub nvd_feed: Option<PathBuf>,
   /// customized ua
   #[argh(option, default = "default_ua()")]
   #[serde(default = "default_ua")]
   pub ua: String,
-  /// mode probes option[tcp,http,all] default: all
+  /// mode probes option[tcp,http,udp,all] default: all
   #[argh(option)]
   #[serde(skip)]
   pub mode: Option<Mode>,
+  /// version intensity of service probes option[0-9] default: 7
+  #[argh(option, default = "default_intensity()")]
+  #[serde(default = "default_intensity")]
+  pub intensity: u8,
   /// set request timeout.
   #[argh(option, default = "default_timeout()")]
   #[serde(default = "default_timeout")]
@@ -205,6 +226,10 @@ pub struct ObserverWardConfig {
   #[argh(switch)]
   #[serde(default)]
   pub ic: bool,
+  /// compute JARM and JA3S fingerprint of TLS targets
+  #[argh(switch)]
+  #[serde(default)]
+  pub jarm: bool,
   /// customized template dir
   #[argh(option)]
   #[serde(default)]
@@ -299,6 +324,14 @@ pub struct ObserverWardConfig {
   #[argh(switch)]
   #[serde(default)]
   pub index: bool,
+  /// only output fingerprints of the categories (ex:waf,cdn)
+  #[argh(option)]
+  #[serde(default)]
+  pub only_category: Vec<String>,
+}
+
+fn default_intensity() -> u8 {
+  7
 }
 
 fn default_token() -> Option<String> {
@@ -473,7 +506,106 @@ impl ObserverWardConfig {
       })
       .collect()
   }
+  pub fn nmap_probes(&self) -> Option<Vec<Template>> {
+    let path = self.nmap_probes.as_ref()?;
+    let templates = std::fs::read_to_string(path)
+      .map_err(engine::error::Error::from)
+      .and_then(|content| parse_service_probes(&content));
+    match templates {
+      Ok(templates) if !templates.is_empty() => Some(templates),
+      Ok(_) => None,
+      Err(err) => {
+        error!(
+          "{}load nmap service probes {} err {}",
+          Emoji("💢", ""),
+          path.to_string_lossy(),
+          err
+        );
+        None
+      }
+    }
+  }
+  // 本地的NVD数据只加载一次，所有目标共用
+  pub fn nvd_database(&self) -> Option<Arc<NvdDatabase>> {
+    static NVD: OnceLock<Option<Arc<NvdDatabase>>> = OnceLock::new();
+    let path = self.nvd_feed.as_ref()?;
+    NVD
+      .get_or_init(|| match NvdDatabase::load(path) {
+        Ok(nvd) => Some(Arc::new(nvd)),
+        Err(err) => {
+          error!(
+            "{}load nvd feed {} err {}",
+            Emoji("💢", ""),
+            path.to_string_lossy(),
+            err
+          );
+          None
+        }
+      })
+      .clone()
+  }
+  // 逗号分割的分类，支持多次指定
+  pub fn only_category(&self) -> HashSet<String> {
+    self
+      .only_category
+      .iter()
+      .flat_map(|c| c.split(','))
+      .map(|c| c.trim().to_lowercase())
+      .filter(|c| !c.is_empty())
+      .collect()
+  }
+  pub fn wappalyzer_probes(&self) -> Option<Vec<Template>> {
+    let dir = self.wappalyzer_dir.as_ref()?;
+    let mut paths: Vec<PathBuf> = match std::fs::read_dir(dir) {
+      Ok(entries) => entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect(),
+      Err(err) => {
+        error!(
+          "{}load wappalyzer dir {} err {}",
+          Emoji("💢", ""),
+          dir.to_string_lossy(),
+          err
+        );
+        return None;
+      }
+    };
+    paths.sort();
+    let mut templates = Vec::new();
+    for path in paths {
+      match std::fs::read_to_string(&path)
+        .map_err(engine::error::Error::from)
+        .and_then(|content| parse_technologies(&content))
+      {
+        Ok(ts) => templates.extend(ts),
+        Err(err) => {
+          warn!(
+            "{}load wappalyzer technologies {} err: {}",
+            Emoji("⚠️", ""),
+            path.to_string_lossy(),
+            err
+          );
+        }
+      }
+    }
+    if templates.is_empty() {
+      None
+    } else {
+      Some(templates)
+    }
+  }
   pub fn templates(&self) -> Vec<Template> {
+    let mut templates = self.local_templates();
+    if let Some(ts) = self.nmap_probes() {
+      templates.extend(ts);
+    }
+    if let Some(ts) = self.wappalyzer_probes() {
+      templates.extend(ts);
+    }
+    templates
+  }
+  fn local_templates(&self) -> Vec<Template> {
     let mut templates = Vec::new();
     if let Some(ts) = self.yaml_probes() {
       return ts;
@@ -0,0 +1,72 @@
+use crate::error::{Result, new_io_error};
+use engine::request::DnsMessage;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket, lookup_host};
+
+// UDP应答的最大长度，超过的应答会被截断
+const MAX_UDP_SIZE: usize = 4096;
+
+pub use engine::request::system_resolvers;
+
+/// 向解析服务器发送查询，应答被截断时使用TCP重新查询，返回应答和解析服务器的地址
+pub async fn exchange(
+  resolver: &str,
+  query: &DnsMessage,
+  timeout: Duration,
+) -> Result<(DnsMessage, SocketAddr)> {
+  let data = query
+    .encode()
+    .ok_or_else(|| new_io_error("invalid dns question"))?;
+  let addr = lookup_host(resolver)
+    .await?
+    .next()
+    .ok_or_else(|| new_io_error(&format!("invalid dns resolver: {resolver}")))?;
+  let message = tokio::time::timeout(timeout, exchange_udp(addr, query.id, &data))
+    .await
+    .map_err(|_| new_io_error("dns query timed out"))??;
+  if !message.is_truncated() {
+    return Ok((message, addr));
+  }
+  let message = tokio::time::timeout(timeout, exchange_tcp(addr, query.id, &data))
+    .await
+    .map_err(|_| new_io_error("dns query timed out"))??;
+  Ok((message, addr))
+}
+
+async fn exchange_udp(addr: SocketAddr, id: u16, data: &[u8]) -> Result<DnsMessage> {
+  let bind: SocketAddr = if addr.is_ipv4() {
+    ([0, 0, 0, 0], 0).into()
+  } else {
+    ([0u16; 8], 0).into()
+  };
+  let socket = UdpSocket::bind(bind).await?;
+  socket.connect(addr).await?;
+  socket.send(data).await?;
+  let mut buffer = vec![0; MAX_UDP_SIZE];
+  // 忽略ID不一样和解析不了的报文，直到超时
+  loop {
+    let size = socket.recv(&mut buffer).await?;
+    if let Some(message) = DnsMessage::parse(&buffer[..size])
+      && message.id == id
+    {
+      return Ok(message);
+    }
+  }
+}
+
+// TCP的报文前面有两个字节的长度
+async fn exchange_tcp(addr: SocketAddr, id: u16, data: &[u8]) -> Result<DnsMessage> {
+  let mut stream = TcpStream::connect(addr).await?;
+  let mut request = (data.len() as u16).to_be_bytes().to_vec();
+  request.extend_from_slice(data);
+  stream.write_all(&request).await?;
+  let mut len = [0; 2];
+  stream.read_exact(&mut len).await?;
+  let mut buffer = vec![0; u16::from_be_bytes(len) as usize];
+  stream.read_exact(&mut buffer).await?;
+  DnsMessage::parse(&buffer)
+    .filter(|message| message.id == id)
+    .ok_or_else(|| new_io_error("invalid dns response"))
+}
@@ -0,0 +1,396 @@
+use crate::ClusterExecuteRunner;
+use crate::cli::ObserverWardConfig;
+use console::Emoji;
+use engine::common::http::HttpRecord;
+use engine::execute::ClusterExecute;
+use engine::operators::Operators;
+use engine::operators::dsl::DslHistory;
+use engine::request::RequestGenerator;
+use engine::results::MatchEvent;
+use engine::template::flow::{FlowExecutor, FlowProtocol, FlowSelector, FlowValue, FlowVariables};
+use log::debug;
+use std::collections::BTreeMap;
+
+/// 按照模板的flow逐个执行请求
+///
+/// 每次协议调用只使用对应请求的operators进行匹配，匹配结果先暂存，
+/// 由调用方根据flow的最终结果决定是否保存
+pub(crate) struct FlowRunner<'a> {
+  runner: &'a mut ClusterExecuteRunner,
+  config: &'a ObserverWardConfig,
+  cluster: &'a ClusterExecute,
+  http_record: &'a mut HttpRecord,
+  events: Vec<(MatchEvent, String)>,
+  // 已经完成的http响应，flow中后面的请求也可以关联匹配前面的响应
+  history: DslHistory,
+}
+
+impl<'a> FlowRunner<'a> {
+  pub(crate) fn new(
+    runner: &'a mut ClusterExecuteRunner,
+    config: &'a ObserverWardConfig,
+    cluster: &'a ClusterExecute,
+    http_record: &'a mut HttpRecord,
+  ) -> Self {
+    Self {
+      runner,
+      config,
+      cluster,
+      http_record,
+      events: Vec::new(),
+      history: DslHistory::default(),
+    }
+  }
+  pub(crate) fn into_events(self) -> Vec<(MatchEvent, String)> {
+    self.events
+  }
+  // 模板变量加上flow中的变量，用来替换请求中的占位符
+  fn variables(&self, variables: &FlowVariables) -> BTreeMap<String, String> {
+    let mut all = self.cluster.variables.clone();
+    for (name, value) in variables.iter() {
+      all.insert(name.clone(), value.to_string());
+    }
+    all
+  }
+  // 使用指定请求的operators匹配，提取到的值写入flow变量
+  fn matcher(
+    &mut self,
+    operators: &Operators,
+    mut result: MatchEvent,
+    key: String,
+    variables: &mut FlowVariables,
+  ) -> bool {
+    let mut matched = false;
+    for operator in self.cluster.operators.iter() {
+      matched |= operator.matcher_with(operators, &mut result, false);
+    }
+    for (name, values) in result.extractor() {
+      variables.insert(name, values.into_iter().collect::<FlowValue>());
+    }
+    // 内部提取器的值不输出，但是后面的请求可以使用
+    for (name, value) in result.variables() {
+      variables.insert(name.clone(), FlowValue::from(value.clone()));
+    }
+    if !result.matcher_result().is_empty() {
+      self.events.push((result, key));
+    }
+    matched
+  }
+  async fn http(&mut self, selector: FlowSelector, variables: &mut FlowVariables) -> bool {
+    let mut matched = false;
+    let cluster = self.cluster;
+    for (index, http) in cluster.requests.http.iter().enumerate() {
+      if !selector.matches(index, http.id.as_deref(), http.name.as_deref()) {
+        continue;
+      }
+      let client = self.config.fallback_http_client_from_builder(
+        http.http_option.builder_client(),
+        self.runner.tls_backend_cache.clone(),
+      );
+      let mut generator =
+        RequestGenerator::new(http, &self.runner.target, &self.variables(variables));
+      while let Some((request, payload)) = generator.next() {
+        let mut response = match self
+          .runner
+          .send_http(&client, &request, payload, self.http_record)
+          .await
+        {
+          Ok(response) => response,
+          Err(err) => {
+            debug!("{}:{}", Emoji("💢", ""), err);
+            continue;
+          }
+        };
+        response.extensions_mut().insert(generator.marker().clone());
+        self
+          .history
+          .push(&response, generator.index(), http.name.as_deref());
+        if http.http_option.req_condition && !generator.is_round_end() {
+          continue;
+        }
+        response.extensions_mut().insert(self.history.clone());
+        let result = MatchEvent::new(&response);
+        let hit = self.matcher(
+          &http.operators,
+          result,
+          request.uri().to_string(),
+          variables,
+        );
+        matched |= hit;
+        generator.insert_variables(&self.variables(variables));
+        if hit && http.stop_at_first_match {
+          break;
+        }
+      }
+    }
+    matched
+  }
+  async fn tcp(&mut self, selector: FlowSelector, variables: &mut FlowVariables) -> bool {
+    let mut matched = false;
+    let cluster = self.cluster;
+    for (index, tcp) in cluster.requests.tcp.iter().enumerate() {
+      if !selector.matches(index, tcp.id.as_deref(), tcp.name.as_deref()) {
+        continue;
+      }
+      let responses = match self
+        .runner
+        .send_tcp(self.config, tcp, &self.variables(variables))
+        .await
+      {
+        Ok(responses) => responses,
+        Err(err) => {
+          debug!("{}:{}", Emoji("💢", ""), err);
+          continue;
+        }
+      };
+      for (request, response) in responses {
+        let result = MatchEvent::new(&response);
+        matched |= self.matcher(&tcp.operators, result, request.uri().to_string(), variables);
+      }
+    }
+    matched
+  }
+  async fn udp(&mut self, selector: FlowSelector, variables: &mut FlowVariables) -> bool {
+    let mut matched = false;
+    let cluster = self.cluster;
+    for (index, udp) in cluster.requests.udp.iter().enumerate() {
+      if !selector.matches(index, udp.id.as_deref(), udp.name.as_deref()) {
+        continue;
+      }
+      let responses = match self.runner.send_udp(udp, &self.variables(variables)).await {
+        Ok(responses) => responses,
+        Err(err) => {
+          debug!("{}:{}", Emoji("💢", ""), err);
+          continue;
+        }
+      };
+      for (request, response) in responses {
+        let result = MatchEvent::new(&response);
+        matched |= self.matcher(&udp.operators, result, request.uri().to_string(), variables);
+      }
+    }
+    matched
+  }
+  async fn ssl(&mut self, selector: FlowSelector, variables: &mut FlowVariables) -> bool {
+    let mut matched = false;
+    let cluster = self.cluster;
+    for (index, ssl) in cluster.requests.ssl.iter().enumerate() {
+      if !selector.matches(index, ssl.id.as_deref(), ssl.name.as_deref()) {
+        continue;
+      }
+      let response = match self
+        .runner
+        .send_ssl(self.config, ssl, &self.variables(variables))
+        .await
+      {
+        Ok(response) => response,
+        Err(err) => {
+          debug!("{}:{}", Emoji("💢", ""), err);
+          continue;
+        }
+      };
+      let result = MatchEvent::new(&response);
+      let key = self.runner.target.to_string();
+      matched |= self.matcher(&ssl.operators, result, key, variables);
+    }
+    matched
+  }
+  async fn websocket(&mut self, selector: FlowSelector, variables: &mut FlowVariables) -> bool {
+    let mut matched = false;
+    let cluster = self.cluster;
+    for (index, websocket) in cluster.requests.websocket.iter().enumerate() {
+      if !selector.matches(index, websocket.id.as_deref(), websocket.name.as_deref()) {
+        continue;
+      }
+      let responses = match self
+        .runner
+        .send_websocket(self.config, websocket, &self.variables(variables))
+        .await
+      {
+        Ok(responses) => responses,
+        Err(err) => {
+          debug!("{}:{}", Emoji("💢", ""), err);
+          continue;
+        }
+      };
+      for (request, response) in responses {
+        let result = MatchEvent::new(&response);
+        let key = request.uri().to_string();
+        matched |= self.matcher(&websocket.operators, result, key, variables);
+      }
+    }
+    matched
+  }
+  async fn dns(&mut self, selector: FlowSelector, variables: &mut FlowVariables) -> bool {
+    let mut matched = false;
+    let cluster = self.cluster;
+    for (index, dns) in cluster.requests.dns.iter().enumerate() {
+      // dns请求的name是查询的域名，只能用id选择
+      if !selector.matches(index, dns.id.as_deref(), None) {
+        continue;
+      }
+      let response = match self
+        .runner
+        .send_dns(self.config, dns, &self.variables(variables))
+        .await
+      {
+        Ok(response) => response,
+        Err(err) => {
+          debug!("{}:{}", Emoji("💢", ""), err);
+          continue;
+        }
+      };
+      let result = MatchEvent::new(&response);
+      let key = self.runner.target.to_string();
+      matched |= self.matcher(&dns.operators, result, key, variables);
+    }
+    matched
+  }
+  #[cfg(feature = "code")]
+  fn code(&mut self, selector: FlowSelector, variables: &mut FlowVariables) -> bool {
+    use engine::slinger::{Request, Response};
+    let mut matched = false;
+    let cluster = self.cluster;
+    for (index, code_req) in cluster.requests.code.iter().enumerate() {
+      if !selector.matches(index, code_req.id.as_deref(), None) {
+        continue;
+      }
+      let Some(output) = code_req.execute(&self.runner.target) else {
+        continue;
+      };
+      let fake_request = Request::raw(self.runner.target.clone(), vec![], false);
+      let mut response: Response = Response::builder()
+        .body(output.into_bytes())
+        .unwrap_or_default()
+        .into();
+      response.extensions_mut().insert(fake_request);
+      let result = MatchEvent::new(&response);
+      let key = self.runner.target.to_string();
+      matched |= self.matcher(&code_req.operators, result, key, variables);
+    }
+    matched
+  }
+  #[cfg(not(feature = "code"))]
+  fn code(&mut self, _selector: FlowSelector, _variables: &mut FlowVariables) -> bool {
+    false
+  }
+}
+
+impl FlowExecutor for FlowRunner<'_> {
+  async fn call(
+    &mut self,
+    protocol: FlowProtocol,
+    selector: FlowSelector,
+    variables: &mut FlowVariables,
+  ) -> bool {
+    debug!("{}flow call: {}({:?})", Emoji("🔀", ""), protocol, selector);
+    match protocol {
+      FlowProtocol::Http => self.http(selector, variables).await,
+      FlowProtocol::Tcp => self.tcp(selector, variables).await,
+      FlowProtocol::Udp => self.udp(selector, variables).await,
+      FlowProtocol::Ssl => self.ssl(selector, variables).await,
+      FlowProtocol::Dns => self.dns(selector, variables).await,
+      FlowProtocol::Websocket => self.websocket(selector, variables).await,
+      FlowProtocol::Code => self.code(selector, variables),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tls::TlsBackend;
+  use argh::FromArgs;
+  use engine::template::Template;
+  use engine::template::cluster::cluster_templates;
+  use tokio::io::{AsyncReadExt, AsyncWriteExt};
+  use tokio::net::TcpListener;
+
+  const TEMPLATE: &str = r#"
+id: flow-internal-token
+info:
+  name: flow-internal-token
+  author: test
+  severity: info
+flow: http(0) || http(1)
+http:
+  - method: GET
+    path:
+      - "{{BaseURL}}/"
+    extractors:
+      - type: regex
+        name: token
+        internal: true
+        group: 1
+        regex:
+          - "token=([a-z0-9]+)"
+  - method: GET
+    path:
+      - "{{BaseURL}}/check/{{token}}"
+    matchers:
+      - type: word
+        words:
+          - welcome
+"#;
+
+  // 首页返回token，只有带上正确token的请求才返回welcome
+  async fn serve(listener: TcpListener) {
+    while let Ok((mut stream, _)) = listener.accept().await {
+      let mut buf = vec![0; 4096];
+      let n = stream.read(&mut buf).await.unwrap_or_default();
+      let request = String::from_utf8_lossy(&buf[..n]);
+      let body = if request.starts_with("GET /check/abc123 ") {
+        "welcome"
+      } else {
+        "token=abc123"
+      };
+      let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+      );
+      let _ = stream.write_all(response.as_bytes()).await;
+    }
+  }
+
+  #[tokio::test]
+  async fn internal_extractor_feeds_next_request() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve(listener));
+    let template: Template = serde_yaml::from_str(TEMPLATE).unwrap();
+    let cluster_type = cluster_templates(&[template], false);
+    let cluster = cluster_type
+      .web_default
+      .iter()
+      .chain(cluster_type.web_other.iter())
+      .next()
+      .cloned()
+      .unwrap();
+    // Default会解析测试进程的命令行参数
+    let config = ObserverWardConfig::from_args(&["observer_ward"], &[]).unwrap();
+    let target: engine::slinger::http::Uri = format!("http://{addr}/").parse().unwrap();
+    let mut runner = ClusterExecuteRunner::new(&target);
+    let client = config.fallback_http_client(runner.tls_backend_cache.clone());
+    let mut http_record = HttpRecord::new(client.client_for_backend(TlsBackend::Rustls));
+    let mut flow_runner = FlowRunner::new(&mut runner, &config, &cluster, &mut http_record);
+    let mut variables = FlowVariables::new();
+    assert!(
+      !flow_runner
+        .http(FlowSelector::Index(0), &mut variables)
+        .await
+    );
+    // 内部提取器的值不会作为提取结果输出
+    assert_eq!(
+      variables.get("token").map(ToString::to_string).as_deref(),
+      Some("abc123")
+    );
+    assert!(
+      flow_runner
+        .http(FlowSelector::Index(1), &mut variables)
+        .await
+    );
+    let events = flow_runner.into_events();
+    assert_eq!(events.len(), 1);
+    assert!(events[0].0.extractor().is_empty());
+  }
+}
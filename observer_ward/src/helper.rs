@@ -180,6 +180,31 @@ impl<'a> Helper<'a> {
       self.update_plugins().await;
       std::process::exit(0);
     }
+    if let (Some(ts), Some(save_path)) = (&self.config.nmap_probes(), &self.config.probe_path) {
+      if let Ok(f) = File::create(save_path) {
+        serde_json::to_writer(f, ts).unwrap_or_default();
+        info!(
+          "{}convert the {} matches of the nmap service probes to a json file {}",
+          Emoji("ℹ️", ""),
+          ts.len(),
+          save_path.to_string_lossy()
+        );
+      }
+      std::process::exit(0);
+    }
+    if let (Some(ts), Some(save_path)) = (&self.config.wappalyzer_probes(), &self.config.probe_path)
+    {
+      if let Ok(f) = File::create(save_path) {
+        serde_json::to_writer(f, ts).unwrap_or_default();
+        info!(
+          "{}convert the {} wappalyzer technologies to a json file {}",
+          Emoji("ℹ️", ""),
+          ts.len(),
+          save_path.to_string_lossy()
+        );
+      }
+      std::process::exit(0);
+    }
     if let (Some(ts), Some(save_path)) = (&self.config.yaml_probes(), &self.config.probe_path) {
       if let Ok(f) = File::create(save_path) {
         serde_json::to_writer(f, ts).unwrap_or_default();
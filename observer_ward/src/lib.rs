@@ -1,19 +1,35 @@
 use crate::cli::{Mode, ObserverWardConfig};
 use crate::error::new_io_error;
+use crate::flow::FlowRunner;
 use crate::nuclei::{NucleiRunner, gen_nuclei_tags};
-use crate::tls::TlsBackend;
+use crate::tls::{
+  FallbackHttpClient, TlsBackend, fallback_tls_connector, is_tls_record, native_tls_handshake,
+  rustls_handshake, tls_fingerprint,
+};
 use console::Emoji;
 use engine::common::cert::X509Certificate;
 use engine::common::html::extract_title;
 use engine::common::http::HttpRecord;
-use engine::execute::{ClusterExecute, ClusterType};
+use engine::common::jarm::TlsFingerprint;
+use engine::common::nvd::NvdDatabase;
+use engine::common::{ConnectionInfo, Marker, Payload};
+use engine::execute::{ClusterExecute, ClusterType, ClusteredOperator};
+use engine::info::Info;
+use engine::operators::dsl::DslHistory;
 use engine::operators::matchers::FaviconMap;
-use engine::request::RequestGenerator;
-use engine::results::{MatchEvent, MatcherResult};
+use engine::request::{
+  DNSRequest, Opcode, RequestGenerator, SSLRequest, TCPRequest, UDPRequest, WebSocketFrame,
+  WebSocketHandshake, WebSocketRequest, websocket_key,
+};
+use engine::results::{
+  DEFAULT_WEIGHT, Evidence, MatchEvent, MatcherResult, Technology, resolve_relations,
+};
 use engine::slinger::http::StatusCode;
 use engine::slinger::http::uri::{PathAndQuery, Uri};
-use engine::slinger::{Request, Response, http_serde};
+use engine::slinger::tls::CustomTlsStream;
+use engine::slinger::{ConnectorBuilder, Request, Response, Socket, StreamWrapper, http_serde};
 use engine::template::Template;
+use engine::template::flow::{Flow, FlowVariables};
 use error::Result;
 use futures::StreamExt;
 use futures::channel::mpsc::UnboundedSender;
@@ -22,16 +38,20 @@ use log::{debug, info};
 use moka::future::Cache;
 use rustc_hash::FxHasher;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::File;
 use std::hash::Hasher;
+use std::net::{IpAddr, SocketAddr};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
 
 pub mod api;
 pub mod cli;
+mod dns;
 pub mod error;
+mod flow;
 pub mod helper;
 pub mod input;
 #[cfg(feature = "mcp")]
@@ -110,6 +130,16 @@ pub struct MatchedResult {
     )
   )]
   certificate: Option<X509Certificate>,
+  // 服务端TLS指纹
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "TLS fingerprint",
+      description = "JARM and JA3S fingerprints of the TLS server (present for HTTPS and TLS connections)",
+    )
+  )]
+  tls_fingerprint: Option<TlsFingerprint>,
   // 简化指纹列表
   /// Simplified fingerprint names/identifiers
   #[cfg_attr(
@@ -121,6 +151,18 @@ pub struct MatchedResult {
     )
   )]
   name: HashSet<String>,
+  // 指纹的分类
+  /// Categories of the detected technologies
+  #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "categories",
+      description = "Categories of the detected technologies",
+      example = r#"["web-server", "waf"]"#
+    )
+  )]
+  category: BTreeSet<String>,
   // 指纹信息
   /// Detailed fingerprint matching results
   #[cfg_attr(
@@ -129,13 +171,31 @@ pub struct MatchedResult {
       title = "fingerprint details",
       description = "Detailed technology fingerprint matching results",
       example = r#"[{
-            "name": "nginx",
-            "version": "1.18.0",
-            "confidence": 95
+            "matcher-results": [{"template": "nginx", "matcher-name": ["server-header"]}],
+            "matched-at": "https://example.com/"
         }]"#
     )
   )]
   fingerprints: Vec<MatchEvent>,
+  // 每个技术合并之后的版本、可信度和证据
+  /// Consolidated version, confidence and evidence of each detected technology
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  #[cfg_attr(
+    feature = "mcp",
+    schemars(
+      title = "technologies",
+      description = "Consolidated version, confidence and evidence of each detected technology keyed by template id",
+      example = r#"{
+            "nginx": {
+                "name": "nginx",
+                "version": "1.18.0",
+                "confidence": 96,
+                "evidence": [{"matched-at": "https://example.com/", "matcher": ["server-header"], "version": {"version": "1.18.0"}, "weight": 80}]
+            }
+        }"#
+    )
+  )]
+  technologies: BTreeMap<String, Technology>,
 }
 
 impl MatchedResult {
@@ -152,6 +212,90 @@ impl MatchedResult {
   pub fn names(&self) -> &HashSet<String> {
     &self.name
   }
+  pub fn category(&self) -> &BTreeSet<String> {
+    &self.category
+  }
+  pub fn technologies(&self) -> &BTreeMap<String, Technology> {
+    &self.technologies
+  }
+  pub fn correlate_vulnerabilities(&mut self, nvd: &NvdDatabase) {
+    self
+      .technologies
+      .values_mut()
+      .for_each(|technology| technology.correlate(nvd));
+  }
+  fn update_category(&mut self) {
+    self.category = self
+      .fingerprints
+      .iter()
+      .flat_map(|fp| fp.matcher_result())
+      .flat_map(|mr| mr.category.iter().cloned())
+      .collect();
+  }
+  // 只保留指定分类的指纹
+  pub fn retain_category(&mut self, category: &HashSet<String>) {
+    self.fingerprints.iter_mut().for_each(|fp| {
+      fp.matcher_result_mut()
+        .retain(|mr| mr.category.iter().any(|c| category.contains(c)))
+    });
+    self
+      .fingerprints
+      .retain(|fp| !fp.matcher_result().is_empty());
+    self.name = self.fingerprints.iter().flat_map(|fp| fp.name()).collect();
+    self.technologies.retain(|t, _| self.name.contains(t));
+    self.update_category();
+  }
+
+  // 根据指纹之间的关系补充推断出来的指纹，剔除互斥和依赖没有满足的指纹
+  pub fn apply_relations(&mut self, infos: &BTreeMap<String, Arc<Info>>) {
+    let relations = resolve_relations(&self.name, infos);
+    if !relations.removed.is_empty() {
+      self.fingerprints.iter_mut().for_each(|fp| {
+        fp.matcher_result_mut()
+          .retain(|mr| !relations.removed.contains(&mr.template))
+      });
+      self
+        .fingerprints
+        .retain(|fp| !fp.matcher_result().is_empty());
+      self.name.retain(|name| !relations.removed.contains(name));
+      self
+        .technologies
+        .retain(|t, _| !relations.removed.contains(t));
+    }
+    for (template, source) in relations.implied {
+      let info = infos.get(&template).cloned().unwrap_or_else(|| {
+        Arc::new(Info {
+          name: template.clone(),
+          ..Default::default()
+        })
+      });
+      // 推断的指纹放在来源所在的请求下面
+      if let Some(fp) = self
+        .fingerprints
+        .iter_mut()
+        .find(|fp| fp.matcher_result().iter().any(|mr| mr.template == source))
+      {
+        fp.push_inferred(&template, &info, &source);
+        // 推断的可信度不超过来源的可信度
+        let weight = self
+          .technologies
+          .get(&source)
+          .map_or(DEFAULT_WEIGHT, |t| t.confidence);
+        self
+          .technologies
+          .entry(template.clone())
+          .or_insert_with(|| Technology::new(&info))
+          .push(Evidence {
+            matched_at: fp.matched_at().to_string(),
+            inferred_from: Some(source),
+            weight,
+            ..Default::default()
+          });
+        self.name.insert(template);
+      }
+    }
+    self.update_category();
+  }
 
   pub fn update_matched(&mut self, result: &MatchEvent) {
     let response = result.response().unwrap_or_default();
@@ -168,9 +312,16 @@ impl MatchedResult {
       self.title.insert(t);
       self.status = Some(status_code);
     }
-    // if self.certificate.is_none() {
-    //   self.certificate = None;
-    // }
+    // 第一个有证书的响应，https的响应和ssl探针都会带上对端证书
+    if self.certificate.is_none() {
+      self.certificate = response
+        .certificate()
+        .and_then(|certs| certs.first())
+        .and_then(|cert| X509Certificate::from_der(cert));
+    }
+    if self.tls_fingerprint.is_none() {
+      self.tls_fingerprint = response.extensions().get::<TlsFingerprint>().cloned();
+    }
     if let Some(fav) = response.extensions().get::<HashSet<FaviconMap>>() {
       self.favicon.extend(fav.clone());
     }
@@ -187,6 +338,20 @@ impl MatchedResult {
       }
       // Merge template names into the overall name set
       self.name.extend(result.name());
+      let matched_at = result.matched_at().to_string();
+      for mr in result.matcher_result() {
+        self
+          .technologies
+          .entry(mr.template.clone())
+          .or_insert_with(|| Technology::new(&mr.info))
+          .push(Evidence::new(mr, &matched_at));
+      }
+      self.category.extend(
+        result
+          .matcher_result()
+          .iter()
+          .flat_map(|mr| mr.category.iter().cloned()),
+      );
 
       // For each incoming MatcherResult, try to merge into an existing MatchEvent
       // keyed by template string. If no existing MatchEvent contains that template,
@@ -213,11 +378,16 @@ impl MatchedResult {
             let incoming_extractors = std::mem::take(&mut incoming_mr.extractor);
             for (k, vset) in incoming_extractors.into_iter() {
               if let Some(existing_set) = existing_mr.extractor.get_mut(&k) {
-                existing_set.extend(vset.into_iter());
+                existing_set.extend(vset);
               } else {
                 existing_mr.extractor.insert(k, vset);
               }
             }
+            // 保留先命中的payload，只补充缺少的
+            let incoming_payload = std::mem::take(&mut incoming_mr.payload);
+            for (k, v) in incoming_payload.into_iter() {
+              existing_mr.payload.entry(k).or_insert(v);
+            }
 
             // we merged this matcher into an existing fingerprint
             merged = true;
@@ -258,6 +428,21 @@ pub struct ClusterExecuteRunner {
   cache: Cache<u64, Response>,
   #[serde(skip, default = "default_tls_backend_cache")]
   tls_backend_cache: Arc<RwLock<HashMap<String, TlsBackend>>>,
+  // 已解析的主机地址，同一个主机只解析一次
+  #[serde(skip)]
+  resolved_ip: HashMap<String, Option<IpAddr>>,
+  // 目标的JARM和JA3S指纹，https和tls的响应都会带上
+  #[serde(skip)]
+  tls_fingerprint: Option<TlsFingerprint>,
+  // softmatch确定的服务，后面的探针只匹配这个服务的规则
+  #[serde(skip)]
+  softmatch: Option<String>,
+  // 已经识别到服务，不用再发送其他探针
+  #[serde(skip)]
+  service_matched: bool,
+  // tcp探针收到了TLS的响应，需要握手之后重新识别服务
+  #[serde(skip)]
+  tls_detected: bool,
 }
 fn default_cache() -> Cache<u64, Response> {
   Cache::builder().max_capacity(100).build()
@@ -276,6 +461,11 @@ impl ClusterExecuteRunner {
       http_record: None,
       cache: Cache::builder().max_capacity(100).build(),
       tls_backend_cache: Arc::new(RwLock::new(HashMap::new())),
+      resolved_ip: HashMap::new(),
+      tls_fingerprint: None,
+      softmatch: None,
+      service_matched: false,
+      tls_detected: false,
     }
   }
   fn update_result(&mut self, result: MatchEvent, key: Option<String>) {
@@ -302,6 +492,8 @@ impl ClusterExecuteRunner {
     // 相同插件和url只跑一次
     let mut skip_target: BTreeMap<String, Vec<String>> = BTreeMap::new();
     for (base_url, matched_result) in self.matched_result.iter_mut() {
+      // nuclei提取到的版本也作为技术的证据
+      let mut nuclei_evidence = Vec::new();
       for fingerprints in matched_result.fingerprints.iter_mut() {
         let mut nuclei_results = Vec::new();
         let matched_at = fingerprints.matched_at().to_string();
@@ -320,12 +512,24 @@ impl ClusterExecuteRunner {
             }
             let result = args.run(config);
             if !result.nuclei.is_empty() {
+              nuclei_evidence.extend(
+                result
+                  .nuclei
+                  .iter()
+                  .filter_map(|n| Evidence::from_nuclei(n))
+                  .map(|e| (matched.template.clone(), e)),
+              );
               nuclei_results.push(result);
             }
           };
         }
         fingerprints.insert_nuclei(nuclei_results);
       }
+      for (template, evidence) in nuclei_evidence {
+        if let Some(technology) = matched_result.technologies.get_mut(&template) {
+          technology.push(evidence);
+        }
+      }
     }
   }
 }
@@ -353,30 +557,34 @@ impl ClusterExecuteRunner {
     http_record: &mut HttpRecord,
     extra_clusters: Option<&[Arc<ClusterExecute>]>,
   ) -> Result<()> {
+    // 有flow的模板按照flow的流程执行
+    if let Some(flow) = &cluster.flow {
+      self.flow(config, cluster, flow, http_record).await;
+      return Ok(());
+    }
+    // 模板变量和内部提取器提取到的变量，同一个模板后面的请求都可以使用
+    let mut variables = cluster.variables.clone();
+    // 模板已经完成的响应，给关联匹配的DSL使用
+    let mut history = DslHistory::default();
     // 可能会有多个http，一般只有一个，多个会有flow控制
     for http in cluster.requests.http.iter() {
       let client = config.fallback_http_client_from_builder(
         http.http_option.builder_client(),
         self.tls_backend_cache.clone(),
       );
-      let generator = RequestGenerator::new(http, &self.target);
+      let mut generator = RequestGenerator::new(http, &self.target, &variables);
       // 请求全部路径
-      for request in generator {
-        debug!("{}{:#?}", Emoji("📤", ""), request);
-        let key = self.get_request_hash(&request);
-        let (mut response, backend) = if let Some(response) = self.cache.get(&key).await {
-          // cache hit
-          (response, client.preferred_backend_for_uri(request.uri()))
-        } else {
-          // cache miss
-          let (response, backend) = client.execute_with_backend(request.clone()).await?;
-          self.cache.insert(key, response.clone()).await;
-          (response, backend)
-        };
-        debug!("{}{:#?}", Emoji("📥", ""), response);
-        // 提取icon
-        http_record.set_client(client.client_for_backend(backend));
-        http_record.find_favicon_tag(&mut response).await;
+      while let Some((request, payload)) = generator.next() {
+        let mut response = self
+          .send_http(&client, &request, payload, http_record)
+          .await?;
+        response.extensions_mut().insert(generator.marker().clone());
+        history.push(&response, generator.index(), http.name.as_deref());
+        // 关联匹配的请求等当前payload的全部请求完成后，在最后一个响应上匹配
+        if http.http_option.req_condition && !generator.is_round_end() {
+          continue;
+        }
+        response.extensions_mut().insert(history.clone());
         let mut flag = false;
         let mut result = MatchEvent::new(&response);
         cluster
@@ -393,6 +601,8 @@ impl ClusterExecuteRunner {
               .for_each(|operator| operator.matcher(&mut result, false));
           }
         }
+        generator.insert_variables(result.variables());
+        variables.extend(result.variables().clone());
         if !result.matcher_result().is_empty() {
           let mut base_keys: Vec<String> = Vec::new();
           base_keys.push(self.target.to_string());
@@ -436,6 +646,63 @@ impl ClusterExecuteRunner {
     }
     Ok(())
   }
+  // 发送单个http请求，相同的请求会命中缓存，生成请求的payload会放到响应的扩展中
+  async fn send_http(
+    &mut self,
+    client: &FallbackHttpClient,
+    request: &Request,
+    payload: Payload,
+    http_record: &mut HttpRecord,
+  ) -> Result<Response> {
+    debug!("{}{:#?}", Emoji("📤", ""), request);
+    let key = self.get_request_hash(request);
+    let (mut response, backend) = if let Some(response) = self.cache.get(&key).await {
+      // cache hit
+      (response, client.preferred_backend_for_uri(request.uri()))
+    } else {
+      // cache miss
+      let start = Instant::now();
+      let (mut response, backend) = client.execute_with_backend(request.clone()).await?;
+      let duration = start.elapsed();
+      let ip = self.resolve_ip(request.uri()).await;
+      response.extensions_mut().insert(ConnectionInfo {
+        ip,
+        duration,
+        tls_version: None,
+      });
+      self.cache.insert(key, response.clone()).await;
+      (response, backend)
+    };
+    debug!("{}{:#?}", Emoji("📥", ""), response);
+    if request.uri().scheme_str() == Some("https")
+      && let Some(fingerprint) = &self.tls_fingerprint
+    {
+      response.extensions_mut().insert(fingerprint.clone());
+    }
+    // 提取icon
+    http_record.set_client(client.client_for_backend(backend));
+    http_record.find_favicon_tag(&mut response).await;
+    response.extensions_mut().insert(payload);
+    Ok(response)
+  }
+  // 请求主机的IP地址，给DSL的ip变量使用
+  async fn resolve_ip(&mut self, uri: &Uri) -> Option<IpAddr> {
+    let host = uri.host()?.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = host.parse() {
+      return Some(ip);
+    }
+    if let Some(ip) = self.resolved_ip.get(host) {
+      return *ip;
+    }
+    let port = uri.port_u16().unwrap_or(80);
+    let ip = tokio::net::lookup_host((host, port))
+      .await
+      .ok()
+      .and_then(|mut addrs| addrs.next())
+      .map(|addr| addr.ip());
+    self.resolved_ip.insert(host.to_string(), ip);
+    ip
+  }
   fn get_request_hash(&self, request: &Request) -> u64 {
     let mut hasher = FxHasher::default();
     hasher.write(request.method().as_str().as_bytes());
@@ -458,20 +725,82 @@ impl ClusterExecuteRunner {
 // 处理tcp的探针
 impl ClusterExecuteRunner {
   // 单个tcp
-  async fn tcp(&mut self, config: &ObserverWardConfig, cluster: &ClusterExecute) -> Result<bool> {
+  // fallback探针的规则也会用来匹配当前探针的响应
+  async fn tcp(
+    &mut self,
+    config: &ObserverWardConfig,
+    cluster: &ClusterExecute,
+    fallback: &[&Arc<ClusterExecute>],
+  ) -> Result<bool> {
+    if let Some(flow) = &cluster.flow {
+      let client = config
+        .fallback_http_client(self.tls_backend_cache.clone())
+        .client_for_backend(TlsBackend::Rustls);
+      let mut http_record = HttpRecord::new(client);
+      return Ok(self.flow(config, cluster, flow, &mut http_record).await);
+    }
     // 服务指纹识别，实验功能
     let mut flag = false;
+    let mut variables = cluster.variables.clone();
+    let operators: Vec<&Arc<ClusteredOperator>> = cluster
+      .operators
+      .iter()
+      .chain(fallback.iter().flat_map(|c| c.operators.iter()))
+      .filter(|op| {
+        self
+          .softmatch
+          .as_ref()
+          .is_none_or(|service| &op.info().name == service)
+      })
+      .collect();
     for tcp in cluster.requests.tcp.iter() {
-      let conn_builder = config.tcp_client_builder();
-      let timeout = Duration::from_secs(config.timeout / 2);
-      let mut socket = conn_builder
-        .read_timeout(Some(timeout))
-        .write_timeout(Some(timeout))
-        .build()?
-        .connect_with_uri(&self.target)
-        .await?;
+      for (request, response) in self.send_tcp(config, tcp, &variables).await? {
+        if self.target.scheme_str() == Some("tcp")
+          && response.body().as_ref().is_some_and(|b| is_tls_record(b))
+        {
+          self.tls_detected = true;
+        }
+        let mut result = MatchEvent::new(&response);
+        operators
+          .iter()
+          .for_each(|operator| operator.matcher(&mut result, false));
+        variables.extend(result.variables().clone());
+        if !result.matcher_result().is_empty() {
+          flag = true;
+          for mr in result.matcher_result() {
+            if !mr.info.is_softmatch() {
+              self.service_matched = true;
+            } else if self.softmatch.is_none() {
+              self.softmatch = Some(mr.info.name.clone());
+            }
+          }
+          self.update_result(result, Some(request.uri().to_string()));
+        }
+      }
+    }
+    Ok(flag)
+  }
+  // 按顺序发送tcp请求的全部输入，返回有数据的响应，每组payload使用单独的连接
+  async fn send_tcp(
+    &self,
+    config: &ObserverWardConfig,
+    tcp: &TCPRequest,
+    variables: &BTreeMap<String, String>,
+  ) -> Result<Vec<(Request, Response)>> {
+    let mut responses = Vec::new();
+    let mut marker = Marker::with_target(&self.target);
+    marker.extend_resolved(variables);
+    let timeout = Duration::from_secs(config.timeout / 2);
+    let conn_builder = config
+      .tcp_client_builder()
+      .read_timeout(Some(timeout))
+      .write_timeout(Some(timeout));
+    for payload in Payload::generate(tcp.payload_attack.as_ref()) {
+      let marker = marker.with_payload(&payload);
+      let mut socket = self.connect_tcp(conn_builder.clone()).await?;
+      let certificate = socket.peer_certificate();
       for input in tcp.inputs.iter() {
-        let data = input.data();
+        let data = input.data(&marker);
         let request = Request::raw(self.target.clone(), data.clone(), true);
         debug!("{}{:#?}", Emoji("📤", ""), request);
         socket.write_all(&data).await.unwrap_or_default();
@@ -480,29 +809,91 @@ impl ClusterExecuteRunner {
         let mut buffer = vec![0; 12]; // 定义一个缓冲区
         let mut total_bytes_read = 0;
         // http超时对于tcp来说还是太长了
-        while let Ok(n) = socket.read(&mut buffer).await {
-          if n == 0 {
-            break;
-          }
-          full.extend_from_slice(&buffer[..n]);
-          total_bytes_read += n;
-          // 检查是否读取到了全部数据，如果是，则退出循环
-          if total_bytes_read >= input.read.unwrap_or(2048) {
-            break;
+        let read = async {
+          while let Ok(n) = socket.read(&mut buffer).await {
+            if n == 0 {
+              break;
+            }
+            full.extend_from_slice(&buffer[..n]);
+            total_bytes_read += n;
+            // 检查是否读取到了全部数据，如果是，则退出循环
+            if total_bytes_read >= input.read.unwrap_or(2048) {
+              break;
+            }
           }
+        };
+        // 探针设置了等待时间的，超时之后使用已经读到的数据
+        match tcp.wait() {
+          Some(wait) => tokio::time::timeout(wait, read).await.unwrap_or_default(),
+          None => read.await,
         }
         let mut response: Response = Response::builder().body(full).unwrap_or_default().into();
         response.extensions_mut().insert(request.clone());
+        response.extensions_mut().insert(payload.clone());
+        response.extensions_mut().insert(marker.clone());
+        // 隧道里面的响应带上证书和TLS指纹
+        if let Some(certificate) = &certificate {
+          response.extensions_mut().insert(certificate.clone());
+          if let Some(fingerprint) = &self.tls_fingerprint {
+            response.extensions_mut().insert(fingerprint.clone());
+          }
+        }
         debug!("{}{:#?}", Emoji("📥", ""), response);
         // TCP的如果没有响应都不用匹配规则了
         if response.body().is_none() {
           continue;
         }
+        responses.push((request, response));
+      }
+    }
+    Ok(responses)
+  }
+  // tls目标握手之后在隧道里面发送探针，SNI使用目标的域名，rustls握手失败再用native-tls握手
+  async fn connect_tcp(&self, conn_builder: ConnectorBuilder) -> Result<Socket> {
+    if self.target.scheme_str() != Some("tls") {
+      return Ok(conn_builder.build()?.connect_with_uri(&self.target).await?);
+    }
+    let uri = set_uri_scheme("https", &self.target)?;
+    let conn_builder = conn_builder
+      .certs_verification(false)
+      .hostname_verification(false)
+      .min_tls_version(Some(engine::slinger::tls::Version::TLS_1_0));
+    match conn_builder.build()?.connect_with_uri(&uri).await {
+      Ok(socket) => Ok(socket),
+      Err(err) => {
+        debug!("{}{}", Emoji("💢", ""), err);
+        Ok(
+          conn_builder
+            .custom_tls_connector(fallback_tls_connector())
+            .build()?
+            .connect_with_uri(&uri)
+            .await?,
+        )
+      }
+    }
+  }
+}
+
+// 处理udp的探针
+impl ClusterExecuteRunner {
+  async fn udp(&mut self, config: &ObserverWardConfig, cluster: &ClusterExecute) -> Result<bool> {
+    if let Some(flow) = &cluster.flow {
+      let client = config
+        .fallback_http_client(self.tls_backend_cache.clone())
+        .client_for_backend(TlsBackend::Rustls);
+      let mut http_record = HttpRecord::new(client);
+      return Ok(self.flow(config, cluster, flow, &mut http_record).await);
+    }
+    let mut flag = false;
+    let mut variables = cluster.variables.clone();
+    for udp in cluster.requests.udp.iter() {
+      for (request, response) in self.send_udp(udp, &variables).await? {
         let mut result = MatchEvent::new(&response);
         cluster
           .operators
           .iter()
           .for_each(|operator| operator.matcher(&mut result, false));
+        variables.extend(result.variables().clone());
         if !result.matcher_result().is_empty() {
           flag = true;
           self.update_result(result, Some(request.uri().to_string()));
@@ -511,6 +902,437 @@ impl ClusterExecuteRunner {
     }
     Ok(flag)
   }
+  // 按顺序发送udp探针的全部输入，没有响应的时候重发，每组payload使用单独的端口
+  async fn send_udp(
+    &self,
+    udp: &UDPRequest,
+    variables: &BTreeMap<String, String>,
+  ) -> Result<Vec<(Request, Response)>> {
+    let mut responses = Vec::new();
+    let mut marker = Marker::with_target(&self.target);
+    marker.extend_resolved(variables);
+    let host = self.target.host().unwrap_or_default();
+    let port = self
+      .target
+      .port_u16()
+      .ok_or_else(|| new_io_error("udp target without port"))?;
+    let addr = tokio::net::lookup_host((host.trim_start_matches('[').trim_end_matches(']'), port))
+      .await?
+      .next()
+      .ok_or_else(|| new_io_error(&format!("invalid udp target: {}", self.target)))?;
+    for payload in Payload::generate(udp.payload_attack.as_ref()) {
+      let marker = marker.with_payload(&payload);
+      let bind: SocketAddr = if addr.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+      } else {
+        ([0u16; 8], 0).into()
+      };
+      let socket = UdpSocket::bind(bind).await?;
+      socket.connect(addr).await?;
+      for input in udp.inputs.iter() {
+        let data = input.data(&marker);
+        let request = Request::raw(self.target.clone(), data.clone(), true);
+        debug!("{}{:#?}", Emoji("📤", ""), request);
+        let start = Instant::now();
+        let mut buffer = vec![0; input.read.unwrap_or(2048)];
+        let mut full = Vec::new();
+        for _ in 0..=udp.retransmits() {
+          let received = match socket.send(&data).await {
+            Ok(_) => tokio::time::timeout(udp.wait(), socket.recv(&mut buffer)).await,
+            Err(err) => Ok(Err(err)),
+          };
+          match received {
+            Ok(Ok(size)) => {
+              full.extend_from_slice(&buffer[..size]);
+              break;
+            }
+            // 端口不可达的时候不用再发送其他的输入，已经收到的响应还要匹配，一个都没有才返回错误
+            Ok(Err(err)) if responses.is_empty() => return Err(err.into()),
+            Ok(Err(_)) => return Ok(responses),
+            // 超时重发
+            Err(_) => {}
+          }
+        }
+        // UDP的如果没有响应都不用匹配规则了
+        if full.is_empty() {
+          continue;
+        }
+        let mut response: Response = Response::builder().body(full).unwrap_or_default().into();
+        response.extensions_mut().insert(ConnectionInfo {
+          ip: Some(addr.ip()),
+          duration: start.elapsed(),
+          tls_version: None,
+        });
+        response.extensions_mut().insert(request.clone());
+        response.extensions_mut().insert(payload.clone());
+        response.extensions_mut().insert(marker.clone());
+        debug!("{}{:#?}", Emoji("📥", ""), response);
+        responses.push((request, response));
+      }
+    }
+    Ok(responses)
+  }
+}
+
+// 处理flow控制的模板
+impl ClusterExecuteRunner {
+  // 执行flow，只有flow的结果为真时才保存执行过程中的匹配结果
+  async fn flow(
+    &mut self,
+    config: &ObserverWardConfig,
+    cluster: &ClusterExecute,
+    flow: &Flow,
+    http_record: &mut HttpRecord,
+  ) -> bool {
+    let mut flow_runner = FlowRunner::new(self, config, cluster, http_record);
+    let matched = flow.execute(&mut flow_runner, FlowVariables::new()).await;
+    let events = flow_runner.into_events();
+    debug!("{}flow result: {}", Emoji("🔀", ""), matched);
+    if matched {
+      for (result, key) in events {
+        self.update_result(result, Some(key));
+      }
+    }
+    matched
+  }
+}
+
+// 处理ssl的探针
+impl ClusterExecuteRunner {
+  async fn ssl(&mut self, config: &ObserverWardConfig, cluster: &ClusterExecute) -> Result<bool> {
+    if let Some(flow) = &cluster.flow {
+      let client = config
+        .fallback_http_client(self.tls_backend_cache.clone())
+        .client_for_backend(TlsBackend::Rustls);
+      let mut http_record = HttpRecord::new(client);
+      return Ok(self.flow(config, cluster, flow, &mut http_record).await);
+    }
+    let mut flag = false;
+    for ssl in cluster.requests.ssl.iter() {
+      let response = self.send_ssl(config, ssl, &cluster.variables).await?;
+      let mut result = MatchEvent::new(&response);
+      cluster
+        .operators
+        .iter()
+        .for_each(|operator| operator.matcher(&mut result, false));
+      if !result.matcher_result().is_empty() {
+        flag = true;
+        self.update_result(result, Some(self.target.to_string()));
+      }
+    }
+    Ok(flag)
+  }
+  // 和目标握手，握手的结果转换成可以匹配的响应，rustls握手失败再用native-tls握手
+  async fn send_ssl(
+    &self,
+    config: &ObserverWardConfig,
+    ssl: &SSLRequest,
+    variables: &BTreeMap<String, String>,
+  ) -> Result<Response> {
+    let mut marker = Marker::with_target(&self.target);
+    marker.extend_resolved(variables);
+    let address = ssl.address(&marker);
+    let uri: Uri = format!("tcp://{address}")
+      .parse()
+      .map_err(|_| new_io_error(&format!("invalid ssl address: {address}")))?;
+    let host = uri.host().unwrap_or_default();
+    // 没有指定SNI的时候，域名作为SNI，IP地址不发送SNI
+    let server_name = match &ssl.server_name {
+      Some(name) => Some(marker.replace(name)),
+      None => host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+        .is_err()
+        .then(|| host.to_string()),
+    };
+    let connector = config.tcp_client_builder().build()?;
+    debug!("{}ssl handshake: {}", Emoji("📤", ""), address);
+    let start = Instant::now();
+    let stream = tcp_stream(connector.connect_with_uri(&uri).await?)?;
+    let ip = stream.peer_addr().ok().map(|addr| addr.ip());
+    let handshake = match rustls_handshake(
+      stream,
+      server_name.as_deref(),
+      ssl.min_version,
+      ssl.max_version,
+    )
+    .await
+    {
+      Ok(handshake) => handshake,
+      Err(err) => {
+        debug!("{}{}", Emoji("💢", ""), err);
+        let stream = tcp_stream(connector.connect_with_uri(&uri).await?)?;
+        native_tls_handshake(
+          stream,
+          server_name.as_deref(),
+          ssl.min_version,
+          ssl.max_version,
+        )
+        .await?
+      }
+    };
+    let connection = ConnectionInfo {
+      ip,
+      duration: start.elapsed(),
+      tls_version: None,
+    };
+    let request = Request::raw(set_uri_scheme("tls", &uri)?, vec![], false);
+    let mut response = handshake.into_response(request, connection);
+    if let Some(fingerprint) = &self.tls_fingerprint {
+      response.extensions_mut().insert(fingerprint.clone());
+    }
+    debug!("{}{:#?}", Emoji("📥", ""), response);
+    Ok(response)
+  }
+}
+
+impl ClusterExecuteRunner {
+  // 计算目标的JARM和JA3S指纹，每个目标只计算一次
+  async fn fingerprint_tls(&mut self, config: &ObserverWardConfig) {
+    if self.tls_fingerprint.is_some() {
+      return;
+    }
+    let host = self.target.host().unwrap_or_default().to_string();
+    let port = self.target.port_u16().unwrap_or(443);
+    let Ok(uri) = format!("tcp://{host}:{port}").parse::<Uri>() else {
+      return;
+    };
+    let timeout = Duration::from_secs(config.timeout / 2);
+    let Ok(connector) = config
+      .tcp_client_builder()
+      .read_timeout(Some(timeout))
+      .write_timeout(Some(timeout))
+      .build()
+    else {
+      return;
+    };
+    let server_name = host.trim_start_matches('[').trim_end_matches(']');
+    self.tls_fingerprint = tls_fingerprint(&connector, &uri, server_name).await;
+    debug!(
+      "{}tls fingerprint: {:?}",
+      Emoji("🔏", ""),
+      self.tls_fingerprint
+    );
+  }
+}
+
+// 处理websocket的探针
+impl ClusterExecuteRunner {
+  async fn websocket(
+    &mut self,
+    config: &ObserverWardConfig,
+    cluster: &ClusterExecute,
+  ) -> Result<bool> {
+    if let Some(flow) = &cluster.flow {
+      let client = config
+        .fallback_http_client(self.tls_backend_cache.clone())
+        .client_for_backend(TlsBackend::Rustls);
+      let mut http_record = HttpRecord::new(client);
+      return Ok(self.flow(config, cluster, flow, &mut http_record).await);
+    }
+    let mut flag = false;
+    let mut variables = cluster.variables.clone();
+    for websocket in cluster.requests.websocket.iter() {
+      for (request, response) in self.send_websocket(config, websocket, &variables).await? {
+        let mut result = MatchEvent::new(&response);
+        cluster
+          .operators
+          .iter()
+          .for_each(|operator| operator.matcher(&mut result, false));
+        variables.extend(result.variables().clone());
+        if !result.matcher_result().is_empty() {
+          flag = true;
+          self.update_result(result, Some(request.uri().to_string()));
+        }
+      }
+    }
+    Ok(flag)
+  }
+  // 握手之后按顺序发送全部输入，再读取数据帧，每组payload使用单独的连接
+  async fn send_websocket(
+    &self,
+    config: &ObserverWardConfig,
+    websocket: &WebSocketRequest,
+    variables: &BTreeMap<String, String>,
+  ) -> Result<Vec<(Request, Response)>> {
+    let mut responses = Vec::new();
+    let mut marker = Marker::with_target(&self.target);
+    marker.extend_resolved(variables);
+    let timeout = Duration::from_secs(config.timeout / 2);
+    let connector = config
+      .tcp_client_builder()
+      .read_timeout(Some(timeout))
+      .write_timeout(Some(timeout))
+      .build()?;
+    for payload in Payload::generate(websocket.payload_attack.as_ref()) {
+      let marker = marker.with_payload(&payload);
+      let uri = websocket
+        .address(&marker)
+        .ok_or_else(|| new_io_error("invalid websocket address"))?;
+      let key = websocket_key();
+      let data = websocket.handshake(&uri, &marker, &key);
+      let request = Request::raw(uri.clone(), data.clone(), true);
+      debug!("{}{:#?}", Emoji("📤", ""), request);
+      let start = Instant::now();
+      let mut socket = connector.connect_with_uri(&uri).await?;
+      let ip = match &socket.inner {
+        StreamWrapper::Tcp(stream) => stream.peer_addr().ok().map(|addr| addr.ip()),
+        _ => None,
+      };
+      socket.write_all(&data).await?;
+      let mut full = Vec::new();
+      let mut buffer = vec![0; 4096];
+      // 读取握手响应的头部
+      let (handshake, rest) = loop {
+        if let Some((handshake, rest)) = WebSocketHandshake::parse(&full) {
+          break (handshake, rest.to_vec());
+        }
+        match socket.read(&mut buffer).await {
+          Ok(n) if n > 0 && full.len() < 65536 => full.extend_from_slice(&buffer[..n]),
+          _ => return Err(new_io_error("invalid websocket handshake response")),
+        }
+      };
+      let mut frames = Vec::new();
+      if handshake.is_upgraded(&key) {
+        for input in websocket.inputs.iter() {
+          let frame = WebSocketFrame::new(Opcode::Text, input.data(&marker));
+          socket.write_all(&frame.encode()).await?;
+        }
+        read_frames(&mut socket, rest, websocket.read_frames(), &mut frames).await;
+        // 尽量正常关闭连接
+        let close = WebSocketFrame::new(Opcode::Close, vec![]);
+        socket.write_all(&close.encode()).await.unwrap_or_default();
+      }
+      let connection = ConnectionInfo {
+        ip,
+        duration: start.elapsed(),
+        tls_version: None,
+      };
+      let mut response = handshake.into_response(request.clone(), &frames, connection);
+      response.extensions_mut().insert(payload.clone());
+      response.extensions_mut().insert(marker.clone());
+      debug!("{}{:#?}", Emoji("📥", ""), response);
+      responses.push((request, response));
+    }
+    Ok(responses)
+  }
+}
+
+// 读取数据帧直到足够数量、服务端关闭或者超时，回复ping，分片的帧合并成一个
+async fn read_frames(
+  socket: &mut engine::slinger::Socket,
+  mut data: Vec<u8>,
+  count: usize,
+  frames: &mut Vec<WebSocketFrame>,
+) {
+  let mut buffer = vec![0; 4096];
+  while frames.len() < count {
+    while let Some((frame, size)) = WebSocketFrame::decode(&data) {
+      data.drain(..size);
+      match frame.opcode {
+        Opcode::Text | Opcode::Binary => frames.push(frame),
+        Opcode::Continuation => {
+          if let Some(last) = frames.last_mut() {
+            last.payload.extend_from_slice(&frame.payload);
+          }
+        }
+        Opcode::Ping => {
+          let pong = WebSocketFrame::new(Opcode::Pong, frame.payload);
+          socket.write_all(&pong.encode()).await.unwrap_or_default();
+        }
+        Opcode::Close => return,
+        _ => {}
+      }
+    }
+    if frames.len() >= count {
+      break;
+    }
+    match socket.read(&mut buffer).await {
+      Ok(n) if n > 0 => data.extend_from_slice(&buffer[..n]),
+      _ => break,
+    }
+  }
+  frames.truncate(count);
+}
+
+// 处理dns的探针
+impl ClusterExecuteRunner {
+  async fn dns(&mut self, config: &ObserverWardConfig, cluster: &ClusterExecute) -> Result<bool> {
+    if let Some(flow) = &cluster.flow {
+      let client = config
+        .fallback_http_client(self.tls_backend_cache.clone())
+        .client_for_backend(TlsBackend::Rustls);
+      let mut http_record = HttpRecord::new(client);
+      return Ok(self.flow(config, cluster, flow, &mut http_record).await);
+    }
+    let mut flag = false;
+    for dns in cluster.requests.dns.iter() {
+      let response = self.send_dns(config, dns, &cluster.variables).await?;
+      let mut result = MatchEvent::new(&response);
+      cluster
+        .operators
+        .iter()
+        .for_each(|operator| operator.matcher(&mut result, false));
+      if !result.matcher_result().is_empty() {
+        flag = true;
+        self.update_result(result, Some(self.target.to_string()));
+      }
+    }
+    Ok(flag)
+  }
+  // 依次向解析服务器查询，没有应答的时候重试，应答转换成可以匹配的响应
+  async fn send_dns(
+    &self,
+    config: &ObserverWardConfig,
+    dns: &DNSRequest,
+    variables: &BTreeMap<String, String>,
+  ) -> Result<Response> {
+    let mut marker = Marker::with_target(&self.target);
+    marker.extend_resolved(variables);
+    let mut resolvers = dns.resolvers(&marker);
+    if resolvers.is_empty() {
+      resolvers = dns::system_resolvers();
+    }
+    let timeout = Duration::from_secs(config.timeout / 2);
+    let mut error = new_io_error("no dns resolver");
+    for _ in 0..=dns.retries() {
+      for resolver in resolvers.iter() {
+        let query = dns.query(&marker);
+        debug!("{}{}", Emoji("📤", ""), query);
+        let start = Instant::now();
+        let (message, addr) = match dns::exchange(resolver, &query, timeout).await {
+          Ok(answer) => answer,
+          Err(err) => {
+            debug!("{}{}: {}", Emoji("💢", ""), resolver, err);
+            error = err;
+            continue;
+          }
+        };
+        let connection = ConnectionInfo {
+          ip: Some(addr.ip()),
+          duration: start.elapsed(),
+          tls_version: None,
+        };
+        let uri: Uri = format!("dns://{addr}")
+          .parse()
+          .map_err(|_| new_io_error(&format!("invalid dns resolver: {addr}")))?;
+        let request = Request::raw(uri, query.to_string(), false);
+        let response = message.into_response(request, connection);
+        debug!("{}{:#?}", Emoji("📥", ""), response);
+        return Ok(response);
+      }
+    }
+    Err(error)
+  }
+}
+
+// ssl探针需要自己握手，只接受没有升级的tcp连接
+fn tcp_stream(socket: engine::slinger::Socket) -> Result<tokio::net::TcpStream> {
+  match socket.inner {
+    StreamWrapper::Tcp(stream) => Ok(stream),
+    _ => Err(new_io_error("expected plain tcp stream for ssl handshake")),
+  }
 }
 
 // 处理code的探针
@@ -709,6 +1531,7 @@ impl ObserverWard {
     runner.http_record = Some(Arc::new(http_record));
   }
   // 根据端口优先选择探针
+  // 和nmap的服务识别一样，null探针之后按稀有度发送其他探针
   async fn tcp(&self, runner: &mut ClusterExecuteRunner) {
     let (mut include, mut exclude) = (Vec::new(), Vec::new());
     let port = if let Some(port) = runner.target.port_u16() {
@@ -722,29 +1545,149 @@ impl ObserverWard {
       } else {
         continue;
       };
-      if let Some(pr) = port_range {
-        if pr.contains(port) {
-          include.push(clusters);
-        } else {
-          exclude.push(clusters);
-        }
-      } else {
+      // nmap的sslports也算是端口对应的探针
+      let ssl_port = clusters
+        .requests
+        .is_tcp()
+        .and_then(|tcp| tcp.ssl_port.as_ref());
+      if ssl_port.is_some_and(|pr| pr.contains(port))
+        || port_range.as_ref().is_some_and(|pr| pr.contains(port))
+      {
+        include.push(clusters);
+      } else if clusters.rarity <= self.config.intensity {
+        // 端口对应的探针不受强度限制，其他探针只发送稀有度不超过强度的
         exclude.push(clusters);
       }
     }
-    include.sort_by(|x, y| x.rarity.cmp(&y.rarity));
-    exclude.sort_by(|x, y| x.rarity.cmp(&y.rarity));
-    // 先跑有匹配到端口的，如果有匹配到就不跑其他的冷门指纹
+    include.sort_by_key(|x| x.rarity);
+    exclude.sort_by_key(|x| x.rarity);
+    // 先跑有匹配到端口的，识别到服务之后就不跑其他的冷门指纹
     // TODO： 可以考虑加个多线程
-    for clusters in include {
-      if let Ok(flag) = runner.tcp(&self.config, clusters).await
-        && flag
+    for clusters in include.into_iter().chain(exclude) {
+      // 响应是TLS的时候明文的探针都不会有结果了
+      if runner.service_matched || runner.tls_detected {
+        break;
+      }
+      // softmatch之后只发送有这个服务规则的探针
+      if let Some(service) = &runner.softmatch
+        && !clusters
+          .operators
+          .iter()
+          .any(|op| &op.info().name == service)
       {
+        continue;
+      }
+      let fallback = self.tcp_fallback(clusters);
+      runner
+        .tcp(&self.config, clusters, &fallback)
+        .await
+        .unwrap_or_default();
+    }
+  }
+  // 服务识别，tcp目标的响应是TLS的时候，握手之后在隧道里面重新识别，结果使用tls协议
+  async fn tcp_service(&self, runner: &mut ClusterExecuteRunner) -> Result<()> {
+    loop {
+      if runner.target.scheme_str() == Some("tls") {
+        self.fingerprint_tls(runner).await;
+        self.ssl(runner).await;
+      }
+      if let Some(tcp) = &self.cluster_type.tcp_default {
+        runner.tcp(&self.config, tcp, &[]).await?;
+      }
+      self.tcp(runner).await;
+      if !runner.tls_detected {
+        return Ok(());
+      }
+      debug!("{}tls detected: {}", Emoji("🔐", ""), runner.target);
+      runner.target = set_uri_scheme("tls", &runner.target)?;
+      runner.tls_detected = false;
+      runner.softmatch = None;
+      runner.service_matched = false;
+    }
+  }
+  // 探针指定的fallback探针，null探针的规则所有tcp探针都会使用
+  fn tcp_fallback(&self, clusters: &ClusterExecute) -> Vec<&Arc<ClusterExecute>> {
+    let mut fallback = Vec::new();
+    if let Some(tcp) = clusters.requests.is_tcp() {
+      for name in tcp.fallback.iter() {
+        if let Some(c) = self.cluster_type.tcp_other.get(name) {
+          fallback.push(c);
+        }
+      }
+    }
+    if let Some(null) = &self.cluster_type.tcp_default {
+      fallback.push(null);
+    }
+    fallback
+  }
+  // 先发送端口对应的探针，没有指定端口的探针所有端口都发送
+  async fn udp(&self, runner: &mut ClusterExecuteRunner) {
+    let Some(port) = runner.target.port_u16() else {
+      return;
+    };
+    let (mut include, mut other) = (Vec::new(), Vec::new());
+    for clusters in self.cluster_type.udp.iter() {
+      match clusters.requests.is_udp().and_then(|udp| udp.port.as_ref()) {
+        Some(pr) if pr.contains(port) => include.push(clusters),
+        Some(_) => {}
+        None => other.push(clusters),
+      }
+    }
+    include.sort_by_key(|x| x.rarity);
+    other.sort_by_key(|x| x.rarity);
+    for clusters in include.into_iter().chain(other) {
+      match runner.udp(&self.config, clusters).await {
+        Ok(true) => break,
+        Ok(false) => {}
+        Err(err) => {
+          debug!("{}:{}", Emoji("💢", ""), err);
+          // 端口不可达的目标不用再试其他探针
+          break;
+        }
+      }
+    }
+  }
+  // 每个目标计算TLS指纹需要十几次握手，只有加载了jarm匹配器或者指定了--jarm才计算
+  async fn fingerprint_tls(&self, runner: &mut ClusterExecuteRunner) {
+    if self.cluster_type.jarm || self.config.jarm {
+      runner.fingerprint_tls(&self.config).await;
+    }
+  }
+  // 证书和TLS参数的指纹，所有ssl探针都使用同一个目标
+  async fn ssl(&self, runner: &mut ClusterExecuteRunner) {
+    for clusters in self.cluster_type.ssl.iter() {
+      if let Err(err) = runner.ssl(&self.config, clusters).await {
+        debug!("{}:{}", Emoji("💢", ""), err);
+        // 握手失败的目标不用再试其他探针
         break;
       }
     }
-    for clusters in exclude {
-      runner.tcp(&self.config, clusters).await.unwrap_or_default();
+  }
+  // websocket端点的指纹，只对web目标发送
+  async fn websocket(&self, runner: &mut ClusterExecuteRunner) {
+    for clusters in self.cluster_type.websocket.iter() {
+      if let Err(err) = runner.websocket(&self.config, clusters).await {
+        debug!("{}:{}", Emoji("💢", ""), err);
+      }
+    }
+  }
+  // 域名的dns记录指纹，目标是IP地址时没有可以查询的记录
+  async fn dns(&self, runner: &mut ClusterExecuteRunner) {
+    let Some(host) = runner.target.host() else {
+      return;
+    };
+    if host
+      .trim_start_matches('[')
+      .trim_end_matches(']')
+      .parse::<IpAddr>()
+      .is_ok()
+    {
+      return;
+    }
+    for clusters in self.cluster_type.dns.iter() {
+      if let Err(err) = runner.dns(&self.config, clusters).await {
+        debug!("{}:{}", Emoji("💢", ""), err);
+      }
     }
   }
   pub async fn run(&self, target: Uri) -> FingerprintResult {
@@ -759,43 +1702,60 @@ impl ObserverWard {
             self.handle_http_mode(&mut runner, &target).await;
           }
           Mode::TCP => self.handle_tcp_mode(&mut runner, &target).await,
+          Mode::UDP => self.handle_udp_mode(&mut runner, &target).await,
           Mode::HTTP => self.handle_http_mode(&mut runner, &target).await,
         }
       }
       // 只跑web指纹
-      Some("http") | Some("https") => {
+      Some("http") => {
         self.http(&mut runner).await;
+        self.websocket(&mut runner).await;
+      }
+      Some("https") => {
+        self.fingerprint_tls(&mut runner).await;
+        self.http(&mut runner).await;
+        self.ssl(&mut runner).await;
+        self.websocket(&mut runner).await;
       }
       // 只跑服务指纹
       Some("tcp") | Some("tls") => {
-        if let Some(tcp) = &self.cluster_type.tcp_default
-          && let Err(_err) = runner.tcp(&self.config, tcp).await
-        {
-          return FingerprintResult {
-            task_id: None,
-            target: target.to_string(),
-            matched: runner
-              .matched_result
-              .into_iter()
-              .map(|(k, v)| MatchedEntry {
-                base_url: k,
-                result: v,
-              })
-              .collect(),
-            success: true,
-            record: None,
-            error: None,
-          };
+        // 切换到tls之后可能已经有证书等结果，失败也要走后面的统一处理
+        if let Err(err) = self.tcp_service(&mut runner).await {
+          debug!("{}:{}", Emoji("💢", ""), err);
         }
-        self.tcp(&mut runner).await;
       }
+      Some("udp") => {
+        self.udp(&mut runner).await;
+      }
+      // 只跑dns指纹
+      Some("dns") => {}
       // 跳过
       _ => {}
     }
+    self.dns(&mut runner).await;
     // 执行code类型的模板
     #[cfg(feature = "code")]
     self.code_execute(&mut runner);
+    runner
+      .matched_result
+      .values_mut()
+      .for_each(|mr| mr.apply_relations(&self.cluster_type.infos));
+    // 只输出指定分类的指纹
+    let only_category = self.config.only_category();
+    if !only_category.is_empty() {
+      runner.matched_result.retain(|_, mr| {
+        mr.retain_category(&only_category);
+        !mr.fingerprint().is_empty()
+      });
+    }
     runner.use_nuclei(&self.config);
+    // 使用本地的NVD数据关联漏洞，不需要联网
+    if let Some(nvd) = self.config.nvd_database() {
+      runner
+        .matched_result
+        .values_mut()
+        .for_each(|mr| mr.correlate_vulnerabilities(&nvd));
+    }
     runner.matched_result.values_mut().for_each(|mr| {
       if !self.config.ic {
         mr.certificate = None;
@@ -832,23 +1792,32 @@ impl ObserverWard {
     for scheme in schemes {
       if let Ok(http_target) = set_uri_scheme(scheme, target) {
         runner.target = http_target;
+        if scheme == "https" {
+          self.fingerprint_tls(runner).await;
+        }
         self.http(runner).await;
         if !runner.matched_result.is_empty() {
+          if scheme == "https" {
+            self.ssl(runner).await;
+          }
+          self.websocket(runner).await;
           break;
         }
       }
     }
   }
 
+  async fn handle_udp_mode(&self, runner: &mut ClusterExecuteRunner, target: &Uri) {
+    if let Ok(udp_target) = set_uri_scheme("udp", target) {
+      runner.target = udp_target;
+      self.udp(runner).await;
+    }
+  }
+
   async fn handle_tcp_mode(&self, runner: &mut ClusterExecuteRunner, target: &Uri) {
     if let Ok(tcp_target) = set_uri_scheme("tcp", target) {
       runner.target = tcp_target;
-      if let Some(tcp) = &self.cluster_type.tcp_default
-        && let Err(_err) = runner.tcp(&self.config, tcp).await
-      {
-        return;
-      }
-      self.tcp(runner).await;
+      self.tcp_service(runner).await.unwrap_or_default();
     }
   }
 
@@ -859,3 +1828,308 @@ impl ObserverWard {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use argh::FromArgs;
+  use engine::template::cluster::cluster_templates;
+  use engine::template::nmap::parse_service_probes;
+  use std::sync::Mutex;
+  use tokio::io::{AsyncReadExt, AsyncWriteExt};
+  use tokio::net::TcpListener;
+
+  type Received = Arc<Mutex<Vec<Vec<u8>>>>;
+
+  // 每个连接先发送banner，收到探针后返回应答并关闭连接，记录收到的探针
+  async fn serve_tcp(
+    listener: TcpListener,
+    banner: &'static [u8],
+    reply: &'static [u8],
+  ) -> Received {
+    let received = Received::default();
+    let log = received.clone();
+    tokio::spawn(async move {
+      while let Ok((mut stream, _)) = listener.accept().await {
+        let log = log.clone();
+        tokio::spawn(async move {
+          let _ = stream.write_all(banner).await;
+          let mut buf = vec![0; 1024];
+          // null探针不发送数据，等一会没有数据就关闭连接
+          if let Ok(Ok(n)) =
+            tokio::time::timeout(Duration::from_millis(300), stream.read(&mut buf)).await
+            && n > 0
+          {
+            log.lock().unwrap().push(buf[..n].to_vec());
+            let _ = stream.write_all(reply).await;
+          }
+        });
+      }
+    });
+    received
+  }
+
+  // 使用nmap格式的探针识别本地服务，返回命中的模板
+  async fn identify(
+    probes: &str,
+    args: &[&str],
+    banner: &'static [u8],
+    reply: &'static [u8],
+  ) -> (HashSet<String>, Vec<Vec<u8>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let target: Uri = format!("tcp://{}", listener.local_addr().unwrap())
+      .parse()
+      .unwrap();
+    let received = serve_tcp(listener, banner, reply).await;
+    // Default会解析测试进程的命令行参数
+    let config = ObserverWardConfig::from_args(&["observer_ward"], args).unwrap();
+    let templates = parse_service_probes(probes).unwrap();
+    let ward = ObserverWard::new(&config, cluster_templates(&templates, false));
+    let mut runner = ClusterExecuteRunner::new(&target);
+    ward.tcp_service(&mut runner).await.unwrap();
+    let matched = runner
+      .matched_result
+      .values()
+      .flat_map(|mr| mr.fingerprint())
+      .flat_map(|event| event.name())
+      .collect();
+    let received = received.lock().unwrap().clone();
+    (matched, received)
+  }
+
+  #[tokio::test]
+  async fn softmatch_narrows_probes() {
+    let probes = r#"
+Probe TCP NULL q||
+totalwaitms 1000
+softmatch ftp m|^220[- ]|
+Probe TCP GetRequest q|GET / HTTP/1.0\r\n\r\n|
+rarity 1
+match http m|^220 stub\r\n|
+Probe TCP Help q|HELP\r\n|
+rarity 2
+match ftp m|^220 stub\r\n214 vsFTPd ([\d.]+)| p/vsftpd/ v/$1/
+"#;
+    let (matched, received) = identify(probes, &[], b"220 stub\r\n", b"214 vsFTPd 3.0.3\r\n").await;
+    assert!(matched.contains("nmap-tcp-null-0"));
+    assert!(matched.contains("nmap-tcp-help-0"));
+    // softmatch之后没有ftp规则的探针不会发送，即使它的稀有度更低
+    assert!(!matched.contains("nmap-tcp-getrequest-0"));
+    assert_eq!(received, vec![b"HELP\r\n".to_vec()]);
+  }
+
+  #[tokio::test]
+  async fn fallback_and_rarity() {
+    let probes = r#"
+Probe TCP GetRequest q|GET / HTTP/1.0\r\n\r\n|
+rarity 8
+match http m|^HTTP/1\.0 200 OK\r\nServer: stub| p/stub/
+Probe TCP GenericLines q|\r\n\r\n|
+rarity 1
+fallback GetRequest
+match never m|^NEVER|
+"#;
+    let reply = b"HTTP/1.0 200 OK\r\nServer: stub\r\n\r\n";
+    // 稀有度超过强度的探针不发送，fallback探针的规则匹配当前探针的响应
+    let (matched, received) = identify(probes, &[], b"", reply).await;
+    assert_eq!(
+      matched,
+      HashSet::from(["nmap-tcp-getrequest-0".to_string()])
+    );
+    assert_eq!(received, vec![b"\r\n\r\n".to_vec()]);
+    // 没有fallback的时候识别不到
+    let without_fallback = probes.replace("fallback GetRequest\n", "");
+    let (matched, received) = identify(&without_fallback, &[], b"", reply).await;
+    assert!(matched.is_empty());
+    assert_eq!(received, vec![b"\r\n\r\n".to_vec()]);
+    // 提高强度之后才会发送
+    let (matched, received) = identify(&without_fallback, &["--intensity", "9"], b"", reply).await;
+    assert!(matched.contains("nmap-tcp-getrequest-0"));
+    assert!(received.contains(&b"GET / HTTP/1.0\r\n\r\n".to_vec()));
+  }
+
+  const UDP_TEMPLATE: &str = r#"
+id: udp-stub
+info:
+  name: udp-stub
+  author: test
+  severity: info
+udp:
+  - inputs:
+      - data: "ping"
+      - data: "version"
+    wait: 300
+    retransmits: 2
+    matchers:
+      - type: word
+        words:
+          - "pong"
+    extractors:
+      - type: regex
+        name: version
+        group: 1
+        regex:
+          - "stub/([0-9.]+)"
+"#;
+
+  fn udp_cluster() -> Arc<ClusterExecute> {
+    let template: engine::template::Template = serde_yaml::from_str(UDP_TEMPLATE).unwrap();
+    cluster_templates(&[template], false).udp[0].clone()
+  }
+
+  #[tokio::test]
+  async fn udp_retransmits_dropped_datagram() {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let target: Uri = format!("udp://{}", socket.local_addr().unwrap())
+      .parse()
+      .unwrap();
+    // 丢掉第一个数据报，之后的数据报都有应答
+    let server = tokio::spawn(async move {
+      let mut buf = [0; 64];
+      let mut arrived = Vec::new();
+      while let Ok(Ok((n, peer))) =
+        tokio::time::timeout(Duration::from_secs(2), socket.recv_from(&mut buf)).await
+      {
+        arrived.push((buf[..n].to_vec(), Instant::now()));
+        if arrived.len() > 1 {
+          let reply: &[u8] = if &buf[..n] == b"ping" {
+            b"pong"
+          } else {
+            b"stub/1.2.3"
+          };
+          socket.send_to(reply, peer).await.unwrap();
+        }
+      }
+      arrived
+    });
+    let config = ObserverWardConfig::from_args(&["observer_ward"], &[]).unwrap();
+    let mut runner = ClusterExecuteRunner::new(&target);
+    assert!(runner.udp(&config, &udp_cluster()).await.unwrap());
+    let arrived = server.await.unwrap();
+    let inputs: Vec<&[u8]> = arrived.iter().map(|(data, _)| data.as_slice()).collect();
+    assert_eq!(inputs, vec![&b"ping"[..], b"ping", b"version"]);
+    // 每个数据报等待wait之后才重发
+    assert!(arrived[1].1 - arrived[0].1 >= Duration::from_millis(300));
+    let result = runner.matched_result.values().next().unwrap();
+    assert!(
+      result
+        .fingerprint()
+        .iter()
+        .any(|event| event.name().contains("udp-stub"))
+    );
+  }
+
+  #[tokio::test]
+  async fn udp_keeps_responses_before_unreachable() {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let target: Uri = format!("udp://{}", socket.local_addr().unwrap())
+      .parse()
+      .unwrap();
+    // 应答第一个输入之后关闭端口，后面的输入会收到端口不可达
+    tokio::spawn(async move {
+      let mut buf = [0; 64];
+      let (_, peer) = socket.recv_from(&mut buf).await.unwrap();
+      socket.send_to(b"pong", peer).await.unwrap();
+    });
+    let runner = ClusterExecuteRunner::new(&target);
+    let cluster = udp_cluster();
+    let udp = cluster.requests.is_udp().unwrap();
+    let responses = runner.send_udp(udp, &BTreeMap::new()).await.unwrap();
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].1.text().unwrap(), "pong");
+    // 一个应答都没有的时候返回错误
+    assert!(runner.send_udp(udp, &BTreeMap::new()).await.is_err());
+  }
+
+  // 自签名证书的TLS服务，握手之后按HTTP应答
+  async fn serve_tls(listener: TcpListener) {
+    use tokio_rustls::rustls::ServerConfig;
+    use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+    let key = rcgen::KeyPair::generate().unwrap();
+    let params = rcgen::CertificateParams::new(vec!["stub.example".to_string()]).unwrap();
+    let cert = params.self_signed(&key).unwrap();
+    let provider = Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+    let config = ServerConfig::builder_with_provider(provider)
+      .with_safe_default_protocol_versions()
+      .unwrap()
+      .with_no_client_auth()
+      .with_single_cert(
+        vec![CertificateDer::from(cert.der().to_vec())],
+        PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der())),
+      )
+      .unwrap();
+    let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+    while let Ok((stream, _)) = listener.accept().await {
+      let acceptor = acceptor.clone();
+      tokio::spawn(async move {
+        let Ok(mut stream) = acceptor.accept(stream).await else {
+          return;
+        };
+        let mut buf = vec![0; 1024];
+        if stream.read(&mut buf).await.unwrap_or_default() > 0 {
+          let _ = stream
+            .write_all(b"HTTP/1.0 200 OK\r\nServer: stub-tls\r\n\r\n")
+            .await;
+          let _ = stream.shutdown().await;
+        }
+      });
+    }
+  }
+
+  #[tokio::test]
+  async fn jarm_of_local_rustls() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve_tls(listener));
+    let config = ObserverWardConfig::from_args(&["observer_ward"], &[]).unwrap();
+    let mut runner = ClusterExecuteRunner::new(&format!("https://{addr}/").parse().unwrap());
+    runner.fingerprint_tls(&config).await;
+    let fingerprint = runner.tls_fingerprint.unwrap();
+    // TLS1.1、只有冷门密码套件和没有TLS1.3密码套件的三个探针握手失败
+    assert_eq!(
+      fingerprint.jarm,
+      "27d40d40d00040d00042d43d00041d72fdadea3cae0ac3d9c87ac8f63ec17a"
+    );
+    // md5("771,49195,65281-23")，ECDHE-ECDSA-AES128-GCM-SHA256
+    assert_eq!(
+      fingerprint.ja3s.as_deref(),
+      Some("4ce0442e9d20d092d9d74e9ce54b363a")
+    );
+  }
+
+  #[tokio::test]
+  async fn tls_service_outside_sslports() {
+    let probes = r#"
+Probe TCP GetRequest q|GET / HTTP/1.0\r\n\r\n|
+rarity 1
+ports 80
+sslports 443
+match http m|^HTTP/1\.0 200 OK\r\nServer: stub-tls| p/stub/
+"#;
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve_tls(listener));
+    let config = ObserverWardConfig::from_args(&["observer_ward"], &[]).unwrap();
+    let templates = parse_service_probes(probes).unwrap();
+    let ward = ObserverWard::new(&config, cluster_templates(&templates, false));
+    let mut runner = ClusterExecuteRunner::new(&format!("tcp://{addr}").parse().unwrap());
+    ward.tcp_service(&mut runner).await.unwrap();
+    // 明文探针收到TLS记录之后，切换到tls在隧道里面重新识别
+    let tls_target = format!("tls://{addr}");
+    assert_eq!(runner.target.to_string().trim_end_matches('/'), tls_target);
+    let result = runner
+      .matched_result
+      .iter()
+      .find(|(key, _)| key.trim_end_matches('/') == tls_target)
+      .map(|(_, result)| result)
+      .unwrap();
+    assert!(
+      result
+        .fingerprint()
+        .iter()
+        .any(|event| event.name().contains("nmap-tcp-getrequest-0"))
+    );
+    let certificate = result.certificate.as_ref().unwrap();
+    assert_eq!(certificate.dns_names(), vec!["stub.example"]);
+  }
+}
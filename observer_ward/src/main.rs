@@ -103,8 +103,11 @@ async fn main() {
     templates = config.templates();
   }
   info!("{}probes loaded: {}", Emoji("📇", ""), templates.len());
-  let cl = cluster_templates(&templates,config.index);
+  let cl = cluster_templates(&templates, config.index);
   info!("{}optimized probes: {}", Emoji("🚀", ""), cl.count());
+  if let Some(nvd) = config.nvd_database() {
+    info!("{}nvd products loaded: {}", Emoji("🛡️", ""), nvd.len());
+  }
 
   // Check for asynq mode
   #[cfg(feature = "asynq_task")]
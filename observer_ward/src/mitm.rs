@@ -164,6 +164,7 @@ impl engine::slinger_mitm::Interceptor for FingerprintInterceptor {
           debug!("{}Match found for: {}", Emoji("✅", ""), target);
           let mut matched_result = MatchedResult::default();
           matched_result.update_matched(&result);
+          matched_result.apply_relations(&cluster_type.infos);
           let entry = crate::MatchedEntry {
             base_url: target.to_string(),
             result: matched_result,
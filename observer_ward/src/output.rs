@@ -2,7 +2,7 @@ use crate::cli::{ObserverWardConfig, OutputFormat};
 use crate::{FingerprintResult, MatchedEntry};
 use console::{Emoji, style};
 use engine::slinger::http::header;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::fs::File;
 use std::io::{BufWriter, Write};
 
@@ -42,7 +42,11 @@ impl Output {
       }
     };
     if let OutputFormat::CSV = output_format {
-      writeln!(writer, "url,name,title,length,status_code,nuclei,extractor").unwrap_or_default();
+      writeln!(
+        writer,
+        "url,name,category,title,length,status_code,nuclei,extractor"
+      )
+      .unwrap_or_default();
     }
     Self {
       config: config.clone(),
@@ -121,9 +125,14 @@ impl Output {
             .join(";");
           writeln!(
             self.writer,
-            "{ },\"{}\",\"{}\",{},{},\"{}\",\"{}\"",
+            "{ },\"{}\",\"{}\",\"{}\",{},{},\"{}\",\"{}\"",
             uri,
             app.join(";").trim(),
+            mr.category()
+              .iter()
+              .cloned()
+              .collect::<Vec<String>>()
+              .join(";"),
             set_to_string(&mr.title),
             mr.length,
             mr.status.map_or(0, |x| x.as_u16()),
@@ -196,7 +205,35 @@ fn write_to_buf(writer: &mut BufWriter<dyn Write>, results: &[MatchedEntry]) {
         continue;
       }
       write!(writer, "{}:[ {}", Emoji("🎯", "uri"), uri).unwrap_or_default();
-      write!(writer, " [{}] ", style(set_to_string(&apps)).green()).unwrap_or_default();
+      // 按分类分组打印指纹，没有分类的指纹排在最前面
+      let mut groups: BTreeMap<String, HashSet<String>> = BTreeMap::new();
+      for x in fp.matcher_result() {
+        if x.category.is_empty() {
+          groups
+            .entry(String::new())
+            .or_default()
+            .insert(x.info.name.clone());
+        }
+        for c in x.category.iter() {
+          groups
+            .entry(c.clone())
+            .or_default()
+            .insert(x.info.name.clone());
+        }
+      }
+      for (category, apps) in groups.iter() {
+        if category.is_empty() {
+          write!(writer, " [{}] ", style(set_to_string(apps)).green()).unwrap_or_default();
+        } else {
+          write!(
+            writer,
+            " [{}: {}] ",
+            style(category).cyan(),
+            style(set_to_string(apps)).green()
+          )
+          .unwrap_or_default();
+        }
+      }
       write!(writer, " <{}>", set_to_string(mr.title())).unwrap_or_default();
       write!(writer, " <{}>", mr.length).unwrap_or_default();
       if let Some(csc) = &osc {
@@ -248,6 +285,29 @@ fn write_to_buf(writer: &mut BufWriter<dyn Write>, results: &[MatchedEntry]) {
       }
       all_app.extend(apps);
     }
+    // 本地NVD数据关联到的漏洞
+    for technology in mr.technologies().values() {
+      if technology.vulnerabilities.is_empty() {
+        continue;
+      }
+      writeln!(
+        writer,
+        " |_{}: {} {} [{}]",
+        Emoji("🔓", "vulnerability"),
+        style(&technology.name).green(),
+        style(technology.version.as_deref().unwrap_or_default()).yellow(),
+        technology
+          .vulnerabilities
+          .iter()
+          .map(|v| match v.score {
+            Some(score) => format!("{}({})", v.id, score),
+            None => v.id.clone(),
+          })
+          .collect::<Vec<String>>()
+          .join(",")
+      )
+      .unwrap_or_default();
+    }
     if mr.fingerprint().is_empty() {
       write!(writer, "{}:[ {}", Emoji("🎯", "uri"), uri).unwrap_or_default();
       if !mr.title().is_empty() {
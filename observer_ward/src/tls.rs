@@ -1,10 +1,25 @@
+use crate::error::{Result, new_io_error};
+use engine::common::jarm::{JARM_READ_SIZE, TlsFingerprint, client_hellos};
+use engine::request::{TlsHandshake, TlsVersion};
 use engine::slinger::http::Uri;
 use engine::slinger::tls::{CustomTlsConnector, CustomTlsStream, PeerCertificate};
-use engine::slinger::{Client, Request, RequestBuilder, Response, Socket, StreamWrapper};
+use engine::slinger::{
+  Client, Connector, Request, RequestBuilder, Response, Socket, StreamWrapper,
+};
 use native_tls::{Protocol, TlsConnector};
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector as RustlsConnector;
+use tokio_rustls::rustls::client::danger::{
+  HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::CryptoProvider;
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{
+  self, ClientConfig, DigitallySignedStruct, ProtocolVersion, SignatureScheme,
+  SupportedProtocolVersion,
+};
 
 struct NativeTlsStream {
   inner: tokio_native_tls::TlsStream<TcpStream>,
@@ -231,3 +246,220 @@ fn should_fallback(request: &Request, err: &engine::slinger::Error) -> bool {
     _ => false,
   }
 }
+
+// ssl探针的握手不校验证书，只记录证书和协商的参数
+#[derive(Debug)]
+struct NoVerifier(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerifier {
+  fn verify_server_cert(
+    &self,
+    _end_entity: &CertificateDer<'_>,
+    _intermediates: &[CertificateDer<'_>],
+    _server_name: &ServerName<'_>,
+    _ocsp_response: &[u8],
+    _now: UnixTime,
+  ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+    Ok(ServerCertVerified::assertion())
+  }
+
+  fn verify_tls12_signature(
+    &self,
+    _message: &[u8],
+    _cert: &CertificateDer<'_>,
+    _dss: &DigitallySignedStruct,
+  ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+    Ok(HandshakeSignatureValid::assertion())
+  }
+
+  fn verify_tls13_signature(
+    &self,
+    _message: &[u8],
+    _cert: &CertificateDer<'_>,
+    _dss: &DigitallySignedStruct,
+  ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+    Ok(HandshakeSignatureValid::assertion())
+  }
+
+  fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+    self.0.signature_verification_algorithms.supported_schemes()
+  }
+}
+
+/// 使用rustls握手，rustls只支持tls12和tls13，不在版本范围内返回错误
+pub async fn rustls_handshake(
+  stream: TcpStream,
+  server_name: Option<&str>,
+  min_version: Option<TlsVersion>,
+  max_version: Option<TlsVersion>,
+) -> Result<TlsHandshake> {
+  let versions: Vec<&'static SupportedProtocolVersion> = rustls::ALL_VERSIONS
+    .iter()
+    .copied()
+    .filter(|v| {
+      let version = tls_version(v.version);
+      version.is_some_and(|version| {
+        min_version.is_none_or(|min| version >= min) && max_version.is_none_or(|max| version <= max)
+      })
+    })
+    .collect();
+  if versions.is_empty() {
+    return Err(new_io_error("tls version is not supported by rustls"));
+  }
+  let provider = Arc::new(rustls::crypto::ring::default_provider());
+  let mut config = ClientConfig::builder_with_provider(provider.clone())
+    .with_protocol_versions(&versions)
+    .map_err(|err| new_io_error(&err.to_string()))?
+    .dangerous()
+    .with_custom_certificate_verifier(Arc::new(NoVerifier(provider)))
+    .with_no_client_auth();
+  config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+  // 没有SNI的时候用对端地址，rustls不会把IP地址作为SNI发送
+  let name = match server_name {
+    Some(name) => ServerName::try_from(name.to_string()),
+    None => Ok(ServerName::IpAddress(stream.peer_addr()?.ip().into())),
+  }
+  .map_err(|err| new_io_error(&err.to_string()))?;
+  let stream = RustlsConnector::from(Arc::new(config))
+    .connect(name, stream)
+    .await?;
+  let (_, connection) = stream.get_ref();
+  Ok(TlsHandshake {
+    version: connection.protocol_version().and_then(tls_version),
+    cipher: connection
+      .negotiated_cipher_suite()
+      .map(|suite| format!("{:?}", suite.suite())),
+    alpn: connection
+      .alpn_protocol()
+      .map(|alpn| String::from_utf8_lossy(alpn).to_string()),
+    server_name: server_name.map(|name| name.to_string()),
+    certificates: connection
+      .peer_certificates()
+      .map(|certs| certs.iter().map(|cert| cert.to_vec()).collect())
+      .unwrap_or_default(),
+    backend: Some("rustls".to_string()),
+  })
+}
+
+/// 使用native-tls握手，可以和只支持旧版本的服务握手，但是拿不到协商的版本和密码套件
+///
+/// 结果的 `tls.backend` 是 `native-tls`，匹配时可以区分版本为空是不是因为回退
+pub async fn native_tls_handshake(
+  stream: TcpStream,
+  server_name: Option<&str>,
+  min_version: Option<TlsVersion>,
+  max_version: Option<TlsVersion>,
+) -> Result<TlsHandshake> {
+  let mut builder = TlsConnector::builder();
+  builder.danger_accept_invalid_certs(true);
+  builder.danger_accept_invalid_hostnames(true);
+  builder.use_sni(server_name.is_some());
+  builder.request_alpns(&["h2", "http/1.1"]);
+  builder.min_protocol_version(Some(min_version.map_or(Protocol::Tlsv10, native_protocol)));
+  builder.max_protocol_version(max_version.map(native_protocol));
+  let connector = builder
+    .build()
+    .map_err(|err| new_io_error(&err.to_string()))?;
+  let domain = match server_name {
+    Some(name) => name.to_string(),
+    None => stream.peer_addr()?.ip().to_string(),
+  };
+  let stream = tokio_native_tls::TlsConnector::from(connector)
+    .connect(&domain, stream)
+    .await
+    .map_err(|err| new_io_error(&err.to_string()))?;
+  let inner = stream.get_ref();
+  Ok(TlsHandshake {
+    version: None,
+    cipher: None,
+    alpn: inner
+      .negotiated_alpn()
+      .ok()
+      .flatten()
+      .map(|alpn| String::from_utf8_lossy(&alpn).to_string()),
+    server_name: server_name.map(|name| name.to_string()),
+    certificates: inner
+      .peer_certificate()
+      .ok()
+      .flatten()
+      .and_then(|cert| cert.to_der().ok())
+      .into_iter()
+      .collect(),
+    backend: Some("native-tls".to_string()),
+  })
+}
+
+fn tls_version(version: ProtocolVersion) -> Option<TlsVersion> {
+  match version {
+    ProtocolVersion::SSLv3 => Some(TlsVersion::Sslv3),
+    ProtocolVersion::TLSv1_0 => Some(TlsVersion::Tls10),
+    ProtocolVersion::TLSv1_1 => Some(TlsVersion::Tls11),
+    ProtocolVersion::TLSv1_2 => Some(TlsVersion::Tls12),
+    ProtocolVersion::TLSv1_3 => Some(TlsVersion::Tls13),
+    _ => None,
+  }
+}
+
+fn native_protocol(version: TlsVersion) -> Protocol {
+  match version {
+    TlsVersion::Sslv3 => Protocol::Sslv3,
+    TlsVersion::Tls10 => Protocol::Tlsv10,
+    TlsVersion::Tls11 => Protocol::Tlsv11,
+    TlsVersion::Tls12 => Protocol::Tlsv12,
+    TlsVersion::Tls13 => Protocol::Tlsv13,
+  }
+}
+
+/// 发送JARM的十个ClientHello计算服务端的TLS指纹，每个探针使用单独的连接
+///
+/// 第一个探针连接失败或者响应不是TLS记录的时候，目标不是TLS服务，直接返回None
+pub async fn tls_fingerprint(
+  connector: &Connector,
+  uri: &Uri,
+  host: &str,
+) -> Option<TlsFingerprint> {
+  let mut responses = Vec::new();
+  for hello in client_hellos(host) {
+    let first = responses.is_empty();
+    let mut data = Vec::new();
+    match connector.connect_with_uri(uri).await {
+      Ok(mut socket) => {
+        if socket.write_all(&hello).await.is_ok() {
+          read_tls_record(&mut socket, &mut data).await;
+        }
+      }
+      Err(_) if first => return None,
+      Err(_) => {}
+    }
+    if first && !is_tls_record(&data) {
+      return None;
+    }
+    responses.push(data);
+  }
+  TlsFingerprint::from_responses(&responses)
+}
+
+/// 数据是不是TLS记录，0x15是告警，0x16是握手，后面跟着0x03开头的版本号
+///
+/// 明文的服务探针收到这样的响应，说明端口上跑的是TLS服务
+pub fn is_tls_record(data: &[u8]) -> bool {
+  matches!(data, [0x15 | 0x16, 0x03, ..])
+}
+
+// 读取第一个TLS记录，最多读取JARM_READ_SIZE字节
+async fn read_tls_record(socket: &mut Socket, data: &mut Vec<u8>) {
+  let mut buffer = [0; JARM_READ_SIZE];
+  while data.len() < JARM_READ_SIZE {
+    let size = match socket
+      .read(&mut buffer[..JARM_READ_SIZE - data.len()])
+      .await
+    {
+      Ok(size) if size > 0 => size,
+      _ => break,
+    };
+    data.extend_from_slice(&buffer[..size]);
+    if data.len() >= 5 && data.len() >= 5 + u16::from_be_bytes([data[3], data[4]]) as usize {
+      break;
+    }
+  }
+}